@@ -1,11 +1,20 @@
-use clap::Parser;
+use clap::{ArgMatches, Parser, Subcommand, ValueEnum};
+use comfy_types::Diagnostic;
+use serde_json::{json, Value};
+
+use crate::compiler::lints::{Level, Lint, Lints, LINTS};
 
 #[derive(Parser, Debug, Clone)]
 #[clap(about, version, author)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+#[command(after_help = "Lint levels are applied in order, the last -A, -W or -D of a lint wins")]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Input file
-    #[clap(short, long)]
-    pub input_file: String,
+    #[clap(short, long, required = true)]
+    pub input_file: Option<String>,
 
     /// Output file
     #[clap(short, long)]
@@ -22,4 +31,287 @@ pub struct Args {
     /// Run only translation phase, not compilation
     #[clap(long)]
     pub dry_run: bool,
+
+    /// Format of the errors
+    #[clap(long, global = true, value_enum, default_value_t = MessageFormat::Human)]
+    pub message_format: MessageFormat,
+
+    /// Do not report a lint
+    #[clap(short = 'A', long = "allow", global = true, value_name = "LINT", value_parser = lint)]
+    pub allow: Vec<Lint>,
+
+    /// Report a lint as a warning
+    #[clap(short = 'W', long = "warn", global = true, value_name = "LINT", value_parser = lint)]
+    pub warn: Vec<Lint>,
+
+    /// Report a lint as an error
+    #[clap(short = 'D', long = "deny", global = true, value_name = "LINT", value_parser = lint)]
+    pub deny: Vec<Lint>,
+}
+
+/// Levels of the lints set with `-A`, `-W` and `-D`, applied in command-line
+/// order so a later flag overrides an earlier one for the same lint
+pub fn lints(matches: &ArgMatches) -> Lints {
+    let mut levels = Vec::new();
+
+    for (level, id) in [
+        (Level::Allow, "allow"),
+        (Level::Warn, "warn"),
+        (Level::Deny, "deny"),
+    ] {
+        if let (Some(indices), Some(names)) = (matches.indices_of(id), matches.get_many::<Lint>(id))
+        {
+            levels.extend(indices.zip(names).map(|(i, lint)| (i, level, *lint)));
+        }
+    }
+
+    levels.sort_by_key(|(i, _, _)| *i);
+
+    let mut lints = Lints::default();
+
+    for (_, level, lint) in levels {
+        lints.set(lint, level);
+    }
+
+    lints
+}
+
+fn lint(name: &str) -> Result<Lint, String> {
+    Lint::from_name(name).ok_or_else(|| {
+        let names = LINTS.iter().map(Lint::name).collect::<Vec<_>>();
+
+        format!("unknown lint, expected one of {}", names.join(", "))
+    })
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum MessageFormat {
+    /// Reports with the source around the error
+    Human,
+    /// One JSON object per line
+    Json,
+}
+
+/// Line and column of a byte offset, both counted from 1, columns count characters
+pub fn line_column(src: &str, offset: usize) -> (usize, usize) {
+    let before = src.get(..offset).unwrap_or(src);
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+
+    (line, column)
+}
+
+/// Diagnostic of `--message-format=json`, `src` is the content of `file`.
+/// `code` is the name of the lint that reported it, `null` for other errors,
+/// `phase` is the lowercase phase like `parser` or `compiler`, or `lint`
+pub fn json_message(diagnostic: &Diagnostic, file: &str, src: &str) -> Value {
+    let position = |offset| {
+        let (line, column) = line_column(src, offset);
+
+        json!({ "line": line, "column": column })
+    };
+
+    let (code, phase) = match Lint::from_name(&diagnostic.code) {
+        Some(lint) => (Some(lint.name()), "lint".to_owned()),
+        None => (None, diagnostic.code.to_lowercase()),
+    };
+
+    json!({
+        "file": file,
+        "severity": diagnostic.severity.to_string(),
+        "code": code,
+        "phase": phase,
+        "message": diagnostic.message,
+        "span": diagnostic.span.map(|s| json!({ "start": s.start, "end": s.end })),
+        "start": diagnostic.span.map(|s| position(s.start)),
+        "end": diagnostic.span.map(|s| position(s.end)),
+    })
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Compile and run a program
+    Run {
+        /// Input file
+        input_file: String,
+
+        /// Evaluate the program directly, without clang++
+        #[clap(long)]
+        interpret: bool,
+    },
+    /// Evaluate statements interactively
+    Repl,
+    /// Format source files in place
+    Fmt {
+        /// Files to format
+        #[clap(required = true)]
+        input_files: Vec<String>,
+
+        /// Only check the formatting, exit with 1 if a file is not formatted
+        #[clap(long)]
+        check: bool,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use chumsky::span::SimpleSpan;
+    use clap::{CommandFactory, Parser};
+    use comfy_types::{Diagnostic, Severity};
+    use serde_json::json;
+
+    use super::{json_message, line_column, Args};
+    use crate::compiler::lints::{Level, Lint};
+
+    fn lints(args: &[&str]) -> Vec<Level> {
+        let matches =
+            Args::command().get_matches_from(["comfy", "-i", "main.co"].iter().chain(args));
+        let lints = super::lints(&matches);
+
+        [
+            Lint::UnusedVariables,
+            Lint::ShadowedBindings,
+            Lint::UnreachableCode,
+        ]
+        .into_iter()
+        .map(|lint| lints.level(lint))
+        .collect()
+    }
+
+    #[test]
+    fn default_levels() {
+        assert_eq!(lints(&[]), [Level::Warn, Level::Allow, Level::Warn]);
+    }
+
+    #[test]
+    fn levels_on_the_command_line() {
+        assert_eq!(
+            lints(&["-A", "unused_variables", "-W", "shadowed_bindings"]),
+            [Level::Allow, Level::Warn, Level::Warn]
+        );
+        assert_eq!(
+            lints(&["-D", "unreachable_code", "--allow", "shadowed_bindings"]),
+            [Level::Warn, Level::Allow, Level::Deny]
+        );
+    }
+
+    #[test]
+    fn later_levels_override_earlier_ones() {
+        assert_eq!(
+            lints(&["-D", "unused_variables", "-W", "unused_variables"]),
+            [Level::Warn, Level::Allow, Level::Warn]
+        );
+        assert_eq!(
+            lints(&["-W", "shadowed_bindings", "-A", "shadowed_bindings"]),
+            [Level::Warn, Level::Allow, Level::Warn]
+        );
+        assert_eq!(
+            lints(&[
+                "-A",
+                "unreachable_code",
+                "-D",
+                "unreachable_code",
+                "-W",
+                "unreachable_code",
+                "-D",
+                "unreachable_code"
+            ]),
+            [Level::Warn, Level::Allow, Level::Deny]
+        );
+    }
+
+    #[test]
+    fn levels_after_a_subcommand() {
+        let matches = Args::command().get_matches_from([
+            "comfy",
+            "run",
+            "main.co",
+            "-A",
+            "shadowed_bindings",
+            "-W",
+            "shadowed_bindings",
+        ]);
+
+        assert_eq!(
+            super::lints(&matches).level(Lint::ShadowedBindings),
+            Level::Warn
+        );
+    }
+
+    #[test]
+    fn unknown_lint() {
+        assert!(Args::try_parse_from(["comfy", "-i", "main.co", "-D", "unused"]).is_err());
+    }
+
+    #[test]
+    fn lines_and_columns() {
+        let src = "fn main() -> i32 {\n    return 0;\n}\n";
+
+        assert_eq!(line_column(src, 0), (1, 1));
+        assert_eq!(line_column(src, 3), (1, 4));
+        assert_eq!(line_column(src, 18), (1, 19));
+        assert_eq!(line_column(src, 19), (2, 1));
+        assert_eq!(line_column(src, 23), (2, 5));
+        assert_eq!(line_column(src, src.len()), (4, 1));
+    }
+
+    #[test]
+    fn columns_count_characters() {
+        let src = "let s: str = \"héllo\"; x\n\"日本\" y";
+
+        assert_eq!(line_column(src, src.find('x').unwrap()), (1, 23));
+        assert_eq!(line_column(src, src.find('y').unwrap()), (2, 6));
+    }
+
+    #[test]
+    fn json_fields() {
+        let src = "fn main() -> i32 {\n    return x;\n}";
+        let diagnostic = Diagnostic::error(
+            "Compiler",
+            "Unknown variable `x`".to_owned(),
+            Some(SimpleSpan::new(30, 31)),
+        );
+
+        assert_eq!(
+            json_message(&diagnostic, "main.co", src),
+            json!({
+                "file": "main.co",
+                "severity": "error",
+                "code": null,
+                "phase": "compiler",
+                "message": "Unknown variable `x`",
+                "span": { "start": 30, "end": 31 },
+                "start": { "line": 2, "column": 12 },
+                "end": { "line": 2, "column": 13 },
+            })
+        );
+    }
+
+    #[test]
+    fn json_code_of_lints() {
+        let diagnostic = Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(
+                Lint::UnusedVariables.name(),
+                "Unused variable `x`".to_owned(),
+                Some(SimpleSpan::new(4, 5)),
+            )
+        };
+        let message = json_message(&diagnostic, "main.co", "let x: i32 = 1;");
+
+        assert_eq!(message["severity"], "warning");
+        assert_eq!(message["code"], "unused_variables");
+        assert_eq!(message["phase"], "lint");
+    }
+
+    #[test]
+    fn json_without_span() {
+        let diagnostic = Diagnostic::error("Clang", "clang++ failed".to_owned(), None);
+        let message = json_message(&diagnostic, "main.co", "");
+
+        assert_eq!(message["code"], json!(null));
+        assert_eq!(message["phase"], "clang");
+        assert_eq!(message["span"], json!(null));
+        assert_eq!(message["start"], json!(null));
+    }
 }
@@ -30,11 +30,13 @@ pub fn compile(src: &str, output_file: String) -> CompileResult<()> {
 fn run_clang(clang_args: ClangArgs) -> Result<(), Error> {
     let mut command = Command::new(clang_resolve());
 
-    let mut args: Vec<&str> = vec![];
-
-    args.push(&clang_args.input_file);
-    args.push("-o");
-    args.push(&clang_args.output_file);
+    // Slices are lowered to `std::span`
+    let args: Vec<&str> = vec![
+        "-std=c++20",
+        &clang_args.input_file,
+        "-o",
+        &clang_args.output_file,
+    ];
 
     command
         .args(args)
@@ -55,7 +57,7 @@ fn run_clang(clang_args: ClangArgs) -> Result<(), Error> {
     };
 
     if !output.status.success() {
-        Err(Error::Clang(format!("Compilation failed")))
+        Err(Error::Clang("Compilation failed".to_owned()))
     } else {
         Ok(())
     }
@@ -1,80 +1,178 @@
-use colored::*;
-use comfy_types::Ast;
+use comfy_parser::parse;
+use comfy_types::{Ast, Diagnostic, Severity};
 use std::time::{Duration, Instant};
 
 mod clang;
 mod translation;
-use clang::compile;
 
 pub use translation::*;
 
-use crate::cli::Args;
+/// Options of the translation to C++
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Path of the source, imports are resolved from its directory
+    pub input_file: String,
+    pub lints: lints::Lints,
+}
 
-pub struct CompilerOut {
+/// C++ translation of a program
+#[derive(Debug, Clone)]
+pub struct CppOutput {
+    /// Translation unit with the includes and the code of the imported modules
     pub code: String,
-    pub compile_time: Duration,
+    /// Findings of the lints at the warn level
+    pub warnings: Vec<Diagnostic>,
     pub translation_time: Duration,
     pub state: State,
 }
 
-impl Compiler {
-    pub fn new(ast: Ast) -> Self {
-        Self { ast }
+/// Parses and translates `source` to C++
+pub fn compile_to_cpp(source: &str, options: &Options) -> Result<CppOutput, Vec<Diagnostic>> {
+    let ast = parse(source)?;
+
+    translate(&ast, options)
+}
+
+/// Translates a parsed program to C++, the errors are returned with the warnings
+pub fn translate(ast: &Ast, options: &Options) -> Result<CppOutput, Vec<Diagnostic>> {
+    let mut state = State::new();
+
+    state.set_input_file(&options.input_file);
+    state.lints = options.lints.clone();
+
+    let translation_time = Instant::now();
+    let result = ast.to_cpp(&mut state);
+
+    // Uses after a failed statement were never seen
+    if result.is_ok() {
+        state.report_unused_items();
     }
 
-    pub fn compile(&mut self, args: Args) -> Result<CompilerOut, Vec<Error>> {
-        let output_file = args.output_file.unwrap_or("a.out".to_owned());
+    let translation_time = translation_time.elapsed();
 
-        let mut state = State::new();
+    let mut warnings = state
+        .warnings
+        .iter()
+        .cloned()
+        .map(|e| Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::from(e)
+        })
+        .collect::<Vec<_>>();
 
-        let translation_time = Instant::now();
-        let result = self.ast.to_cpp(&mut state);
-        let translation_time = translation_time.elapsed();
+    // Lints of a block are run before the lints of the blocks inside it
+    warnings.sort_by_key(|d| (d.file.clone(), d.span.map(|s| s.start)));
 
-        if state.errors.len() > 0 || result.is_err() {
+    let code = match result {
+        Ok(code) if state.errors.is_empty() => code,
+        result => {
             if let Err(e) = result {
                 state.errors.push(e);
             }
 
-            if args.verbose {
-                println!("\n{}\n {:#?}", "State:".bold().green(), state);
-            }
+            let errors = state.errors.into_iter().map(Diagnostic::from);
 
-            return Err(state.errors);
+            return Err(warnings.into_iter().chain(errors).collect());
         }
+    };
+
+    let includes = state
+        .includes
+        .iter()
+        .map(|i| format!("#include {}\n", i))
+        .collect::<String>();
+
+    let helpers = state
+        .helpers
+        .iter()
+        .map(|h| format!("{}\n", h))
+        .collect::<String>();
+
+    let code = format!(
+        "{}\n{}{}{}",
+        includes,
+        helpers,
+        state.module_code.concat(),
+        code
+    );
+
+    Ok(CppOutput {
+        code,
+        warnings,
+        translation_time,
+        state,
+    })
+}
 
-        let code = result.unwrap();
-        let code = format!(
-            "#include <iostream>\n#include <stdint.h>\n#include <string>\n\n{}",
-            code
-        );
-
-        let compile_time = Instant::now();
+/// Builds the translated program into an executable with clang++
+pub fn build(cpp: &CppOutput, output_file: &str) -> Result<(), Vec<Diagnostic>> {
+    clang::compile(&cpp.code, output_file.to_owned()).map_err(|e| vec![Diagnostic::from(e)])
+}
 
-        let compiled = if args.dry_run {
-            Ok(())
-        } else {
-            compile(&code, output_file.to_owned())
-        };
+#[cfg(test)]
+mod tests {
+    use comfy_types::Severity;
+
+    use super::{compile_to_cpp, Options};
+
+    #[test]
+    fn translation_unit() {
+        let output = compile_to_cpp(
+            "fn main() -> i32 {
+                let x: u8 = 1;
+                return 0;
+            }",
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert!(output.code.starts_with("#include"));
+        assert!(output.code.contains("int32_t main()"));
+        assert_eq!(
+            output
+                .warnings
+                .iter()
+                .map(|d| (d.severity, d.code.as_str()))
+                .collect::<Vec<_>>(),
+            [(Severity::Warning, "unused_variables")]
+        );
+    }
 
-        let compile_time = compile_time.elapsed();
+    #[test]
+    fn parse_errors() {
+        let diagnostics =
+            compile_to_cpp("fn main() -> i32 { return }", &Options::default()).unwrap_err();
 
-        if let Err(e) = compiled {
-            state.errors.push(e);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert!(diagnostics[0].span.is_some());
+    }
 
-            if args.verbose {
-                println!("\n{}\n {:#?}", "State:".bold().green(), state);
-                println!("\n{}\n {}", "Code:".bold().green(), code);
+    #[test]
+    fn errors_come_after_warnings() {
+        let diagnostics = compile_to_cpp(
+            "fn main() -> i32 {
+                return 0;
+                let x: u8 = 1;
             }
 
-            return Err(state.errors);
-        } else {
-            return Ok(CompilerOut {
-                code,
-                compile_time,
-                translation_time,
-                state,
-            });
-        }
+            fn f() -> void {
+                let y: u8 = z;
+            }",
+            &Options::default(),
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| (d.severity, d.code.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (Severity::Warning, "unreachable_code"),
+                (Severity::Warning, "unused_variables"),
+                (Severity::Error, "Compiler")
+            ]
+        );
     }
 }
@@ -0,0 +1,103 @@
+use comfy_types::{Argument, Expr, Type};
+
+use super::{
+    coercion::{integer_literal, is_numeric},
+    enums::{match_casted_to, match_expression},
+    slices::is_slice,
+    ComfyNode, CompileResult, State,
+};
+
+/// `std::array` with every element set to the same value, `[value; len]`
+const REPEAT: &str = "template <typename T, std::size_t N>
+std::array<T, N> comfy_repeat(const T& value) {
+    std::array<T, N> result;
+    result.fill(value);
+
+    return result;
+}
+";
+
+/// `value` translated as a value of `ty`. Array literals are typed by the
+/// array they initialize, the elements of a `std::array` do not convert
+/// implicitly. The factorial of a literal is computed as a value of `ty`.
+/// Arrays passed as slices are converted explicitly, C++ does
+/// not deduce template arguments through the conversion to `std::span`.
+/// The arms of a `match` are translated as values of `ty`. Other values are
+/// converted by C++
+pub fn value_as(st: &mut State, value: &Expr, ty: &Type) -> CompileResult<String> {
+    match (value, ty) {
+        (Expr::Array(items, _), Type::Array(item_ty, _, _)) => {
+            let citems = items
+                .iter()
+                .map(|item| value_as(st, item, item_ty))
+                .collect::<CompileResult<Vec<_>>>()?;
+
+            Ok(format!("{}{{ {} }}", ty.to_cpp(st)?, citems.join(", ")))
+        }
+        (Expr::Repeat(item, len, _), Type::Array(item_ty, _, _)) => {
+            st.add_include("<array>");
+            st.add_helper(REPEAT);
+
+            Ok(format!(
+                "comfy_repeat<{}, {}>({})",
+                item_ty.to_cpp(st)?,
+                len,
+                value_as(st, item, item_ty)?
+            ))
+        }
+        (Expr::Factorial(_), _) if is_numeric(ty) => match integer_literal(value) {
+            Some((v, _)) => Ok(format!("static_cast<{}>({})", ty.to_cpp(st)?, v)),
+            None => value.to_cpp(st),
+        },
+        // The arms are converted in the lambda
+        (Expr::Match(value, arms, s), _) if match_casted_to(st, value, arms, ty) => {
+            Ok(match_expression(st, value, arms, Some(ty), *s)?.0)
+        }
+        _ if is_slice(ty) => {
+            let value_t = value.resolve_type(st)?;
+            let cvalue = value.to_cpp(st)?;
+
+            // `&mut [T]` passed as `&[T]` is converted too
+            match is_slice(&value_t)
+                && matches!(value_t, Type::Reference(..)) == matches!(ty, Type::Reference(..))
+            {
+                true => Ok(cvalue),
+                false => Ok(format!("{}({})", ty.to_cpp(st)?, cvalue)),
+            }
+        }
+        _ => value.to_cpp(st),
+    }
+}
+
+/// Arguments of a call translated as values of the types of `params`
+pub fn args_as(st: &mut State, params: &[Argument], args: &[Expr]) -> CompileResult<Vec<String>> {
+    args.iter()
+        .enumerate()
+        .map(|(i, arg)| match params.get(i) {
+            Some(param) => value_as(st, arg, &param.1),
+            None => arg.to_cpp(st),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_utils::translate;
+
+    #[test]
+    fn repeat_of_variable() {
+        let code = translate(
+            "fn main() -> i32 {
+                let v: u8 = 7;
+                let a: [u8; 4] = [v; 4];
+                let m: [[u8; 2]; 0x3] = [[v; sizeof u16]; 0b11];
+                return (a[3] + m[2][1]) as i32;
+            }",
+        );
+
+        assert!(code.contains("a = comfy_repeat<uint8_t, 4>(v);"));
+        assert!(
+            code.contains("comfy_repeat<std::array<uint8_t, 2>, 3>(comfy_repeat<uint8_t, 2>(v))")
+        );
+    }
+}
@@ -0,0 +1,476 @@
+use chumsky::span::SimpleSpan;
+use comfy_types::{Expr, Literal, Type};
+
+use super::{
+    generics::is_type_param, lints::Lint, slices::borrowed_elements, ComfyNode, CompileResult,
+    Error, State,
+};
+
+#[derive(Debug, Clone, Copy)]
+enum Numeric {
+    Signed(u32),
+    Unsigned(u32),
+    Float(u32),
+}
+
+fn numeric(ty: &Type) -> Option<Numeric> {
+    Some(match ty {
+        Type::I8(_) => Numeric::Signed(8),
+        Type::I16(_) => Numeric::Signed(16),
+        Type::I32(_) | Type::Int(_) => Numeric::Signed(32),
+        Type::I64(_) => Numeric::Signed(64),
+        Type::U8(_) => Numeric::Unsigned(8),
+        Type::U16(_) => Numeric::Unsigned(16),
+        Type::U32(_) | Type::Uint(_) => Numeric::Unsigned(32),
+        Type::U64(_) => Numeric::Unsigned(64),
+        Type::F32(_) => Numeric::Float(32),
+        Type::F64(_) => Numeric::Float(64),
+        _ => return None,
+    })
+}
+
+pub fn is_numeric(ty: &Type) -> bool {
+    numeric(ty).is_some()
+}
+
+pub fn is_integer(ty: &Type) -> bool {
+    matches!(numeric(ty), Some(Numeric::Signed(_) | Numeric::Unsigned(_)))
+}
+
+/// Whether every value of `from` is also a value of `to`, `never` converts to anything
+pub fn widens(from: &Type, to: &Type) -> bool {
+    if from == to || matches!(from, Type::Never(_)) {
+        return true;
+    }
+
+    if let (Type::MutableRef(from, _), Type::Reference(to, _)) = (from, to) {
+        return from == to;
+    }
+
+    // The parameters of `Vec::new()` or `None` are inferred from the binding
+    if let (Type::Generic(a, from, _), Type::Generic(b, to, _)) = (from, to) {
+        return a == b
+            && from.len() == to.len()
+            && from
+                .iter()
+                .zip(to)
+                .all(|(from, to)| matches!(from, Type::Unknown(_)) || from == to);
+    }
+
+    match to {
+        Type::Reference(t, _) | Type::MutableRef(t, _) => {
+            if let Type::Slice(elements, _) = t.as_ref() {
+                let mutable = matches!(to, Type::MutableRef(..));
+
+                return borrowed_elements(from, mutable) == Some(elements.as_ref());
+            }
+        }
+        _ => {}
+    }
+
+    let (Some(from), Some(to)) = (numeric(from), numeric(to)) else {
+        return false;
+    };
+
+    match (from, to) {
+        (Numeric::Signed(a), Numeric::Signed(b))
+        | (Numeric::Unsigned(a), Numeric::Unsigned(b))
+        | (Numeric::Float(a), Numeric::Float(b)) => a <= b,
+        (Numeric::Unsigned(a), Numeric::Signed(b)) => a < b,
+        // Integers up to the width of the mantissa are exact
+        (Numeric::Signed(a) | Numeric::Unsigned(a), Numeric::Float(b)) => {
+            a <= if b == 32 { 16 } else { 32 }
+        }
+        _ => false,
+    }
+}
+
+/// Whether `value` is a value of the integer type `ty`, floats take any integer
+pub fn fits(value: i128, ty: &Type) -> bool {
+    let (min, max) = match numeric(ty) {
+        Some(Numeric::Signed(bits)) => (-(1 << (bits - 1)), (1 << (bits - 1)) - 1),
+        Some(Numeric::Unsigned(bits)) => (0, (1 << bits) - 1),
+        Some(Numeric::Float(_)) => return true,
+        None => return false,
+    };
+
+    (min..=max).contains(&value)
+}
+
+/// `n!`, `None` if it does not fit in an `i128`
+pub fn factorial(n: i128) -> Option<i128> {
+    (2..=n).try_fold(1i128, |acc, i| acc.checked_mul(i))
+}
+
+/// Value of an integer literal, a negated one and the factorial of one included
+pub fn constant_value(e: &Expr) -> Option<i128> {
+    match e {
+        Expr::Literal(Literal::Decimal(v, _, _)) => v.parse().ok(),
+        Expr::Literal(Literal::Hex(v, _, _)) => i128::from_str_radix(v, 16).ok(),
+        Expr::Literal(Literal::Octal(v, _, _)) => i128::from_str_radix(v, 8).ok(),
+        Expr::Literal(Literal::Binary(v, _, _)) => i128::from_str_radix(v, 2).ok(),
+        Expr::Neg(e) => constant_value(e).map(|v| -v),
+        Expr::Factorial(e) => constant_value(e).filter(|n| *n >= 0).and_then(factorial),
+        _ => None,
+    }
+}
+
+/// Whether `e` is a literal with a type suffix, which keeps the type of the suffix
+fn suffixed(e: &Expr) -> bool {
+    match e {
+        Expr::Literal(
+            Literal::Decimal(_, ty, _)
+            | Literal::Hex(_, ty, _)
+            | Literal::Octal(_, ty, _)
+            | Literal::Binary(_, ty, _),
+        ) => ty.is_some(),
+        Expr::Neg(e) | Expr::Factorial(e) => suffixed(e),
+        _ => false,
+    }
+}
+
+/// Value of an integer literal without a suffix, a negated one and the
+/// factorial of one included
+pub fn integer_literal(e: &Expr) -> Option<(i128, SimpleSpan)> {
+    if suffixed(e) || float_literal(e) {
+        return None;
+    }
+
+    constant_value(e).map(|v| (v, e.span()))
+}
+
+fn float_literal(e: &Expr) -> bool {
+    match e {
+        Expr::Literal(Literal::Decimal(v, None, _)) => v.contains(['.', 'e', 'E']),
+        Expr::Neg(e) => float_literal(e),
+        _ => false,
+    }
+}
+
+/// Type of `e!`. The factorial of a literal is computed when translating, it
+/// has to fit in the type of the suffix or is typed by value like a literal
+pub fn factorial_type(st: &mut State, e: &Expr) -> CompileResult<Type> {
+    let ty = e.resolve_type(st)?;
+
+    if !is_integer(&ty) {
+        Err(Error::Compile(
+            format!("Factorial is only defined for integers, found {}", ty),
+            e.span(),
+        ))?
+    }
+
+    let Some(n) = constant_value(e) else {
+        return Ok(ty);
+    };
+
+    if n < 0 {
+        Err(Error::Compile(
+            format!("Factorial of negative number `{}`", n),
+            e.span(),
+        ))?
+    }
+
+    let value = factorial(n);
+    let s = e.span();
+
+    let ty = match suffixed(e) {
+        true => ty,
+        false => [Type::U8(s), Type::U16(s), Type::U32(s)]
+            .into_iter()
+            .find(|ty| value.is_some_and(|v| fits(v, ty)))
+            .unwrap_or(Type::U64(s)),
+    };
+
+    match value {
+        Some(v) if fits(v, &ty) => Ok(ty),
+        _ => Err(Error::Compile(
+            format!("Factorial of `{}` overflows {}", n, ty),
+            e.span(),
+        )),
+    }
+}
+
+/// Type of a negated integer literal, the smallest signed type it fits in
+pub fn negative_literal_type(e: &Expr) -> Option<Type> {
+    let (value, s) = integer_literal(e)?;
+
+    Some(match value {
+        v if v >= i8::MIN as i128 => Type::I8(s),
+        v if v >= i16::MIN as i128 => Type::I16(s),
+        v if v >= i32::MIN as i128 => Type::I32(s),
+        _ => Type::I64(s),
+    })
+}
+
+pub fn is_literal(e: &Expr) -> bool {
+    integer_literal(e).is_some() || float_literal(e)
+}
+
+/// Whether the literal `e` can be typed as `ty`, `None` if `e` is not a
+/// numeric literal. Integer literals take any numeric type, the ones that do
+/// not fit are errors and the ones a float rounds are reported
+pub fn literal_to(st: &mut State, e: &Expr, ty: &Type) -> Option<bool> {
+    if float_literal(e) {
+        return Some(matches!(numeric(ty), Some(Numeric::Float(_))));
+    }
+
+    let (value, s) = integer_literal(e)?;
+
+    let exact = match numeric(ty) {
+        None => return Some(false),
+        Some(Numeric::Float(32)) => value as f32 as i128 == value,
+        Some(Numeric::Float(_)) => value as f64 as i128 == value,
+        Some(_) => true,
+    };
+
+    let literal = match e {
+        Expr::Factorial(n) => format!(
+            "Factorial `{}!` = {}",
+            constant_value(n).unwrap_or_default(),
+            value
+        ),
+        _ => format!("Literal `{}`", value),
+    };
+
+    if !fits(value, ty) {
+        st.error(format!("{} does not fit in {}", literal, ty), s);
+    } else if !exact {
+        st.lint(
+            Lint::ImplicitNarrowing,
+            format!("{} is rounded to the nearest {}", literal, ty),
+            s,
+        );
+    }
+
+    Some(true)
+}
+
+/// Type both operands of a binary operator are converted to, a literal
+/// takes the type of the other operand
+pub fn common_type(st: &mut State, l: &Expr, r: &Expr) -> CompileResult<Type> {
+    let lt = l.resolve_type(st)?;
+    let rt = r.resolve_type(st)?;
+
+    // Checked again with the type argument when the function is instantiated
+    if is_literal(l) && is_type_param(st, &rt) {
+        return Ok(rt);
+    } else if is_literal(r) && is_type_param(st, &lt) {
+        return Ok(lt);
+    }
+
+    if is_literal(l) && l.casted_to(&rt, st) {
+        Ok(rt)
+    } else if r.casted_to(&lt, st) {
+        Ok(lt)
+    } else if l.casted_to(&rt, st) {
+        Ok(rt)
+    } else {
+        Err(Error::Compile(
+            format!(
+                "Cannot convert between {} and {}, use `as` to convert",
+                lt, rt
+            ),
+            SimpleSpan::new(l.span().start, r.span().end),
+        ))
+    }
+}
+
+/// Structs have no operators, the operators on type parameters are checked
+/// when the generic function is instantiated
+pub fn check_operator(st: &State, op: &str, ty: &Type, span: SimpleSpan) -> CompileResult<()> {
+    match ty {
+        Type::Custom(name, _) | Type::Generic(name, _, _) if st.get_struct(name, span).is_ok() => {
+            Err(Error::Compile(
+                format!("Operator `{}` is not defined for {}", op, ty),
+                span,
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Type of an assignment, the value is converted to the type of the target
+pub fn assign_type(st: &mut State, l: &Expr, r: &Expr) -> CompileResult<Type> {
+    let lt = l.resolve_type(st)?;
+
+    if r.casted_to(&lt, st) {
+        Ok(lt)
+    } else {
+        Err(Error::Compile(
+            format!(
+                "Cannot assign {} to {}, use `as` to convert",
+                r.resolve_type(st)?,
+                lt
+            ),
+            r.span(),
+        ))
+    }
+}
+
+/// Type of `e as ty`, numbers, `bool` and `char` convert to each other
+pub fn cast_type(st: &mut State, e: &Expr, ty: &Expr) -> CompileResult<Type> {
+    let from = e.resolve_type(st)?;
+    let to = ty.resolve_type(st)?;
+
+    let scalar = |ty: &Type| is_numeric(ty) || matches!(ty, Type::Bool(_) | Type::Char(_));
+
+    if widens(&from, &to) || scalar(&from) && scalar(&to) {
+        Ok(to)
+    } else {
+        Err(Error::Compile(
+            format!("Cannot cast {} as {}", from, to),
+            SimpleSpan::new(e.span().start, ty.span().end),
+        ))
+    }
+}
+
+/// Checks the value of a variable with a type annotation, lossy numeric
+/// conversions need `as` and other types only take values of the same type
+pub fn check_binding(st: &mut State, e: &Expr, ty: &Type) -> CompileResult<()> {
+    let et = e.resolve_type(st)?;
+
+    if !e.casted_to(ty, st) {
+        let hint = match is_numeric(&et) && is_numeric(ty) {
+            true => ", use `as` to convert",
+            false => "",
+        };
+
+        Err(Error::Compile(
+            format!("Expected type {}, got {}{}", ty, et, hint),
+            e.span(),
+        ))?
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_utils::{errors, translate, warnings};
+
+    #[test]
+    fn undefined_name_in_typed_binding() {
+        let errors = errors("fn main() -> i32 { let y: i32 = zz; return y; }");
+
+        assert_eq!(errors, ["Unknown identifier: zz"]);
+    }
+
+    #[test]
+    fn undefined_name_in_return() {
+        let errors = errors("fn main() -> i32 { return c; }");
+
+        assert_eq!(errors, ["Unknown identifier: c"]);
+    }
+
+    #[test]
+    fn mismatched_bindings() {
+        let structs = "struct P { pub x: i32 }\nstruct Q { pub x: i32 }\n";
+        let cases = [
+            (
+                "fn main() -> i32 { let b: bool = 5; return 0; }",
+                "Expected type bool, got u8",
+            ),
+            (
+                "fn main() -> i32 { let d: i32 = true; return d; }",
+                "Expected type i32, got bool",
+            ),
+            (
+                "fn main() -> i32 { let t: (i32, bool) = (1, 2); return 0; }",
+                "Expected type (i32, bool), got (u8, u8)",
+            ),
+            (
+                "fn main() -> i32 { let p: P = Q { x: 1 }; return 0; }",
+                "Expected type P, got Q",
+            ),
+            (
+                "fn main() -> i32 { let x: i8 = 5i16; return 0; }",
+                "Expected type i8, got i16, use `as` to convert",
+            ),
+        ];
+
+        for (source, message) in cases {
+            assert_eq!(
+                errors(&format!("{}{}", structs, source)),
+                [message],
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn mismatched_returns() {
+        let errors_of = |source: &str| {
+            errors(&format!(
+                "struct P {{ pub x: i32 }}\nstruct Q {{ pub x: i32 }}\n{}\nfn main() -> i32 {{ return 0; }}",
+                source
+            ))
+        };
+
+        assert_eq!(
+            errors_of("fn f() -> bool { return 3; }"),
+            ["Expected type bool, got u8"]
+        );
+        assert_eq!(
+            errors_of("fn f() -> P { return Q { x: 1 }; }"),
+            ["Expected type P, got Q"]
+        );
+    }
+
+    #[test]
+    fn literal_out_of_range() {
+        assert_eq!(
+            errors("fn main() -> i32 { let v: u8 = 300; return v as i32; }"),
+            ["Literal `300` does not fit in u8"]
+        );
+        assert_eq!(
+            errors("fn main() -> i32 { let v: i8 = 5i8 + 300; return v as i32; }"),
+            ["Literal `300` does not fit in i8"]
+        );
+    }
+
+    #[test]
+    fn literal_rounded_by_float() {
+        let source = "fn main() -> i32 { let v: f32 = 16777217; return v as i32; }";
+
+        assert_eq!(
+            warnings(source),
+            ["Literal `16777217` is rounded to the nearest f32"]
+        );
+    }
+
+    #[test]
+    fn inferred_generic_parameters() {
+        let source = "fn main() -> i32 { let v: Vec<i32> = Vec::new(); return v.len() as i32; }";
+
+        assert!(translate(source).contains("std::vector<int32_t> v"));
+    }
+
+    #[test]
+    fn factorial_takes_the_type_of_the_binding() {
+        let source = "fn f(x: i64) -> i64 { return x; }
+            fn main() -> i32 {
+                let a: u16 = 6!;
+                let b: f32 = 4!;
+                return (a as i64 + f(5!)) as i32 + b as i32;
+            }";
+        let cpp = translate(source);
+
+        assert!(cpp.contains("const uint16_t a = static_cast<uint16_t>(720);"));
+        assert!(cpp.contains("const float b = static_cast<float>(24);"));
+        assert!(cpp.contains("f(static_cast<int64_t>(120))"));
+        assert!(warnings(source).is_empty());
+    }
+
+    #[test]
+    fn factorial_out_of_range() {
+        assert_eq!(
+            errors("fn main() -> i32 { let a: u8 = 6!; return a as i32; }"),
+            ["Factorial `6!` = 720 does not fit in u8"]
+        );
+        assert_eq!(
+            errors("fn main() -> i32 { let a: i8 = 1i8 + 6!; return a as i32; }"),
+            ["Factorial `6!` = 720 does not fit in i8"]
+        );
+    }
+}
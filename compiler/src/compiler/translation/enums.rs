@@ -0,0 +1,630 @@
+use std::collections::HashMap;
+
+use chumsky::span::SimpleSpan;
+use comfy_types::{Expr, Literal, MatchArm, MatchExprArm, Pattern, Type, Variant};
+use comfy_utils::inc_indent;
+
+use super::{
+    arrays::value_as, coercion::is_literal, cpp_ident, structs::method_prototypes, ComfyNode,
+    CompileResult, Error, State,
+};
+
+/// Enums are lowered to a struct wrapping a `std::variant` of one struct per variant
+pub fn enum_declaration(
+    st: &mut State,
+    name: &str,
+    variants: &[Variant],
+    span: SimpleSpan,
+) -> CompileResult<String> {
+    if variants.is_empty() {
+        Err(Error::Compile(
+            format!("Enum `{}` must have at least one variant", name),
+            span,
+        ))?
+    }
+
+    let mut members = vec![];
+
+    for (i, Variant(variant, types, s)) in variants.iter().enumerate() {
+        if variants[..i].iter().any(|v| &v.0 == variant) {
+            Err(Error::Compile(
+                format!("Variant `{}` is declared more than once", variant),
+                *s,
+            ))?
+        }
+
+        let fields = types
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| Ok(format!("{} _{};", ty.to_cpp(st)?, i)))
+            .collect::<CompileResult<Vec<_>>>()?;
+
+        members.push(if fields.is_empty() {
+            format!("struct {} {{}};", cpp_ident(variant))
+        } else {
+            format!(
+                "struct {} {{\n{}\n}};",
+                cpp_ident(variant),
+                inc_indent(fields.join("\n"))
+            )
+        });
+    }
+
+    st.add_include("<variant>");
+
+    // Methods may refer to the enum itself
+    st.add_enum(name, variants.to_vec(), span);
+
+    let alternatives = variants
+        .iter()
+        .map(|v| cpp_ident(&v.0))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    members.push(format!("std::variant<{}> value;", alternatives));
+
+    let methods = method_prototypes(st, name, None)?;
+
+    Ok(format!(
+        "struct {} {{\n{}{}\n}};\n",
+        cpp_ident(name),
+        inc_indent(members.join("\n")),
+        methods.join("\n")
+    ))
+}
+
+/// `Enum::Variant` or `Enum::Variant(args)`, `None` if `item` is not a variant of `name`
+pub fn variant_value(
+    st: &mut State,
+    name: &str,
+    variants: &[Variant],
+    item: &Expr,
+) -> CompileResult<Option<(String, Type)>> {
+    let (variant, args, span) = match item {
+        Expr::Ident(variant, s) => (variant, vec![], *s),
+        Expr::Call(f, args, s) => match f.as_ref() {
+            Expr::Ident(variant, _) => (variant, args.clone(), *s),
+            _ => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+
+    let Some(Variant(_, types, _)) = variants.iter().find(|v| &v.0 == variant) else {
+        return Ok(None);
+    };
+
+    if args.len() != types.len() {
+        Err(Error::Compile(
+            format!(
+                "Variant `{}::{}` has {} field(s), got {}",
+                name,
+                variant,
+                types.len(),
+                args.len()
+            ),
+            span,
+        ))?
+    }
+
+    let cargs = types
+        .iter()
+        .zip(&args)
+        .map(|(ty, arg)| {
+            let arg_t = arg.resolve_type(st)?;
+
+            if !arg.casted_to(ty, st) {
+                Err(Error::Compile(
+                    format!("Expected type {}, got {}", ty, arg_t),
+                    arg.span(),
+                ))?
+            }
+
+            value_as(st, arg, ty)
+        })
+        .collect::<CompileResult<Vec<_>>>()?;
+
+    let cname = cpp_ident(name);
+
+    Ok(Some((
+        format!(
+            "{}{{{}::{}{{{}}}}}",
+            cname,
+            cname,
+            cpp_ident(variant),
+            cargs.join(", ")
+        ),
+        Type::Custom(name.to_owned(), span),
+    )))
+}
+
+/// Name of the enum behind `ty`, references are dereferenced automatically
+fn enum_name(st: &State, ty: &Type) -> Option<String> {
+    match ty {
+        Type::Custom(name, s) if st.get_enum(name, *s).is_ok() => Some(name.clone()),
+        Type::Reference(t, _) | Type::MutableRef(t, _) => enum_name(st, t),
+        _ => None,
+    }
+}
+
+/// Collects the C++ conditions `value` has to satisfy to match `pattern`
+/// and the variables the pattern binds
+fn pattern_to_cpp(
+    st: &mut State,
+    pattern: &Pattern,
+    value: &str,
+    ty: &Type,
+    conditions: &mut Vec<String>,
+    bindings: &mut Vec<(String, Type, String, SimpleSpan)>,
+) -> CompileResult<()> {
+    match pattern {
+        Pattern::Wildcard(_) => {}
+        Pattern::Binding(name, s) => {
+            bindings.push((name.clone(), ty.clone(), value.to_owned(), *s))
+        }
+        Pattern::Literal(l) => {
+            let lty = l.resolve_type(st)?;
+
+            if !Expr::Literal(l.clone()).casted_to(ty, st) {
+                Err(Error::Compile(
+                    format!("Expected pattern of type {}, got {}", ty, lty),
+                    l.span(),
+                ))?
+            }
+
+            conditions.push(format!("{} == {}", value, l.to_cpp(st)?));
+        }
+        Pattern::Variant(name, variant, patterns, s) => {
+            if enum_name(st, ty).as_ref() != Some(name) {
+                Err(Error::Compile(
+                    format!("Expected pattern of type {}, got {}", ty, name),
+                    *s,
+                ))?
+            }
+
+            let types = st
+                .get_enum(name, *s)?
+                .iter()
+                .find(|v| &v.0 == variant)
+                .map(|v| v.1.clone())
+                .ok_or_else(|| {
+                    Error::Compile(format!("No variant `{}` on enum `{}`", variant, name), *s)
+                })?;
+
+            if patterns.len() != types.len() {
+                Err(Error::Compile(
+                    format!(
+                        "Variant `{}::{}` has {} field(s), pattern has {}",
+                        name,
+                        variant,
+                        types.len(),
+                        patterns.len()
+                    ),
+                    *s,
+                ))?
+            }
+
+            let alternative = format!("{}::{}", cpp_ident(name), cpp_ident(variant));
+
+            conditions.push(format!(
+                "std::holds_alternative<{}>({}.value)",
+                alternative, value
+            ));
+
+            for (i, (pattern, ty)) in patterns.iter().zip(&types).enumerate() {
+                let field = format!("std::get<{}>({}.value)._{}", alternative, value, i);
+
+                pattern_to_cpp(st, pattern, &field, ty, conditions, bindings)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn is_irrefutable(pattern: &Pattern) -> bool {
+    matches!(pattern, Pattern::Wildcard(_) | Pattern::Binding(_, _))
+}
+
+/// Lists the cases not covered by `patterns`, variant payloads only count as
+/// covered when they are matched by bindings or wildcards
+fn missing_cases(st: &State, ty: &Type, patterns: &[&Pattern]) -> Vec<String> {
+    if patterns.iter().any(|p| is_irrefutable(p)) {
+        return vec![];
+    }
+
+    if let Some(name) = enum_name(st, ty) {
+        let variants = st.get_enum(&name, ty.span()).unwrap_or_default();
+
+        return variants
+            .iter()
+            .filter(|Variant(variant, _, _)| {
+                !patterns.iter().any(|p| match p {
+                    Pattern::Variant(_, v, patterns, _) => {
+                        v == variant && patterns.iter().all(is_irrefutable)
+                    }
+                    _ => false,
+                })
+            })
+            .map(|v| format!("`{}::{}`", name, v.0))
+            .collect();
+    }
+
+    if let Type::Bool(_) = ty {
+        let covers = |value: bool| {
+            patterns.iter().any(|p| match p {
+                Pattern::Literal(Literal::True(_)) => value,
+                Pattern::Literal(Literal::False(_)) => !value,
+                _ => false,
+            })
+        };
+
+        return [true, false]
+            .into_iter()
+            .filter(|value| !covers(*value))
+            .map(|value| format!("`{}`", value))
+            .collect();
+    }
+
+    vec!["`_`".to_owned()]
+}
+
+fn check_exhaustive(
+    st: &State,
+    ty: &Type,
+    patterns: &[&Pattern],
+    span: SimpleSpan,
+) -> CompileResult<()> {
+    let missing = missing_cases(st, ty, patterns);
+
+    if !missing.is_empty() {
+        Err(Error::Compile(
+            format!("Non-exhaustive match, missing {}", missing.join(", ")),
+            span,
+        ))?
+    }
+
+    Ok(())
+}
+
+/// Runs `f` in a scope with the variables bound by `pattern`, used to resolve
+/// the types of an arm without translating it
+fn in_arm<T>(
+    st: &mut State,
+    pattern: &Pattern,
+    ty: &Type,
+    f: impl FnOnce(&mut State) -> CompileResult<T>,
+) -> CompileResult<T> {
+    let (mut conditions, mut bindings) = (vec![], vec![]);
+
+    st.scope_stack.push(HashMap::new());
+
+    let result = pattern_to_cpp(st, pattern, "_match", ty, &mut conditions, &mut bindings)
+        .and_then(|_| {
+            for (name, ty, _, _) in bindings {
+                st.add_variable(&name, ty, false);
+            }
+
+            f(st)
+        });
+
+    st.scope_stack.pop();
+
+    result
+}
+
+/// `if`/`else` chain testing `_match` against the pattern of each arm,
+/// `body` translates an arm with the variables of its pattern in scope
+fn branches<A>(
+    st: &mut State,
+    ty: &Type,
+    arms: &[A],
+    pattern: impl Fn(&A) -> &Pattern,
+    mut body: impl FnMut(&mut State, &A) -> CompileResult<String>,
+) -> CompileResult<String> {
+    let mut branches = vec![];
+
+    for (i, arm) in arms.iter().enumerate() {
+        let mut conditions = vec![];
+        let mut bindings = vec![];
+
+        pattern_to_cpp(
+            st,
+            pattern(arm),
+            "_match",
+            ty,
+            &mut conditions,
+            &mut bindings,
+        )?;
+
+        st.scope_stack.push(HashMap::new());
+
+        let mut lines = vec![];
+
+        for (name, ty, value, s) in bindings {
+            lines.push(format!(
+                "{} {} = {};",
+                ty.to_cpp(st)?,
+                cpp_ident(&name),
+                value
+            ));
+            st.add_binding(&name, ty, false, s);
+        }
+
+        let cbody = body(st, arm);
+        st.pop_scope();
+
+        lines.push(cbody?);
+
+        let block = format!("{{\n{}\n}}", inc_indent(lines.join("\n")));
+
+        // Every value reaching the last arm matches it, the match is exhaustive
+        let last = i == arms.len() - 1 || conditions.is_empty();
+
+        branches.push(match (i, last) {
+            (0, true) => block,
+            (_, true) => format!("else {}", block),
+            (0, false) => format!("if ({}) {}", conditions.join(" && "), block),
+            (_, false) => format!("else if ({}) {}", conditions.join(" && "), block),
+        });
+
+        if last {
+            break;
+        }
+    }
+
+    Ok(branches.join(" "))
+}
+
+pub fn match_statement(
+    st: &mut State,
+    value: &Expr,
+    arms: &[MatchArm],
+    span: SimpleSpan,
+) -> CompileResult<String> {
+    let ty = value.resolve_type(st)?;
+
+    check_exhaustive(
+        st,
+        &ty,
+        &arms.iter().map(|a| &a.0).collect::<Vec<_>>(),
+        span,
+    )?;
+
+    let branches = branches(st, &ty, arms, |arm| &arm.0, |st, arm| arm.1.to_cpp(st))?;
+
+    Ok(format!(
+        "{{\n{}\n}}",
+        inc_indent(format!(
+            "const auto& _match = {};\n{}",
+            value.to_cpp(st)?,
+            branches
+        ))
+    ))
+}
+
+/// Type of a `match` expression, the first type of an arm every arm converts
+/// to, literals are typed by the other arms
+pub fn match_type(st: &mut State, value: &Expr, arms: &[MatchExprArm]) -> CompileResult<Type> {
+    let ty = value.resolve_type(st)?;
+
+    let mut types = arms
+        .iter()
+        .map(|MatchExprArm(pattern, value, _)| {
+            in_arm(st, pattern, &ty, |st| value.resolve_type(st)).map(|t| (is_literal(value), t))
+        })
+        .collect::<CompileResult<Vec<_>>>()?;
+
+    types.sort_by_key(|(literal, _)| *literal);
+
+    for (_, candidate) in &types {
+        let mut fits = true;
+
+        for MatchExprArm(pattern, value, _) in arms {
+            fits &= in_arm(st, pattern, &ty, |st| Ok(value.casted_to(candidate, st)))?;
+        }
+
+        if fits {
+            return Ok(candidate.clone());
+        }
+    }
+
+    let first = &types[0].1;
+    let other = types
+        .iter()
+        .map(|(_, t)| t)
+        .find(|t| t != &first)
+        .unwrap_or(first);
+
+    Err(Error::Compile(
+        format!(
+            "Cannot convert between {} and {}, use `as` to convert",
+            first, other
+        ),
+        SimpleSpan::new(arms[0].2.start, arms[arms.len() - 1].2.end),
+    ))
+}
+
+/// Whether the value of every arm converts to `to`
+pub fn match_casted_to(st: &mut State, value: &Expr, arms: &[MatchExprArm], to: &Type) -> bool {
+    let Ok(ty) = value.resolve_type(st) else {
+        return false;
+    };
+
+    arms.iter().all(|MatchExprArm(pattern, value, _)| {
+        in_arm(st, pattern, &ty, |st| Ok(value.casted_to(to, st))).unwrap_or(false)
+    })
+}
+
+/// `match` expressions are lowered to a lambda called in place, every arm
+/// returns its value as `result`, the type of the match if `None`
+pub fn match_expression(
+    st: &mut State,
+    value: &Expr,
+    arms: &[MatchExprArm],
+    result: Option<&Type>,
+    span: SimpleSpan,
+) -> CompileResult<(String, Type)> {
+    let ty = value.resolve_type(st)?;
+
+    check_exhaustive(
+        st,
+        &ty,
+        &arms.iter().map(|a| &a.0).collect::<Vec<_>>(),
+        span,
+    )?;
+
+    let result = match result {
+        Some(result) => result.clone(),
+        None => match_type(st, value, arms)?,
+    };
+    let branches = branches(
+        st,
+        &ty,
+        arms,
+        |arm| &arm.0,
+        |st, arm| Ok(format!("return {};", value_as(st, &arm.1, &result)?)),
+    )?;
+
+    Ok((
+        format!(
+            "[&]() -> {} {{\n{}\n}}()",
+            result.to_cpp(st)?,
+            inc_indent(format!(
+                "const auto& _match = {};\n{}",
+                value.to_cpp(st)?,
+                branches
+            ))
+        ),
+        result,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_utils::{errors, translate};
+
+    const SHAPE: &str = "enum Shape { Circle(f32), Rect(f32, f32), Empty }\n";
+
+    fn errors_of(main: &str) -> Vec<String> {
+        errors(&format!("{}fn main() -> i32 {{ {} }}", SHAPE, main))
+    }
+
+    #[test]
+    fn enums_are_variants() {
+        let code = translate(&format!(
+            "{}fn main() -> i32 {{
+                let s: Shape = Shape::Rect(2.0, 3.0);
+                let mut area: f32 = 0.0;
+                match s {{
+                    Shape::Circle(r) => {{ area = r * r; }},
+                    Shape::Rect(w, h) => {{ area = w * h; }},
+                    Shape::Empty => {{}},
+                }}
+                return area as i32;
+            }}",
+            SHAPE
+        ));
+
+        assert!(code.contains("std::variant<Circle, Rect, Empty> value;"));
+        assert!(code.contains("const Shape s = Shape{Shape::Rect{2.0, 3.0}};"));
+        assert!(code.contains("if (std::holds_alternative<Shape::Circle>(_match.value)) {"));
+        assert!(code.contains("float h = std::get<Shape::Rect>(_match.value)._1;"));
+    }
+
+    #[test]
+    fn non_exhaustive_match() {
+        assert_eq!(
+            errors_of("let s: Shape = Shape::Empty; match s { Shape::Empty => {}, } return 0;"),
+            ["Non-exhaustive match, missing `Shape::Circle`, `Shape::Rect`"]
+        );
+        assert_eq!(
+            errors_of("let b: bool = true; match b { true => {}, } return 0;"),
+            ["Non-exhaustive match, missing `false`"]
+        );
+        assert_eq!(
+            errors_of("let n: i32 = 1; match n { 1 => {}, } return 0;"),
+            ["Non-exhaustive match, missing `_`"]
+        );
+    }
+
+    #[test]
+    fn wildcards_and_bindings_are_exhaustive() {
+        translate(&format!(
+            "{}fn main() -> i32 {{
+                let s: Shape = Shape::Empty;
+                match s {{ Shape::Circle(_) => {{}}, _ => {{}}, }}
+                let n: i32 = 1;
+                match n {{ 1 => {{}}, other => {{}}, }}
+                return 0;
+            }}",
+            SHAPE
+        ));
+    }
+
+    #[test]
+    fn variant_fields() {
+        assert_eq!(
+            errors_of("let s: Shape = Shape::Rect(1.0); return 0;"),
+            ["Variant `Shape::Rect` has 2 field(s), got 1"]
+        );
+        assert_eq!(
+            errors_of("let s: Shape = Shape::Circle(true); return 0;"),
+            ["Expected type f32, got bool"]
+        );
+        assert_eq!(
+            errors_of(
+                "let s: Shape = Shape::Empty; match s { Shape::Circle(a, b) => {}, _ => {}, } return 0;"
+            ),
+            ["Variant `Shape::Circle` has 1 field(s), pattern has 2"]
+        );
+        assert_eq!(
+            errors_of(
+                "let s: Shape = Shape::Empty; match s { Shape::Square => {}, _ => {}, } return 0;"
+            ),
+            ["No variant `Square` on enum `Shape`"]
+        );
+    }
+
+    #[test]
+    fn match_expressions() {
+        let code = translate(&format!(
+            "{}fn area(s: Shape) -> f32 {{
+                return match s {{
+                    Shape::Circle(r) => 3.0 * r * r,
+                    Shape::Rect(w, h) => w * h,
+                    Shape::Empty => 0.0,
+                }};
+            }}
+            fn main() -> i32 {{
+                let n: i32 = 1;
+                let a: i32 = match n {{ 1 => 2, _ => 3 }};
+                let b = match n {{ 1 => 300, other => other * 2 }};
+                return a + b + area(Shape::Empty) as i32;
+            }}",
+            SHAPE
+        ));
+
+        assert!(code.contains("return [&]() -> float {"));
+        assert!(code.contains("float h = std::get<Shape::Rect>(_match.value)._1;"));
+        assert!(code.contains("return (w * h);"));
+        // The arms are values of the type the match initializes
+        assert!(code.contains("const int32_t a = [&]() -> int32_t {"));
+        assert!(code.contains("const int32_t b = [&]() -> int32_t {"));
+    }
+
+    #[test]
+    fn match_expression_errors() {
+        assert_eq!(
+            errors_of("let s: Shape = Shape::Empty; let a: i32 = match s { Shape::Empty => 1 }; return a;"),
+            ["Non-exhaustive match, missing `Shape::Circle`, `Shape::Rect`"]
+        );
+        assert_eq!(
+            errors_of("let n: i32 = 1; let a = match n { 1 => true, _ => 2 }; return 0;"),
+            ["Cannot convert between bool and u8, use `as` to convert"]
+        );
+        assert_eq!(
+            errors_of("let n: i32 = 1; let a: u8 = match n { 1 => 2, _ => 300 }; return 0;"),
+            ["Literal `300` does not fit in u8"]
+        );
+    }
+}
@@ -1,151 +1,370 @@
 use chumsky::span::SimpleSpan;
-use comfy_types::{Expr, Type};
+use comfy_types::{Argument, Expr, Type};
 use comfy_utils::b;
 
-use super::{ComfyNode, CompileResult, Error, Ident, IdentValue, State};
+use super::{
+    arrays::{args_as, value_as},
+    coercion::{
+        assign_type, cast_type, check_binding, constant_value, factorial_type, literal_to,
+        negative_literal_type, widens,
+    },
+    cpp_ident,
+    enums::{match_casted_to, match_expression, match_type, variant_value},
+    generics::{instantiate, library_associated_call, library_index},
+    places::{check_borrow_mut, check_writable},
+    slices::{element_type, is_range, slice},
+    structs::{associated_call, method_call, resolve_field, struct_literal},
+    tuples::tuple_field,
+    values::negated_literal,
+    ComfyNode, CompileResult, Error, Ident, IdentValue, State,
+};
 
 #[macro_export]
 macro_rules! cast_format {
-    ($l: ident, $op: literal, $r: ident, $st: ident, $self: ident) => {
-        if $l.casted_to(&$r.resolve_type($st)?, $st) {
-            format!("({} {} {})", $l.to_cpp($st)?, $op, $r.to_cpp($st)?)
-        } else {
-            Err(Error::Compile(
-                format!("Cannot cast types, do it manually"),
-                $self.span(),
-            ))?
-        }
-    };
+    ($l: ident, $op: literal, $r: ident, $st: ident) => {{
+        let ty = $crate::compiler::coercion::common_type($st, $l, $r)?;
+        let span = chumsky::span::SimpleSpan::new($l.span().start, $r.span().end);
+        $crate::compiler::coercion::check_operator($st, $op, &ty, span)?;
+
+        format!(
+            "({} {} {})",
+            $crate::compiler::arrays::value_as($st, $l, &ty)?,
+            $op,
+            $crate::compiler::arrays::value_as($st, $r, &ty)?
+        )
+    }};
+}
+
+#[macro_export]
+macro_rules! assign_format {
+    ($l: ident, $op: literal, $r: ident, $st: ident) => {{
+        $crate::compiler::places::check_writable($st, $l)?;
+        let ty = $crate::compiler::coercion::assign_type($st, $l, $r)?;
+
+        format!(
+            "({} {} {})",
+            $l.to_cpp($st)?,
+            $op,
+            $crate::compiler::arrays::value_as($st, $r, &ty)?
+        )
+    }};
 }
 
 #[macro_export]
 macro_rules! cast {
     ($l: ident, $r: ident, $st: ident) => {
-        $l.cast_to(&$r.resolve_type($st)?, $st)
+        $crate::compiler::coercion::common_type($st, $l, $r)
     };
 }
 
+/// Computes the factorial of values only known at run time
+const FACTORIAL: &str = "template <typename T>
+T comfy_factorial(T n) {
+    T result = 1;
+
+    for (T i = 2; i <= n; i++) {
+        result *= i;
+    }
+
+    return result;
+}
+";
+
+/// `value.field[index]`, indexing binds tighter than `.` in the parser
+fn indexed_field(value: &Expr, field: &Expr, index: &Expr) -> Expr {
+    Expr::ArrMember(
+        b(Expr::Member(b(value.clone()), b(field.clone()))),
+        b(index.clone()),
+    )
+}
+
+/// `arr[a..b]` without `&`, a slice does not own its elements
+fn unborrowed_slice(e: &Expr) -> Error {
+    Error::Compile(
+        "Slices must be borrowed, write `&arr[a..b]` or `&mut arr[a..b]`".to_owned(),
+        e.span(),
+    )
+}
+
+/// `&e` or `&mut e` where `e` has type `ty`, references to references would
+/// be emitted as invalid C++
+fn borrowed(ty: Type, mutable: bool, span: SimpleSpan) -> CompileResult<Type> {
+    if let Type::Reference(..) | Type::MutableRef(..) = ty {
+        Err(Error::Compile(
+            format!(
+                "Cannot borrow a value of type {}, references to references are not supported",
+                ty
+            ),
+            span,
+        ))?
+    }
+
+    Ok(match mutable {
+        true => Type::MutableRef(b(ty), span),
+        false => Type::Reference(b(ty), span),
+    })
+}
+
+/// Type written as `e`, like `[P; 4]` where `P` is parsed as an identifier,
+/// `None` if `e` is a value
+fn written_type(st: &State, e: &Expr) -> Option<Type> {
+    match e {
+        Expr::Type(ty) => Some(ty.clone()),
+        Expr::Ident(name, s) => st
+            .get_type(name, *s)
+            .ok()
+            .map(|_| Type::Custom(name.clone(), *s)),
+        Expr::Tuple(items, s) => Some(Type::Tuple(
+            items
+                .iter()
+                .map(|e| written_type(st, e))
+                .collect::<Option<_>>()?,
+            *s,
+        )),
+        Expr::Repeat(item, len, s) => Some(Type::Array(b(written_type(st, item)?), *len, *s)),
+        _ => None,
+    }
+}
+
+/// Type operand of `sizeof` and `alignof`, `None` if the operand is a value
+fn layout_type(st: &mut State, e: &Expr) -> CompileResult<Option<String>> {
+    written_type(st, e).map(|ty| ty.to_cpp(st)).transpose()
+}
+
+/// Checks the arguments of a call, parameters with a default value can be left out
+pub fn check_args(
+    st: &mut State,
+    params: &[Argument],
+    args: &[Expr],
+    span: SimpleSpan,
+) -> CompileResult<()> {
+    let required = params.iter().filter(|arg| arg.2 == Expr::Unknown).count();
+
+    if args.len() < required || args.len() > params.len() {
+        let expected = match required == params.len() {
+            true => required.to_string(),
+            false => format!("{} to {}", required, params.len()),
+        };
+
+        Err(Error::Compile(
+            format!("Expected {} argument(s), got {}", expected, args.len()),
+            span,
+        ))?
+    }
+
+    for (param, arg) in params.iter().zip(args) {
+        check_binding(st, arg, &param.1)?;
+    }
+
+    Ok(())
+}
+
+/// `Type::item`, either an enum variant or an associated function call, user-defined
+/// types shadow the library templates
+fn path(st: &mut State, l: &Expr, r: &Expr) -> CompileResult<(String, Type)> {
+    if let Expr::Ident(name, s) = l {
+        if let Ok(variants) = st.get_enum(name, *s).map(<[_]>::to_vec) {
+            if let Some(value) = variant_value(st, name, &variants, r)? {
+                return Ok(value);
+            }
+        }
+
+        if st.get_type(name, *s).is_err() {
+            if let Some(value) = library_associated_call(name, r)? {
+                return Ok(value);
+            }
+        }
+    }
+
+    associated_call(st, l, r)
+}
+
 impl ComfyNode<String> for Expr {
     fn to_cpp(&self, st: &mut State) -> CompileResult<String> {
         Ok(match self {
             Expr::Literal(l) => l.to_cpp(st)?,
-            Expr::Type(t) => {
-                let ct = t.to_cpp(st)?;
+            Expr::Type(t) => t.to_cpp(st)?,
+            Expr::Ident(i, _) => {
+                st.use_ident(i);
 
-                if ct.1 .0 {
-                    st.errors.push(Error::Compile(
-                        "Cannot cast to array-like type".to_owned(),
-                        self.span(),
-                    ));
+                cpp_ident(i)
+            }
+            Expr::Add(l, r) => cast_format!(l, "+", r, st),
+            Expr::Sub(l, r) => cast_format!(l, "-", r, st),
+            Expr::Mul(l, r) => cast_format!(l, "*", r, st),
+            Expr::Div(l, r) => cast_format!(l, "/", r, st),
+            Expr::Mod(l, r) => cast_format!(l, "%", r, st),
+            Expr::Neg(l) => match l.as_ref() {
+                Expr::Literal(lit) => match negated_literal(st, lit) {
+                    Some(negated) => negated?.0,
+                    None => format!("(-{})", l.to_cpp(st)?),
+                },
+                l => format!("(-{})", l.to_cpp(st)?),
+            },
+            Expr::Pos(l) => format!("({})", l.to_cpp(st)?),
+            Expr::IncR(l) | Expr::IncL(l) | Expr::DecR(l) | Expr::DecL(l) => {
+                check_writable(st, l)?;
+
+                match self {
+                    Expr::IncR(_) => format!("({}++)", l.to_cpp(st)?),
+                    Expr::IncL(_) => format!("(++{})", l.to_cpp(st)?),
+                    Expr::DecR(_) => format!("({}--)", l.to_cpp(st)?),
+                    _ => format!("(--{})", l.to_cpp(st)?),
                 }
+            }
+            Expr::Factorial(r) => {
+                let ty = factorial_type(st, r)?;
 
-                ct.0
+                match constant_value(self) {
+                    Some(v) => format!("static_cast<{}>({})", ty.to_cpp(st)?, v),
+                    None => {
+                        st.add_helper(FACTORIAL);
+
+                        format!("comfy_factorial({})", r.to_cpp(st)?)
+                    }
+                }
             }
-            Expr::Ident(i, _) => i.into(),
-            Expr::Add(l, r) => cast_format!(l, "+", r, st, self),
-            Expr::Sub(l, r) => cast_format!(l, "-", r, st, self),
-            Expr::Mul(l, r) => cast_format!(l, "*", r, st, self),
-            Expr::Div(l, r) => cast_format!(l, "/", r, st, self),
-            Expr::Mod(l, r) => cast_format!(l, "%", r, st, self),
-            Expr::Neg(l) => format!("(-{})", l.to_cpp(st)?),
-            Expr::Pos(l) => format!("({})", l.to_cpp(st)?),
-            Expr::IncR(l) => format!("({}++)", l.to_cpp(st)?),
-            Expr::IncL(r) => format!("(++{})", r.to_cpp(st)?),
-            Expr::DecR(l) => format!("({}--)", l.to_cpp(st)?),
-            Expr::DecL(r) => format!("(--{})", r.to_cpp(st)?),
-            Expr::Factorial(_) => todo!(),
-            Expr::Deref(r) => format!("(*{})", r.to_cpp(st)?),
-            Expr::Address(r) => format!("(&{})", r.to_cpp(st)?),
-            Expr::Eq(l, r) => cast_format!(l, "==", r, st, self),
-            Expr::Ne(l, r) => cast_format!(l, "!=", r, st, self),
-            Expr::Lt(l, r) => cast_format!(l, "<", r, st, self),
-            Expr::Le(l, r) => cast_format!(l, "<=", r, st, self),
-            Expr::Gt(l, r) => cast_format!(l, ">", r, st, self),
-            Expr::Ge(l, r) => cast_format!(l, ">=", r, st, self),
-            Expr::And(l, r) => cast_format!(l, "&&", r, st, self),
-            Expr::Or(l, r) => cast_format!(l, "||", r, st, self),
+            // References are C++ references, only pointers are dereferenced
+            Expr::Deref(r) => match self.resolve_type(st).and(r.resolve_type(st))? {
+                Type::Pointer(_, _) => format!("(*{})", r.to_cpp(st)?),
+                _ => r.to_cpp(st)?,
+            },
+            Expr::Address(r) => match r.as_ref() {
+                Expr::ArrMember(arr, range) if is_range(range) => {
+                    slice(st, arr, range, false, self.span())?.0
+                }
+                _ => r.to_cpp(st)?,
+            },
+            Expr::MutAddress(r) => match r.as_ref() {
+                Expr::ArrMember(arr, range) if is_range(range) => {
+                    slice(st, arr, range, true, self.span())?.0
+                }
+                _ => {
+                    check_borrow_mut(st, r)?;
+
+                    r.to_cpp(st)?
+                }
+            },
+            Expr::Eq(l, r) => cast_format!(l, "==", r, st),
+            Expr::Ne(l, r) => cast_format!(l, "!=", r, st),
+            Expr::Lt(l, r) => cast_format!(l, "<", r, st),
+            Expr::Le(l, r) => cast_format!(l, "<=", r, st),
+            Expr::Gt(l, r) => cast_format!(l, ">", r, st),
+            Expr::Ge(l, r) => cast_format!(l, ">=", r, st),
+            Expr::And(l, r) => cast_format!(l, "&&", r, st),
+            Expr::Or(l, r) => cast_format!(l, "||", r, st),
             Expr::Not(r) => format!("(!{})", r.to_cpp(st)?),
-            Expr::BitAnd(l, r) => cast_format!(l, "&", r, st, self),
-            Expr::BitOr(l, r) => cast_format!(l, "|", r, st, self),
-            Expr::BitXor(l, r) => cast_format!(l, "^", r, st, self),
+            Expr::BitAnd(l, r) => cast_format!(l, "&", r, st),
+            Expr::BitOr(l, r) => cast_format!(l, "|", r, st),
+            Expr::BitXor(l, r) => cast_format!(l, "^", r, st),
             Expr::BitNot(r) => format!("(~{})", r.to_cpp(st)?),
-            Expr::Shl(l, r) => cast_format!(l, "<<", r, st, self),
-            Expr::Shr(l, r) => cast_format!(l, ">>", r, st, self),
-            Expr::Member(l, r) => format!("({}.{})", l.to_cpp(st)?, r.to_cpp(st)?), // TODO: check if member exists
-            Expr::Cast(l, r) => format!("(static_cast<{}>({}))", r.to_cpp(st)?, l.to_cpp(st)?),
-            Expr::Size(r) => format!("(sizeof({}))", r.to_cpp(st)?),
-            Expr::Align(r) => format!("(alignof({}))", r.to_cpp(st)?),
-            Expr::Assign(l, r) => cast_format!(l, "=", r, st, self),
-            Expr::AddAssign(l, r) => cast_format!(l, "+=", r, st, self),
-            Expr::SubAssign(l, r) => cast_format!(l, "-=", r, st, self),
-            Expr::MulAssign(l, r) => cast_format!(l, "*=", r, st, self),
-            Expr::DivAssign(l, r) => cast_format!(l, "/=", r, st, self),
-            Expr::ModAssign(l, r) => cast_format!(l, "%=", r, st, self),
-            Expr::ShlAssign(l, r) => cast_format!(l, "<<=", r, st, self),
-            Expr::ShrAssign(l, r) => cast_format!(l, ">>=", r, st, self),
-            Expr::BitAndAssign(l, r) => cast_format!(l, "&=", r, st, self),
-            Expr::BitXorAssign(l, r) => cast_format!(l, "^=", r, st, self),
-            Expr::BitOrAssign(l, r) => cast_format!(l, "|=", r, st, self),
+            Expr::Shl(l, r) => cast_format!(l, "<<", r, st),
+            Expr::Shr(l, r) => cast_format!(l, ">>", r, st),
+            Expr::Member(l, r) => match r.as_ref() {
+                Expr::Call(method, args, s) => method_call(st, l, method, args, *s)?.0,
+                Expr::Literal(index) => tuple_field(st, l, index)?.0,
+                Expr::ArrMember(field, index) => indexed_field(l, field, index).to_cpp(st)?,
+                _ => {
+                    resolve_field(st, l, r)?;
+
+                    format!("({}.{})", l.to_cpp(st)?, r.to_cpp(st)?)
+                }
+            },
+            Expr::Path(l, r) => path(st, l, r)?.0,
+            Expr::Cast(l, r) => {
+                cast_type(st, l, r)?;
+
+                format!("(static_cast<{}>({}))", r.to_cpp(st)?, l.to_cpp(st)?)
+            }
+            Expr::Size(r) => match layout_type(st, r)? {
+                Some(ty) => format!("(sizeof({}))", ty),
+                None => format!("(sizeof({}))", r.to_cpp(st)?),
+            },
+            Expr::Align(r) => match layout_type(st, r)? {
+                Some(ty) => format!("(alignof({}))", ty),
+                None => format!("(alignof(decltype({})))", r.to_cpp(st)?),
+            },
+            Expr::Assign(l, r) => assign_format!(l, "=", r, st),
+            Expr::AddAssign(l, r) => assign_format!(l, "+=", r, st),
+            Expr::SubAssign(l, r) => assign_format!(l, "-=", r, st),
+            Expr::MulAssign(l, r) => assign_format!(l, "*=", r, st),
+            Expr::DivAssign(l, r) => assign_format!(l, "/=", r, st),
+            Expr::ModAssign(l, r) => assign_format!(l, "%=", r, st),
+            Expr::ShlAssign(l, r) => assign_format!(l, "<<=", r, st),
+            Expr::ShrAssign(l, r) => assign_format!(l, ">>=", r, st),
+            Expr::BitAndAssign(l, r) => assign_format!(l, "&=", r, st),
+            Expr::BitXorAssign(l, r) => assign_format!(l, "^=", r, st),
+            Expr::BitOrAssign(l, r) => assign_format!(l, "|=", r, st),
             Expr::Call(fun, args, _) => {
                 let cfun = fun.to_cpp(st)?;
 
                 let ident = fun.resolve_ident(st)?;
 
                 match &ident.value {
-                    IdentValue::Func(ident_args) => {
-                        let filtered_default_args = ident_args
-                            .iter()
-                            .filter(|arg| arg.2 == Expr::Unknown)
-                            .collect::<Vec<_>>();
-
-                        if args.len() < filtered_default_args.len() {
-                            st.errors.push(Error::Compile(
-                                format!(
-                                    "Expected at least {} argument(s), got {}",
-                                    filtered_default_args.len(),
-                                    args.len()
-                                ),
-                                self.span(),
-                            ));
-                        } else {
-                            let args_types = args
-                                .iter()
-                                .map(|arg| arg.resolve_type(st))
-                                .collect::<Result<Vec<_>, _>>()?;
-
-                            for ((ident_arg, arg_t), arg) in
-                                ident_args.iter().zip(args_types).zip(args)
-                            {
-                                if !ident_arg.1.casted_to(&arg_t, st) {
-                                    return Err(Error::Compile(
-                                        format!("Expected type {}, got {}", ident_arg.1, arg_t),
-                                        arg.span(),
-                                    ));
-                                }
-                            }
-                        }
-
-                        format!("{}({})", cfun, args.to_cpp(st)?)
+                    IdentValue::Func(generics, ident_args) => {
+                        let name = match fun.as_ref() {
+                            Expr::Ident(name, _) => name,
+                            _ => &cfun,
+                        };
+                        let (params, _) = instantiate(
+                            st,
+                            name,
+                            generics,
+                            ident_args,
+                            &ident.return_type,
+                            args,
+                            self.span(),
+                        )?;
+
+                        check_args(st, &params, args, self.span())?;
+
+                        format!("{}({})", cfun, args_as(st, &params, args)?.join(","))
                     }
+                    IdentValue::Struct(_, _) => Err(Error::Compile(
+                        format!("Cannot call struct `{}`", cfun),
+                        self.span(),
+                    ))?,
+                    IdentValue::Enum(_) => Err(Error::Compile(
+                        format!("Cannot call enum `{}`", cfun),
+                        self.span(),
+                    ))?,
+                    IdentValue::TypeParam => Err(Error::Compile(
+                        format!("Cannot call type parameter `{}`", cfun),
+                        self.span(),
+                    ))?,
                     IdentValue::Variable => Err(Error::Compile(
                         format!("Cannot call variable `{}`", cfun),
                         self.span(),
                     ))?,
                 }
             }
+            Expr::ArrMember(_, r) if is_range(r) => Err(unborrowed_slice(self))?,
             Expr::ArrMember(l, r) => format!("({}[{}])", l.to_cpp(st)?, r.to_cpp(st)?),
-            Expr::Tuple(_, _) => todo!(),
-            Expr::Array(v, _) => {
-                let arr = v
-                    .iter()
-                    .map(|s| s.to_cpp(st))
-                    .collect::<Result<Vec<_>, _>>()?
-                    .join(", ");
-
-                format!("{{ {} }}", arr)
+            Expr::Range(_, _) | Expr::RangeInclusive(_, _) => Err(Error::Compile(
+                "Ranges can only be used as `for` loop iterators".to_owned(),
+                self.span(),
+            ))?,
+            Expr::Tuple(v, _) => {
+                st.add_include("<tuple>");
+
+                format!("std::make_tuple({})", v.to_cpp(st)?)
+            }
+            Expr::Array(_, _) | Expr::Repeat(_, _, _) => {
+                let ty = self.resolve_type(st)?;
+
+                value_as(st, self, &ty)?
             }
+            Expr::Struct(name, inits, s) => struct_literal(st, name, inits, *s)?.0,
+            Expr::Match(value, arms, s) => match_expression(st, value, arms, None, *s)?.0,
             Expr::Unknown => Err(Error::Compile("Unknown expression".to_owned(), self.span()))?,
-            Expr::CppCode(v, _) => format!("{}", v),
+            Expr::CppCode(v, _) => {
+                // Identifiers can only be found by name in the raw code
+                for word in v.split(|c: char| !c.is_alphanumeric() && c != '_') {
+                    st.use_ident(word);
+                }
+
+                v.clone()
+            }
         })
     }
 
@@ -168,6 +387,7 @@ impl ComfyNode<String> for Expr {
             Expr::Factorial(v) => v.span(),
             Expr::Deref(v) => v.span(),
             Expr::Address(v) => v.span(),
+            Expr::MutAddress(v) => v.span(),
             Expr::Eq(l, r) => SimpleSpan::new(l.span().start, r.span().end),
             Expr::Ne(l, r) => SimpleSpan::new(l.span().start, r.span().end),
             Expr::Lt(l, r) => SimpleSpan::new(l.span().start, r.span().end),
@@ -184,6 +404,7 @@ impl ComfyNode<String> for Expr {
             Expr::Shl(l, r) => SimpleSpan::new(l.span().start, r.span().end),
             Expr::Shr(l, r) => SimpleSpan::new(l.span().start, r.span().end),
             Expr::Member(l, r) => SimpleSpan::new(l.span().start, r.span().end),
+            Expr::Path(l, r) => SimpleSpan::new(l.span().start, r.span().end),
             Expr::Cast(l, r) => SimpleSpan::new(l.span().start, r.span().end),
             Expr::Size(v) => v.span(),
             Expr::Align(v) => v.span(),
@@ -200,8 +421,13 @@ impl ComfyNode<String> for Expr {
             Expr::BitOrAssign(l, r) => SimpleSpan::new(l.span().start, r.span().end),
             Expr::Call(_, _, s) => *s,
             Expr::ArrMember(l, r) => SimpleSpan::new(l.span().start, r.span().end),
+            Expr::Range(l, r) => SimpleSpan::new(l.span().start, r.span().end),
+            Expr::RangeInclusive(l, r) => SimpleSpan::new(l.span().start, r.span().end),
             Expr::Tuple(_, s) => *s,
             Expr::Array(_, s) => *s,
+            Expr::Repeat(_, _, s) => *s,
+            Expr::Struct(_, _, s) => *s,
+            Expr::Match(_, _, s) => *s,
             Expr::Unknown => SimpleSpan::new(0, 0),
             Expr::CppCode(_, s) => *s,
         }
@@ -217,70 +443,125 @@ impl ComfyNode<String> for Expr {
             Expr::Mul(l, r) => cast!(l, r, st),
             Expr::Div(l, r) => cast!(l, r, st),
             Expr::Mod(l, r) => cast!(l, r, st),
-            Expr::Neg(r) => r.resolve_type(st),
+            Expr::Neg(r) => {
+                if let Expr::Literal(lit) = r.as_ref() {
+                    if let Some(negated) = negated_literal(st, lit) {
+                        return Ok(negated?.1);
+                    }
+                }
+
+                match negative_literal_type(self) {
+                    Some(ty) => Ok(ty),
+                    None => r.resolve_type(st),
+                }
+            }
             Expr::Pos(r) => r.resolve_type(st),
             Expr::IncR(r) => r.resolve_type(st),
             Expr::IncL(r) => r.resolve_type(st),
             Expr::DecR(r) => r.resolve_type(st),
             Expr::DecL(r) => r.resolve_type(st),
-            Expr::Factorial(r) => r.resolve_type(st),
-            Expr::Deref(r) => r.resolve_type(st),
-            Expr::Address(r) => r.resolve_type(st),
-            Expr::Eq(l, r) => cast!(l, r, st),
-            Expr::Ne(l, r) => cast!(l, r, st),
-            Expr::Lt(l, r) => cast!(l, r, st),
-            Expr::Le(l, r) => cast!(l, r, st),
-            Expr::Gt(l, r) => cast!(l, r, st),
-            Expr::Ge(l, r) => cast!(l, r, st),
-            Expr::And(l, r) => cast!(l, r, st),
-            Expr::Or(l, r) => cast!(l, r, st),
-            Expr::Not(r) => r.resolve_type(st),
+            Expr::Factorial(r) => factorial_type(st, r),
+            Expr::Deref(r) => match r.resolve_type(st)? {
+                Type::Reference(t, _) | Type::MutableRef(t, _) | Type::Pointer(t, _) => Ok(*t),
+                ty => Err(Error::Compile(
+                    format!("Cannot dereference a value of type {}", ty),
+                    r.span(),
+                )),
+            },
+            Expr::Address(r) => match r.as_ref() {
+                Expr::ArrMember(arr, range) if is_range(range) => {
+                    Ok(slice(st, arr, range, false, self.span())?.1)
+                }
+                _ => borrowed(r.resolve_type(st)?, false, self.span()),
+            },
+            Expr::MutAddress(r) => match r.as_ref() {
+                Expr::ArrMember(arr, range) if is_range(range) => {
+                    Ok(slice(st, arr, range, true, self.span())?.1)
+                }
+                _ => borrowed(r.resolve_type(st)?, true, self.span()),
+            },
+            Expr::Eq(l, r) => cast!(l, r, st).map(|_| Type::Bool(self.span())),
+            Expr::Ne(l, r) => cast!(l, r, st).map(|_| Type::Bool(self.span())),
+            Expr::Lt(l, r) => cast!(l, r, st).map(|_| Type::Bool(self.span())),
+            Expr::Le(l, r) => cast!(l, r, st).map(|_| Type::Bool(self.span())),
+            Expr::Gt(l, r) => cast!(l, r, st).map(|_| Type::Bool(self.span())),
+            Expr::Ge(l, r) => cast!(l, r, st).map(|_| Type::Bool(self.span())),
+            Expr::And(l, r) => cast!(l, r, st).map(|_| Type::Bool(self.span())),
+            Expr::Or(l, r) => cast!(l, r, st).map(|_| Type::Bool(self.span())),
+            Expr::Not(_) => Ok(Type::Bool(self.span())),
             Expr::BitAnd(l, r) => cast!(l, r, st),
             Expr::BitOr(l, r) => cast!(l, r, st),
             Expr::BitXor(l, r) => cast!(l, r, st),
             Expr::BitNot(r) => r.resolve_type(st),
             Expr::Shl(l, r) => cast!(l, r, st),
             Expr::Shr(l, r) => cast!(l, r, st),
-            Expr::Member(l, r) => cast!(l, r, st),
-            Expr::Cast(l, r) => cast!(l, r, st),
-            Expr::Size(r) => todo!(),
-            Expr::Align(r) => todo!(),
-            Expr::Assign(l, r) => cast!(l, r, st),
-            Expr::AddAssign(l, r) => cast!(l, r, st),
-            Expr::SubAssign(l, r) => cast!(l, r, st),
-            Expr::MulAssign(l, r) => cast!(l, r, st),
-            Expr::DivAssign(l, r) => cast!(l, r, st),
-            Expr::ModAssign(l, r) => cast!(l, r, st),
-            Expr::ShlAssign(l, r) => cast!(l, r, st),
-            Expr::ShrAssign(l, r) => cast!(l, r, st),
-            Expr::BitAndAssign(l, r) => cast!(l, r, st),
-            Expr::BitXorAssign(l, r) => cast!(l, r, st),
-            Expr::BitOrAssign(l, r) => cast!(l, r, st),
-            Expr::Call(l, _, _) => l.resolve_type(st),
+            Expr::Member(l, r) => match r.as_ref() {
+                Expr::Call(method, args, s) => Ok(method_call(st, l, method, args, *s)?.1),
+                Expr::Literal(index) => Ok(tuple_field(st, l, index)?.1),
+                Expr::ArrMember(field, index) => indexed_field(l, field, index).resolve_type(st),
+                _ => Ok(resolve_field(st, l, r)?.2),
+            },
+            Expr::Path(l, r) => Ok(path(st, l, r)?.1),
+            Expr::Cast(l, r) => cast_type(st, l, r),
+            Expr::Size(_) | Expr::Align(_) => Ok(Type::Uint(self.span())),
+            Expr::Assign(l, r) => assign_type(st, l, r),
+            Expr::AddAssign(l, r) => assign_type(st, l, r),
+            Expr::SubAssign(l, r) => assign_type(st, l, r),
+            Expr::MulAssign(l, r) => assign_type(st, l, r),
+            Expr::DivAssign(l, r) => assign_type(st, l, r),
+            Expr::ModAssign(l, r) => assign_type(st, l, r),
+            Expr::ShlAssign(l, r) => assign_type(st, l, r),
+            Expr::ShrAssign(l, r) => assign_type(st, l, r),
+            Expr::BitAndAssign(l, r) => assign_type(st, l, r),
+            Expr::BitXorAssign(l, r) => assign_type(st, l, r),
+            Expr::BitOrAssign(l, r) => assign_type(st, l, r),
+            Expr::Call(l, args, s) => match (l.as_ref(), l.resolve_ident(st)) {
+                (
+                    Expr::Ident(name, _),
+                    Ok(Ident {
+                        return_type,
+                        value: IdentValue::Func(generics, params),
+                        ..
+                    }),
+                ) => Ok(instantiate(st, name, &generics, &params, &return_type, args, *s)?.1),
+                _ => l.resolve_type(st),
+            },
+            Expr::ArrMember(_, r) if is_range(r) => Err(unborrowed_slice(self)),
             Expr::ArrMember(arr, _) => {
                 let t = arr.resolve_type(st)?;
 
                 match t {
-                    Type::Array(t, _, _) => Ok(*t),
-
+                    t if element_type(&t).is_some() => Ok(element_type(&t).unwrap()),
+                    t if library_index(&t).is_some() => Ok(library_index(&t).unwrap()),
                     _ => Err(Error::Compile(
                         "Cannot get member of non array type".to_owned(),
                         self.span(),
                     )),
                 }
             }
-            Expr::Tuple(l, r) => todo!(),
+            Expr::Range(_, _) | Expr::RangeInclusive(_, _) => Err(Error::Compile(
+                "Ranges can only be used as `for` loop iterators".to_owned(),
+                self.span(),
+            )),
+            Expr::Tuple(v, s) => Ok(Type::Tuple(
+                v.iter()
+                    .map(|e| e.resolve_type(st))
+                    .collect::<CompileResult<_>>()?,
+                *s,
+            )),
+            // The elements of `[]` are typed by the binding
+            Expr::Array(v, s) if v.is_empty() => Ok(Type::Array(b(Type::Unknown(*s)), 0, *s)),
             Expr::Array(v, s) => {
                 let size = v.len();
-                let typ = v.first().unwrap().resolve_type(st)?;
+                let typ = v[0].resolve_type(st)?;
 
                 let mut errored = false;
                 for val in v.iter().skip(1) {
                     let t = val.resolve_type(st)?;
 
-                    if t != typ {
+                    if !val.casted_to(&typ, st) {
                         st.errors.push(Error::Compile(
-                            format!("Array types do not match, expected {:?}, got {:?}", typ, t),
+                            format!("Array types do not match, expected {}, got {}", typ, t),
                             val.span(),
                         ));
 
@@ -295,6 +576,9 @@ impl ComfyNode<String> for Expr {
 
                 Ok(Type::Array(b(typ), size.try_into().unwrap(), *s))
             }
+            Expr::Repeat(v, len, s) => Ok(Type::Array(b(v.resolve_type(st)?), *len, *s)),
+            Expr::Struct(name, inits, s) => Ok(struct_literal(st, name, inits, *s)?.1),
+            Expr::Match(value, arms, _) => match_type(st, value, arms),
             Expr::Unknown => Err(Error::Compile("Unknown expression".to_owned(), self.span()))?,
             Expr::CppCode(_, _) => Err(Error::Compile(
                 "Type of cpp code cannot be resolved".to_owned(),
@@ -303,6 +587,32 @@ impl ComfyNode<String> for Expr {
         }
     }
 
+    fn casted_to(&self, ty: &Type, st: &mut State) -> bool {
+        match (self, ty) {
+            (Expr::Array(items, _), Type::Array(item_ty, size, _)) => {
+                items.len() as u64 == *size && items.iter().all(|e| e.casted_to(item_ty, st))
+            }
+            (Expr::Repeat(item, len, _), Type::Array(item_ty, size, _)) => {
+                len == size && item.casted_to(item_ty, st)
+            }
+            (Expr::Tuple(items, _), Type::Tuple(types, _)) => {
+                items.len() == types.len()
+                    && items.iter().zip(types).all(|(e, ty)| e.casted_to(ty, st))
+            }
+            (Expr::Match(value, arms, _), ty) => match_casted_to(st, value, arms, ty),
+            _ => match literal_to(st, self, ty) {
+                Some(fits) => fits,
+                None => match self.resolve_type(st) {
+                    Ok(sty) => widens(&sty, ty),
+                    Err(e) => {
+                        st.errors.push(e);
+                        false
+                    }
+                },
+            },
+        }
+    }
+
     fn resolve_ident(&self, state: &mut State) -> CompileResult<Ident> {
         match self {
             Expr::Ident(ident, span) => Ok(state.get_ident(ident, *span)?.clone()),
@@ -334,3 +644,91 @@ impl ComfyNode<String> for Vec<Expr> {
         Ok(comfy_types::Type::Unknown(self.span()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_utils::{errors, translate};
+
+    const P: &str = "struct P { pub x: i32 }
+        impl P {
+            pub fn new(x: i32) -> P { return P { x: x }; }
+            pub fn get(&self) -> i32 { return self.x; }
+        }
+        fn add(a: i32, b: i32 = 2) -> i32 { return a + b; }
+        ";
+
+    fn errors_of(main: &str) -> Vec<String> {
+        errors(&format!("{}fn main() -> i32 {{ {} }}", P, main))
+    }
+
+    #[test]
+    fn call_arity() {
+        assert_eq!(
+            errors_of("let p: P = P::new(1); return p.get(1);"),
+            ["Expected 0 argument(s), got 1"]
+        );
+        assert_eq!(
+            errors_of("let p: P = P::new(1, 2); return p.get();"),
+            ["Expected 1 argument(s), got 2"]
+        );
+        assert_eq!(
+            errors_of("return add(1, 2, 3);"),
+            ["Expected 1 to 2 argument(s), got 3"]
+        );
+        assert_eq!(
+            errors_of("return add();"),
+            ["Expected 1 to 2 argument(s), got 0"]
+        );
+    }
+
+    #[test]
+    fn call_argument_types() {
+        assert_eq!(
+            errors_of("let p: P = P::new(true); return p.get();"),
+            ["Expected type i32, got bool"]
+        );
+        assert_eq!(
+            errors_of("return add(1, 5i64);"),
+            ["Expected type i32, got i64, use `as` to convert"]
+        );
+    }
+
+    #[test]
+    fn default_arguments() {
+        let code = translate(&format!("{}fn main() -> i32 {{ return add(1); }}", P));
+
+        assert!(code.contains("return add(1);"));
+    }
+
+    #[test]
+    fn references_to_shared_references() {
+        assert_eq!(
+            errors_of("let x: i32 = 1; let r = &x; let rr: &i32 = &r; return 0;"),
+            ["Cannot borrow a value of type &i32, references to references are not supported"]
+        );
+    }
+
+    #[test]
+    fn references_to_mutable_references() {
+        assert_eq!(
+            errors_of("let mut x: i32 = 1; let m = &mut x; let rr: &i32 = &m; return 0;"),
+            ["Cannot borrow a value of type &mut i32, references to references are not supported"]
+        );
+    }
+
+    #[test]
+    fn reference_to_reference_types() {
+        assert_eq!(
+            errors("fn f(a: & &mut i32) -> void { } fn main() -> i32 { return 0; }"),
+            ["Type &&mut i32 is a reference to a reference, which is not supported"]
+        );
+    }
+
+    #[test]
+    fn dereference_of_a_dereferenced_reference() {
+        assert_eq!(
+            errors_of("let mut x: i32 = 1; let m = &mut x; **m = 3; return x;"),
+            ["Cannot dereference a value of type i32"]
+        );
+    }
+}
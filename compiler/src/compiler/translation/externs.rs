@@ -0,0 +1,113 @@
+use chumsky::span::SimpleSpan;
+use comfy_types::{ExternFunction, Type};
+
+use super::{statements::get_real_type_of_argument, ComfyNode, CompileResult, Error, State};
+
+/// `extern "C++" include "<header>" { fn f(x: T) -> R; }`, registers the
+/// signatures so calls are type-checked, the functions come from the headers
+pub fn extern_block(
+    st: &mut State,
+    abi: &str,
+    includes: &[String],
+    functions: &[ExternFunction],
+    span: SimpleSpan,
+) -> CompileResult<String> {
+    if abi != "C++" && abi != "C" {
+        Err(Error::Compile(
+            format!("Unsupported ABI \"{}\", expected \"C++\" or \"C\"", abi),
+            span,
+        ))?
+    }
+
+    for include in includes {
+        if include.starts_with('<') {
+            st.add_include(include);
+        } else {
+            st.add_include(&format!("\"{}\"", include));
+        }
+    }
+
+    for ExternFunction(name, args, ty, s) in functions {
+        let args = args
+            .iter()
+            .map(|a| {
+                let mut a = a.clone();
+                a.1 = get_real_type_of_argument(st, &a.1, &a.2)?;
+
+                // Only checks that the type exists
+                a.1.to_cpp(st)?;
+
+                Ok(a)
+            })
+            .collect::<CompileResult<Vec<_>>>()?;
+
+        if let Type::Unknown(_) = ty {
+            Err(Error::Compile(
+                format!(
+                    "Return type of extern function `{}` must be specified",
+                    name
+                ),
+                *s,
+            ))?
+        }
+
+        ty.to_cpp(st)?;
+
+        st.add_func(name, ty.clone(), vec![], args);
+    }
+
+    Ok("".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_utils::{errors, translate};
+
+    const CMATH: &str = "extern \"C++\" include \"<cmath>\" include \"vendor/lib.h\" {
+            fn sqrtf(x: f32) -> f32;
+            fn powf(base: f32, exp: f32) -> f32;
+        }
+        ";
+
+    #[test]
+    fn includes_and_calls() {
+        let code = translate(&format!(
+            "{}fn main() -> i32 {{ return sqrtf(powf(3.0, 2.0)) as i32; }}",
+            CMATH
+        ));
+
+        assert!(code.contains("#include <cmath>"));
+        assert!(code.contains("#include \"vendor/lib.h\""));
+        assert!(code.contains("sqrtf(powf(3.0,2.0))"));
+    }
+
+    #[test]
+    fn calls_are_type_checked() {
+        let errors_of = |main: &str| errors(&format!("{}fn main() -> i32 {{ {} }}", CMATH, main));
+
+        assert_eq!(
+            errors_of("return sqrtf(true) as i32;"),
+            ["Expected type f32, got bool"]
+        );
+        assert_eq!(
+            errors_of("return powf(2.0) as i32;"),
+            ["Expected 2 argument(s), got 1"]
+        );
+        assert_eq!(
+            errors_of("let x: bool = sqrtf(4.0); return 0;"),
+            ["Expected type bool, got f32"]
+        );
+    }
+
+    #[test]
+    fn signatures_must_be_complete() {
+        assert_eq!(
+            errors("extern \"C++\" { fn f(x: i32); }\nfn main() -> i32 { return 0; }"),
+            ["Return type of extern function `f` must be specified"]
+        );
+        assert_eq!(
+            errors("extern \"Rust\" { fn f() -> i32; }\nfn main() -> i32 { return 0; }"),
+            ["Unsupported ABI \"Rust\", expected \"C++\" or \"C\""]
+        );
+    }
+}
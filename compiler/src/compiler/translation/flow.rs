@@ -0,0 +1,258 @@
+use chumsky::span::SimpleSpan;
+use comfy_types::{Expr, Literal, MatchArm, Statements, Type};
+
+use super::{ComfyNode, Error, Ident, State};
+
+/// Whether `e` calls a function that returns `never`
+fn diverging_call(st: &State, e: &Expr) -> bool {
+    let Expr::Call(f, _, s) = e else {
+        return false;
+    };
+
+    let return_type = match f.as_ref() {
+        Expr::Ident(name, _) => match st.get_ident(name, *s) {
+            Ok(Ident { return_type, .. }) => return_type,
+            Err(_) => return false,
+        },
+        Expr::Path(ty, method) => match (ty.as_ref(), method.as_ref()) {
+            (Expr::Ident(ty, _), Expr::Ident(method, _)) => match st.get_method(ty, method, *s) {
+                Ok(method) => &method.return_type,
+                Err(_) => return false,
+            },
+            _ => return false,
+        },
+        _ => return false,
+    };
+
+    matches!(return_type, Type::Never(_))
+}
+
+/// Whether a `break` in `stmts` leaves the loop they are the body of
+fn breaks(stmts: &[Statements]) -> bool {
+    stmts.iter().any(|stmt| match stmt {
+        Statements::BreakStatement(_) => true,
+        Statements::IfStatement(_, then, els, _) => breaks(then) || breaks(els),
+        Statements::MatchStatement(_, arms, _) => {
+            arms.iter().any(|MatchArm(_, body, _)| breaks(body))
+        }
+        _ => false,
+    })
+}
+
+/// Whether the statement after `stmt` is never reached on any path
+pub fn diverges(st: &State, stmt: &Statements) -> bool {
+    match stmt {
+        Statements::ReturnStatement(_, _)
+        | Statements::BreakStatement(_)
+        | Statements::ContinueStatement(_) => true,
+        Statements::ExpressionStatement(e, _) => diverging_call(st, e),
+        Statements::IfStatement(_, _, els, _) if els.is_empty() => false,
+        Statements::IfStatement(_, then, els, s) => {
+            falls_through(st, then, *s).is_none() && falls_through(st, els, *s).is_none()
+        }
+        Statements::MatchStatement(_, arms, _) => arms
+            .iter()
+            .all(|MatchArm(_, body, arm)| falls_through(st, body, *arm).is_none()),
+        Statements::WhileStatement(Expr::Literal(Literal::True(_)), body, _) => !breaks(body),
+        _ => false,
+    }
+}
+
+/// Where the end of the block `stmts` is reached, `span` if the block is empty
+/// and `None` if every path through it diverges
+pub fn falls_through(st: &State, stmts: &[Statements], span: SimpleSpan) -> Option<SimpleSpan> {
+    if stmts.iter().any(|stmt| diverges(st, stmt)) {
+        return None;
+    }
+
+    match stmts.last() {
+        None => Some(span),
+        // The branch without a `return`, an `if` without `else` itself
+        Some(Statements::IfStatement(_, then, els, s)) => {
+            falls_through(st, then, *s).or_else(|| falls_through(st, els, *s))
+        }
+        Some(Statements::MatchStatement(_, arms, _)) => arms
+            .iter()
+            .find_map(|MatchArm(_, body, arm)| falls_through(st, body, *arm)),
+        Some(stmt) => Some(stmt.span()),
+    }
+}
+
+/// `return`s of `stmts` that return a value, not counting the ones in nested functions
+fn returns(st: &State, stmts: &[Statements], out: &mut Vec<SimpleSpan>) {
+    for stmt in stmts {
+        match stmt {
+            Statements::ReturnStatement(e, s) if !diverging_call(st, e) => out.push(*s),
+            Statements::IfStatement(_, then, els, _) => {
+                returns(st, then, out);
+                returns(st, els, out);
+            }
+            Statements::MatchStatement(_, arms, _) => {
+                for MatchArm(_, body, _) in arms {
+                    returns(st, body, out);
+                }
+            }
+            Statements::WhileStatement(_, body, _) | Statements::ForStatement(_, _, body, _) => {
+                returns(st, body, out)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Checks that every path through the body of a function returning `ty` ends
+/// in a `return`, and that a function returning `never` does not return at all
+pub fn check_returns(st: &mut State, name: &str, ty: &Type, body: &[Statements], span: SimpleSpan) {
+    match ty {
+        Type::Void(_) | Type::Unknown(_) => {}
+        Type::Never(_) => {
+            let mut spans = vec![];
+            returns(st, body, &mut spans);
+
+            if let Some(s) = falls_through(st, body, span) {
+                spans.push(s);
+            }
+
+            for s in spans {
+                st.errors.push(Error::Compile(
+                    format!("Function `{}` returns `never` but can return here", name),
+                    s,
+                ));
+            }
+        }
+        ty => {
+            if let Some(s) = falls_through(st, body, span) {
+                st.errors.push(Error::Compile(
+                    format!(
+                        "Function `{}` must return {} but this path does not return",
+                        name, ty
+                    ),
+                    s,
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_utils::{errors, translate};
+    use crate::{compile_to_cpp, Options};
+
+    /// Source text the errors of `source` point at
+    fn error_spans(source: &str) -> Vec<&str> {
+        compile_to_cpp(source, &Options::default())
+            .unwrap_err()
+            .into_iter()
+            .filter_map(|d| d.span)
+            .map(|s| &source[s.start..s.end])
+            .collect()
+    }
+
+    #[test]
+    fn if_without_else() {
+        let source = "fn f(x: u8) -> u8 {
+            if x > 1 {
+                return x;
+            }
+        }
+
+        fn main() -> i32 {
+            let y: u8 = f(2);
+            return y as i32;
+        }";
+
+        assert_eq!(
+            errors(source),
+            ["Function `f` must return u8 but this path does not return"]
+        );
+        assert!(error_spans(source)[0].starts_with("if x > 1"));
+    }
+
+    #[test]
+    fn every_path_returns() {
+        translate(
+            "fn f(x: u8) -> u8 {
+                if x > 1 {
+                    return x;
+                } else {
+                    return 1;
+                }
+            }
+
+            fn g(x: u8) -> u8 {
+                while true {
+                    if x > 1 {
+                        return x;
+                    }
+                }
+            }
+
+            fn main() -> i32 {
+                let y: u8 = f(2) + g(3);
+                return y as i32;
+            }",
+        );
+    }
+
+    #[test]
+    fn missing_return_in_branch() {
+        let source = "fn f(x: u8) -> u8 {
+            if x > 1 {
+                return x;
+            } else {
+                let _y: u8 = x + 1;
+            }
+        }
+
+        fn main() -> i32 {
+            let y: u8 = f(2);
+            return y as i32;
+        }";
+
+        assert_eq!(
+            errors(source),
+            ["Function `f` must return u8 but this path does not return"]
+        );
+        assert_eq!(error_spans(source), ["let _y: u8 = x + 1;"]);
+    }
+
+    #[test]
+    fn never_calls_diverge() {
+        translate(
+            "fn fail() -> never {
+                while true {}
+            }
+
+            fn f(x: u8) -> u8 {
+                if x > 1 {
+                    return x;
+                }
+                fail();
+            }
+
+            fn main() -> i32 {
+                let y: u8 = f(2);
+                return y as i32;
+            }",
+        );
+    }
+
+    #[test]
+    fn never_functions_cannot_return() {
+        let source = "fn fail(x: u8) -> never {
+            if x > 1 {
+                while true {}
+            }
+        }
+
+        fn main() -> i32 {
+            fail(2);
+        }";
+
+        assert_eq!(
+            errors(source),
+            ["Function `fail` returns `never` but can return here"]
+        );
+    }
+}
@@ -0,0 +1,678 @@
+use std::collections::HashMap;
+
+use chumsky::span::SimpleSpan;
+use comfy_types::{Argument, Expr, FieldInit, MatchArm, MatchExprArm, Statements, Type};
+use comfy_utils::b;
+
+use super::{
+    arrays::value_as, cpp_ident, places::check_borrow_mut, ComfyNode, CompileResult, Error, Ident,
+    IdentValue, State,
+};
+
+/// Comfy name, C++ template, header and number of type parameters
+const LIBRARY_TEMPLATES: &[(&str, &str, &str, usize)] = &[
+    ("Vec", "std::vector", "<vector>", 1),
+    ("Option", "std::optional", "<optional>", 1),
+    ("HashMap", "std::unordered_map", "<unordered_map>", 2),
+];
+
+fn library_template(
+    name: &str,
+) -> Option<&'static (&'static str, &'static str, &'static str, usize)> {
+    LIBRARY_TEMPLATES.iter().find(|t| t.0 == name)
+}
+
+fn check_arity(name: &str, expected: usize, args: &[Type], span: SimpleSpan) -> CompileResult<()> {
+    if expected != args.len() {
+        Err(Error::Compile(
+            format!(
+                "Type `{}` expects {} type argument(s), got {}",
+                name,
+                expected,
+                args.len()
+            ),
+            span,
+        ))?
+    }
+
+    Ok(())
+}
+
+/// Translates `Name<Args>`, either a generic struct or one of the library templates
+pub fn generic_type(
+    st: &mut State,
+    name: &str,
+    args: &[Type],
+    span: SimpleSpan,
+) -> CompileResult<String> {
+    let cargs = args
+        .iter()
+        .map(|ty| ty.to_cpp(st))
+        .collect::<CompileResult<Vec<_>>>()?
+        .join(", ");
+
+    let template = if st.get_struct(name, span).is_ok() {
+        check_arity(name, st.get_generics(name, span)?.len(), args, span)?;
+
+        cpp_ident(name)
+    } else if let Some((_, template, include, params)) = library_template(name) {
+        check_arity(name, *params, args, span)?;
+        st.add_include(include);
+
+        template.to_string()
+    } else {
+        Err(Error::Compile(
+            format!("Unknown generic type: {}", name),
+            span,
+        ))?
+    };
+
+    Ok(format!("{}<{}>", template, cargs))
+}
+
+/// Registers `generics` in the current scope and returns the `template <...>`
+/// line of the declaration
+pub fn template_head(
+    st: &mut State,
+    generics: &[String],
+    span: SimpleSpan,
+) -> CompileResult<String> {
+    if generics.is_empty() {
+        return Ok("".to_owned());
+    }
+
+    for (i, param) in generics.iter().enumerate() {
+        if generics[..i].contains(param) {
+            Err(Error::Compile(
+                format!("Type parameter `{}` is declared more than once", param),
+                span,
+            ))?
+        }
+
+        st.add_type_param(param, span);
+    }
+
+    Ok(format!(
+        "template <{}>\n",
+        generics
+            .iter()
+            .map(|g| format!("typename {}", cpp_ident(g)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))
+}
+
+/// Replaces type parameters in `ty` by the types bound to them
+pub fn substitute(ty: &Type, bindings: &HashMap<String, Type>) -> Type {
+    let sub = |t: &Type| b(substitute(t, bindings));
+
+    match ty {
+        Type::Custom(name, _) => bindings.get(name).cloned().unwrap_or_else(|| ty.clone()),
+        Type::Tuple(types, s) => {
+            Type::Tuple(types.iter().map(|t| substitute(t, bindings)).collect(), *s)
+        }
+        Type::Array(t, size, s) => Type::Array(sub(t), *size, *s),
+        Type::Slice(t, s) => Type::Slice(sub(t), *s),
+        Type::Pointer(t, s) => Type::Pointer(sub(t), *s),
+        Type::MutableRef(t, s) => Type::MutableRef(sub(t), *s),
+        Type::Reference(t, s) => Type::Reference(sub(t), *s),
+        Type::Generic(name, types, s) => Type::Generic(
+            name.clone(),
+            types.iter().map(|t| substitute(t, bindings)).collect(),
+            *s,
+        ),
+        _ => ty.clone(),
+    }
+}
+
+/// Whether `ty` is a type parameter of the function or struct being translated
+pub fn is_type_param(st: &State, ty: &Type) -> bool {
+    match ty {
+        Type::Custom(name, s) => matches!(
+            st.get_ident(name, *s),
+            Ok(Ident {
+                value: IdentValue::TypeParam,
+                ..
+            })
+        ),
+        _ => false,
+    }
+}
+
+/// Whether a type parameter appears anywhere in `ty`
+fn has_type_param(st: &State, ty: &Type) -> bool {
+    match ty {
+        Type::Tuple(types, _) | Type::Generic(_, types, _) => {
+            types.iter().any(|t| has_type_param(st, t))
+        }
+        Type::Array(t, _, _)
+        | Type::Slice(t, _)
+        | Type::Pointer(t, _)
+        | Type::MutableRef(t, _)
+        | Type::Reference(t, _) => has_type_param(st, t),
+        _ => is_type_param(st, ty),
+    }
+}
+
+/// Replaces type parameters in the types written in `e`
+fn substitute_expr(e: &Expr, bindings: &HashMap<String, Type>) -> Expr {
+    let sub = |e: &Expr| b(substitute_expr(e, bindings));
+    let sub_all = |v: &[Expr]| v.iter().map(|e| substitute_expr(e, bindings)).collect();
+
+    macro_rules! map {
+        (unary: [$($u: ident),*], binary: [$($bi: ident),*], $($rest: tt)*) => {
+            match e {
+                $(Expr::$u(r) => Expr::$u(sub(r)),)*
+                $(Expr::$bi(l, r) => Expr::$bi(sub(l), sub(r)),)*
+                $($rest)*
+            }
+        };
+    }
+
+    map! {
+        unary: [
+            Neg, Pos, IncR, IncL, DecR, DecL, Factorial, Deref, Address, MutAddress, Not, BitNot,
+            Size, Align
+        ],
+        binary: [
+            Add, Sub, Mul, Div, Mod, Eq, Ne, Lt, Le, Gt, Ge, And, Or, BitAnd, BitOr, BitXor, Shl,
+            Shr, Member, Path, Cast, Assign, AddAssign, SubAssign, MulAssign, DivAssign, ModAssign,
+            ShlAssign, ShrAssign, BitAndAssign, BitXorAssign, BitOrAssign, ArrMember, Range,
+            RangeInclusive
+        ],
+        Expr::Type(ty) => Expr::Type(substitute(ty, bindings)),
+        Expr::Call(f, args, s) => Expr::Call(sub(f), sub_all(args), *s),
+        Expr::Tuple(v, s) => Expr::Tuple(sub_all(v), *s),
+        Expr::Array(v, s) => Expr::Array(sub_all(v), *s),
+        Expr::Repeat(v, len, s) => Expr::Repeat(sub(v), *len, *s),
+        Expr::Struct(name, inits, s) => Expr::Struct(
+            name.clone(),
+            inits
+                .iter()
+                .map(|i| FieldInit(i.0.clone(), substitute_expr(&i.1, bindings), i.2))
+                .collect(),
+            *s,
+        ),
+        Expr::Match(value, arms, s) => Expr::Match(
+            sub(value),
+            arms.iter()
+                .map(|arm| MatchExprArm(arm.0.clone(), substitute_expr(&arm.1, bindings), arm.2))
+                .collect(),
+            *s,
+        ),
+        Expr::Literal(_) | Expr::CppCode(_, _) | Expr::Ident(_, _) | Expr::Unknown => e.clone(),
+    }
+}
+
+/// Replaces type parameters in the types written in the statement `s`
+fn substitute_statement(s: &Statements, bindings: &HashMap<String, Type>) -> Statements {
+    let ty = |t: &Type| substitute(t, bindings);
+    let expr = |e: &Expr| substitute_expr(e, bindings);
+    let body = |v: &[Statements]| {
+        v.iter()
+            .map(|s| substitute_statement(s, bindings))
+            .collect()
+    };
+
+    match s {
+        Statements::ExpressionStatement(e, s) => Statements::ExpressionStatement(expr(e), *s),
+        Statements::LetStatement(name, mutable, t, e, s) => {
+            Statements::LetStatement(name.clone(), *mutable, ty(t), expr(e), *s)
+        }
+        Statements::LetTupleStatement(names, t, e, s) => {
+            Statements::LetTupleStatement(names.clone(), ty(t), expr(e), *s)
+        }
+        Statements::IfStatement(c, then, els, s) => {
+            Statements::IfStatement(expr(c), body(then), body(els), *s)
+        }
+        Statements::MatchStatement(e, arms, s) => Statements::MatchStatement(
+            expr(e),
+            arms.iter()
+                .map(|arm| MatchArm(arm.0.clone(), body(&arm.1), arm.2))
+                .collect(),
+            *s,
+        ),
+        Statements::WhileStatement(c, b, s) => Statements::WhileStatement(expr(c), body(b), *s),
+        Statements::ForStatement(name, it, b, s) => {
+            Statements::ForStatement(name.clone(), expr(it), body(b), *s)
+        }
+        Statements::ReturnStatement(e, s) => Statements::ReturnStatement(expr(e), *s),
+        _ => s.clone(),
+    }
+}
+
+/// Checks the body of the generic function `name` again with its type
+/// parameters bound to `bindings`, errors are reported at the call. Every
+/// instantiation is checked once
+fn check_instantiation(
+    st: &mut State,
+    name: &str,
+    generics: &[String],
+    bindings: &HashMap<String, Type>,
+    span: SimpleSpan,
+) {
+    let Some(Statements::FunctionDeclaration(access, _, _, args, ty, body, s)) =
+        st.generic_functions.get(name).cloned()
+    else {
+        return;
+    };
+
+    // Type arguments of an enclosing generic function are checked when it is instantiated
+    if bindings.values().any(|t| has_type_param(st, t)) {
+        return;
+    }
+
+    let instance = format!(
+        "{}<{}>",
+        name,
+        generics
+            .iter()
+            .map(|g| bindings[g].to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    if !st.instantiations.insert(instance.clone()) {
+        return;
+    }
+
+    let args = args
+        .iter()
+        .map(|a| {
+            Argument(
+                a.0.clone(),
+                substitute(&a.1, bindings),
+                substitute_expr(&a.2, bindings),
+                a.3,
+            )
+        })
+        .collect();
+    let body = body
+        .iter()
+        .map(|s| substitute_statement(s, bindings))
+        .collect();
+    let declaration = Statements::FunctionDeclaration(
+        access,
+        name.to_owned(),
+        vec![],
+        args,
+        substitute(&ty, bindings),
+        body,
+        s,
+    );
+
+    // The body only sees the items of the module, its lints were reported
+    // with the generic declaration
+    let scopes = st.scope_stack.split_off(1);
+    let loop_depth = st.loop_depth;
+    let return_type = st.return_type.clone();
+    let snapshots = st.snapshots.take();
+    let errors = st.errors.len();
+    let warnings = st.warnings.len();
+
+    st.scope_stack.push(HashMap::new());
+    let result = declaration.to_cpp(st);
+
+    st.scope_stack.truncate(1);
+    st.scope_stack.extend(scopes);
+    st.loop_depth = loop_depth;
+    st.return_type = return_type;
+    st.snapshots = snapshots;
+    st.warnings.truncate(warnings);
+
+    let error = st
+        .errors
+        .split_off(errors)
+        .into_iter()
+        .chain(result.err())
+        .find_map(|e| match e {
+            Error::Compile(message, _) => Some(message),
+            _ => None,
+        });
+
+    if let Some(message) = error {
+        st.error(format!("In `{}`: {}", instance, message), span);
+    }
+}
+
+/// Binds the type parameters in `param` by matching it against `arg`,
+/// structural mismatches are left to the regular type check
+fn infer(
+    generics: &[String],
+    param: &Type,
+    arg: &Type,
+    bindings: &mut HashMap<String, Type>,
+    span: SimpleSpan,
+) -> CompileResult<()> {
+    match (param, arg) {
+        (Type::Custom(name, _), _) if generics.contains(name) => match bindings.get(name) {
+            Some(bound) if bound != arg => Err(Error::Compile(
+                format!(
+                    "Type parameter `{}` is inferred as both {} and {}",
+                    name, bound, arg
+                ),
+                span,
+            ))?,
+            Some(_) => {}
+            None => {
+                bindings.insert(name.clone(), arg.clone());
+            }
+        },
+        // Arrays are borrowed as `&[T]`
+        (Type::Reference(p, _), a @ (Type::Array(..) | Type::MutableRef(..)))
+            if matches!(p.as_ref(), Type::Slice(..)) =>
+        {
+            let a = match a {
+                Type::MutableRef(a, _) => a,
+                a => a,
+            };
+
+            infer(generics, p, a, bindings, span)?
+        }
+        (Type::Array(p, _, _), Type::Array(a, _, _))
+        | (Type::Slice(p, _), Type::Slice(a, _) | Type::Array(a, _, _))
+        | (Type::Pointer(p, _), Type::Pointer(a, _))
+        | (Type::MutableRef(p, _), Type::MutableRef(a, _))
+        | (Type::Reference(p, _), Type::Reference(a, _)) => infer(generics, p, a, bindings, span)?,
+        (Type::Tuple(ps, _), Type::Tuple(args, _)) if ps.len() == args.len() => {
+            for (p, a) in ps.iter().zip(args) {
+                infer(generics, p, a, bindings, span)?;
+            }
+        }
+        (Type::Generic(pn, ps, _), Type::Generic(an, args, _))
+            if pn == an && ps.len() == args.len() =>
+        {
+            for (p, a) in ps.iter().zip(args) {
+                infer(generics, p, a, bindings, span)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Infers the type arguments of `name` from `values` matched against `types`
+pub fn infer_bindings(
+    st: &mut State,
+    name: &str,
+    generics: &[String],
+    types: &[Type],
+    values: &[&Expr],
+    span: SimpleSpan,
+) -> CompileResult<HashMap<String, Type>> {
+    let mut bindings = HashMap::new();
+
+    for (ty, value) in types.iter().zip(values) {
+        let value_t = value.resolve_type(st)?;
+
+        infer(generics, ty, &value_t, &mut bindings, value.span())?;
+    }
+
+    if let Some(param) = generics.iter().find(|g| !bindings.contains_key(*g)) {
+        Err(Error::Compile(
+            format!("Cannot infer type parameter `{}` of `{}`", param, name),
+            span,
+        ))?
+    }
+
+    Ok(bindings)
+}
+
+/// Parameters and return type of the instantiation of a generic function for `args`
+pub fn instantiate(
+    st: &mut State,
+    name: &str,
+    generics: &[String],
+    params: &[Argument],
+    return_type: &Type,
+    args: &[Expr],
+    span: SimpleSpan,
+) -> CompileResult<(Vec<Argument>, Type)> {
+    if generics.is_empty() {
+        return Ok((params.to_vec(), return_type.clone()));
+    }
+
+    let types = params.iter().map(|p| p.1.clone()).collect::<Vec<_>>();
+    let values = args.iter().collect::<Vec<_>>();
+    let bindings = infer_bindings(st, name, generics, &types, &values, span)?;
+
+    check_instantiation(st, name, generics, &bindings, span);
+
+    let params = params
+        .iter()
+        .map(|p| Argument(p.0.clone(), substitute(&p.1, &bindings), p.2.clone(), p.3))
+        .collect();
+
+    Ok((params, substitute(return_type, &bindings)))
+}
+
+/// Type arguments bound by a value of type `ty`, references are dereferenced automatically
+pub fn type_bindings(st: &State, ty: &Type) -> CompileResult<HashMap<String, Type>> {
+    match ty {
+        Type::Generic(name, args, s) if st.get_struct(name, *s).is_ok() => Ok(st
+            .get_generics(name, *s)?
+            .iter()
+            .cloned()
+            .zip(args.iter().cloned())
+            .collect()),
+        Type::Reference(t, _) | Type::MutableRef(t, _) => type_bindings(st, t),
+        _ => Ok(HashMap::new()),
+    }
+}
+
+/// Element type of `value[index]` for library containers
+pub fn library_index(ty: &Type) -> Option<Type> {
+    match ty {
+        Type::Generic(name, args, _) if name == "Vec" => args.first().cloned(),
+        Type::Generic(name, args, _) if name == "HashMap" => args.get(1).cloned(),
+        Type::Reference(t, _) | Type::MutableRef(t, _) => library_index(t),
+        _ => None,
+    }
+}
+
+/// `Template::new()` for library templates, `None` if `name` is not one. The
+/// type arguments are taken from the declaration the value is assigned to
+pub fn library_associated_call(name: &str, item: &Expr) -> CompileResult<Option<(String, Type)>> {
+    let Some((_, _, _, params)) = library_template(name) else {
+        return Ok(None);
+    };
+
+    match item {
+        Expr::Call(f, args, s)
+            if args.is_empty() && matches!(f.as_ref(), Expr::Ident(n, _) if n == "new") =>
+        {
+            Ok(Some((
+                "{}".to_owned(),
+                Type::Generic(name.to_owned(), vec![Type::Unknown(*s); *params], *s),
+            )))
+        }
+        _ => Err(Error::Compile(
+            format!("Only `{}::new()` is supported", name),
+            item.span(),
+        )),
+    }
+}
+
+/// Methods of the library templates as their C++ name, parameter types and return type
+fn library_method(
+    name: &str,
+    args: &[Type],
+    method: &str,
+    span: SimpleSpan,
+) -> Option<(&'static str, Vec<Type>, Type)> {
+    let arg = |i: usize| args[i].clone();
+
+    Some(match (name, method) {
+        ("Vec", "push") => ("push_back", vec![arg(0)], Type::Void(span)),
+        ("Vec", "pop") => ("pop_back", vec![], Type::Void(span)),
+        ("Vec", "len") => ("size", vec![], Type::U64(span)),
+        ("Vec", "clear") => ("clear", vec![], Type::Void(span)),
+        ("Option", "is_some") => ("has_value", vec![], Type::Bool(span)),
+        ("Option", "unwrap") => ("value", vec![], arg(0)),
+        ("HashMap", "len") => ("size", vec![], Type::U64(span)),
+        ("HashMap", "contains_key") => ("contains", vec![arg(0)], Type::Bool(span)),
+        ("HashMap", "insert") => ("insert_or_assign", vec![arg(0), arg(1)], Type::Void(span)),
+        _ => return None,
+    })
+}
+
+/// Methods of library templates that modify the value they are called on
+fn mutating(method: &str) -> bool {
+    matches!(method, "push" | "pop" | "clear" | "insert")
+}
+
+/// `value.method(args)` on a library template, `None` if `ty` is not one
+pub fn library_method_call(
+    st: &mut State,
+    ty: &Type,
+    value: &Expr,
+    method: &Expr,
+    args: &[Expr],
+    span: SimpleSpan,
+) -> CompileResult<Option<(String, Type)>> {
+    let (name, type_args) = match ty {
+        Type::Generic(name, type_args, _) if library_template(name).is_some() => (name, type_args),
+        Type::Reference(t, _) | Type::MutableRef(t, _) => {
+            return library_method_call(st, t, value, method, args, span)
+        }
+        _ => return Ok(None),
+    };
+
+    let Expr::Ident(method_name, s) = method else {
+        Err(Error::Compile(
+            "Expected method name".to_owned(),
+            method.span(),
+        ))?
+    };
+
+    let (cname, params, return_type) = library_method(name, type_args, method_name, *s)
+        .ok_or_else(|| {
+            Error::Compile(
+                format!("No method `{}` on type `{}`", method_name, name),
+                *s,
+            )
+        })?;
+
+    if params.len() != args.len() {
+        Err(Error::Compile(
+            format!("Expected {} argument(s), got {}", params.len(), args.len()),
+            span,
+        ))?
+    }
+
+    if mutating(method_name) {
+        check_borrow_mut(st, value)?;
+    }
+
+    let cargs = params
+        .iter()
+        .zip(args)
+        .map(|(param, arg)| {
+            let arg_t = arg.resolve_type(st)?;
+
+            if !arg.casted_to(param, st) {
+                Err(Error::Compile(
+                    format!("Expected type {}, got {}", param, arg_t),
+                    arg.span(),
+                ))?
+            }
+
+            value_as(st, arg, param)
+        })
+        .collect::<CompileResult<Vec<_>>>()?;
+
+    Ok(Some((
+        format!("{}.{}({})", value.to_cpp(st)?, cname, cargs.join(", ")),
+        return_type,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_utils::{errors, translate};
+
+    const MAX: &str = "struct P { pub x: i32 }
+        fn max<T>(a: T, b: T) -> T { if a > b { return a; } else { return b; } }
+        fn positive<T>(a: T) -> bool { return a > 0; }
+        ";
+
+    fn source(main: &str) -> String {
+        format!("{}fn main() -> i32 {{ {} }}", MAX, main)
+    }
+
+    #[test]
+    fn instantiation_with_supported_operators() {
+        let code = translate(&source(
+            "let a: i32 = max(3, 7); if positive(a) { return a; } else { return 0; }",
+        ));
+
+        assert!(code.contains("template <typename T>"));
+    }
+
+    #[test]
+    fn instantiation_with_unsupported_operator() {
+        let errors = errors(&source(
+            "let p: P = max(P { x: 1 }, P { x: 2 }); let q: P = max(p, p); return q.x;",
+        ));
+
+        assert_eq!(errors, ["In `max<P>`: Operator `>` is not defined for P"]);
+    }
+
+    #[test]
+    fn instantiation_with_literal_operand() {
+        let errors = errors(&source(
+            "if positive(P { x: 1 }) { return 1; } else { return 0; }",
+        ));
+
+        assert_eq!(
+            errors,
+            ["In `positive<P>`: Cannot convert between P and u8, use `as` to convert"]
+        );
+    }
+
+    #[test]
+    fn nested_instantiation() {
+        let errors = errors(&format!(
+            "{}fn larger<T>(a: T) -> T {{ return max(a, a); }}
+            fn main() -> i32 {{ let p: P = larger(P {{ x: 1 }}); return p.x; }}",
+            MAX
+        ));
+
+        assert_eq!(
+            errors,
+            ["In `larger<P>`: In `max<P>`: Operator `>` is not defined for P"]
+        );
+    }
+
+    #[test]
+    fn struct_operands() {
+        let errors = errors(&source(
+            "let p: P = P { x: 1 }; if p == p { return 1; } else { return 0; }",
+        ));
+
+        assert_eq!(errors, ["Operator `==` is not defined for P"]);
+    }
+
+    #[test]
+    fn written_types_are_checked_before_values() {
+        assert_eq!(
+            errors(
+                "struct Pair<T> { pub a: T, pub b: T }
+                fn main() -> i32 {
+                    let p: Pair<i32, i32> = Pair { a: 1, b: 2 };
+                    return 0;
+                }"
+            ),
+            ["Type `Pair` expects 1 type argument(s), got 2"]
+        );
+        assert_eq!(
+            errors("fn main() -> i32 { let p: Foo<i32> = 1; return 0; }"),
+            ["Unknown generic type: Foo"]
+        );
+        assert_eq!(
+            errors("fn main() -> i32 { let (a, b): (Foo<i32>, u8) = (1, 2); return 0; }"),
+            ["Unknown generic type: Foo"]
+        );
+    }
+}
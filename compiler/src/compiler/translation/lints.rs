@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+/// Check that reports code that compiles but is likely a mistake
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    UnusedVariables,
+    UnusedFunctions,
+    UnreachableCode,
+    ShadowedBindings,
+    ImplicitNarrowing,
+}
+
+pub const LINTS: &[Lint] = &[
+    Lint::UnusedVariables,
+    Lint::UnusedFunctions,
+    Lint::UnreachableCode,
+    Lint::ShadowedBindings,
+    Lint::ImplicitNarrowing,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl Lint {
+    /// Name used on the command line and as the code of the diagnostic
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnusedVariables => "unused_variables",
+            Lint::UnusedFunctions => "unused_functions",
+            Lint::UnreachableCode => "unreachable_code",
+            Lint::ShadowedBindings => "shadowed_bindings",
+            Lint::ImplicitNarrowing => "implicit_narrowing",
+        }
+    }
+
+    pub fn default_level(&self) -> Level {
+        match self {
+            Lint::ShadowedBindings => Level::Allow,
+            _ => Level::Warn,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        LINTS.iter().copied().find(|lint| lint.name() == name)
+    }
+}
+
+/// Levels of the lints, the ones not set keep their default level
+#[derive(Debug, Clone, Default)]
+pub struct Lints(HashMap<Lint, Level>);
+
+impl Lints {
+    pub fn set(&mut self, lint: Lint, level: Level) {
+        self.0.insert(lint, level);
+    }
+
+    pub fn level(&self, lint: Lint) -> Level {
+        self.0
+            .get(&lint)
+            .copied()
+            .unwrap_or_else(|| lint.default_level())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use comfy_types::Severity;
+
+    use super::{Level, Lint, Lints};
+    use crate::{compile_to_cpp, Options};
+
+    const SOURCE: &str = "fn main() -> i32 {
+        let x: i32 = 1;
+        let y: i32 = 2;
+        let y: i32 = y + 1;
+        return y;
+    }";
+
+    /// Severity and code of the diagnostics of `SOURCE`
+    fn diagnostics(levels: &[(Lint, Level)]) -> Vec<(Severity, String)> {
+        let mut lints = Lints::default();
+
+        for (lint, level) in levels {
+            lints.set(*lint, *level);
+        }
+
+        let options = Options {
+            lints,
+            ..Options::default()
+        };
+
+        let diagnostics = match compile_to_cpp(SOURCE, &options) {
+            Ok(output) => output.warnings,
+            Err(diagnostics) => diagnostics,
+        };
+
+        diagnostics
+            .into_iter()
+            .map(|d| (d.severity, d.code))
+            .collect()
+    }
+
+    #[test]
+    fn default_levels() {
+        assert_eq!(
+            diagnostics(&[]),
+            [(Severity::Warning, "unused_variables".to_owned())]
+        );
+    }
+
+    #[test]
+    fn allow_warn_and_deny() {
+        assert_eq!(diagnostics(&[(Lint::UnusedVariables, Level::Allow)]), []);
+        assert_eq!(
+            diagnostics(&[(Lint::ShadowedBindings, Level::Warn)]),
+            [
+                (Severity::Warning, "unused_variables".to_owned()),
+                (Severity::Warning, "shadowed_bindings".to_owned())
+            ]
+        );
+        assert_eq!(
+            diagnostics(&[(Lint::UnusedVariables, Level::Deny)]),
+            [(Severity::Error, "unused_variables".to_owned())]
+        );
+    }
+}
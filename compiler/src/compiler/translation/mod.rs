@@ -1,38 +1,55 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
 
 use chumsky::span::SimpleSpan;
-use comfy_types::{Argument, Ast, Type};
+use comfy_types::{AccessModifier, Argument, Diagnostic, Field, Statements, Type, Variant};
+use lints::{Level, Lint, Lints};
+use modules::Module;
 
 pub mod access_modifier;
+pub mod arrays;
+pub mod coercion;
+pub mod enums;
 pub mod expression;
+pub mod externs;
+pub mod flow;
+pub mod generics;
+pub mod lints;
+pub mod modules;
+pub mod places;
+pub mod slices;
 pub mod statements;
+pub mod structs;
+#[cfg(test)]
+mod test_utils;
+pub mod tuples;
 pub mod values;
 
 pub trait ComfyNode<T> {
     fn to_cpp(&self, state: &mut State) -> CompileResult<T>;
     fn span(&self) -> SimpleSpan;
     fn resolve_ident(&self, _state: &mut State) -> CompileResult<Ident> {
-        Err(Error::Compile(format!("Unimplemented"), self.span()))
+        Err(Error::Compile("Unimplemented".to_owned(), self.span()))
     }
     fn resolve_type(&self, state: &mut State) -> CompileResult<Type>;
+    /// Whether the value converts to `ty` implicitly
     fn casted_to(&self, ty: &Type, state: &mut State) -> bool {
-        let sty = self.resolve_type(state);
-
-        if let Err(e) = sty {
-            state.errors.push(e);
-            return false;
-        } else if let Ok(sty) = sty {
-            return ty == &sty; // todo: pattern matching for all types
+        match self.resolve_type(state) {
+            Ok(sty) => coercion::widens(&sty, ty),
+            Err(e) => {
+                state.errors.push(e);
+                false
+            }
         }
-
-        false
     }
 
     fn cast_to(&self, ty: &Type, state: &mut State) -> CompileResult<Type> {
         if self.casted_to(ty, state) {
             Ok(self.resolve_type(state)?)
         } else {
-            Err(Error::Compile(format!("Cannot cast",), self.span()))
+            Err(Error::Compile("Cannot cast".to_owned(), self.span()))
         }
     }
 }
@@ -40,25 +57,82 @@ pub trait ComfyNode<T> {
 #[derive(Debug, Clone)]
 pub enum Error {
     Compile(String, SimpleSpan),
+    /// Error in the imported module at the given path
+    Module(String, Box<Error>),
     Clang(String),
+    /// Error raised while interpreting the program
+    Runtime(String, SimpleSpan),
+    /// Lexer or parser error
+    Syntax(Box<Diagnostic>),
+    /// Finding of a lint, an error when the lint is denied
+    Lint(Lint, String, SimpleSpan),
 }
 
-pub type CompileResult<T> = Result<T, Error>;
+impl From<Error> for Diagnostic {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Compile(msg, s) => Diagnostic::error("Compiler", msg, Some(s)),
+            Error::Runtime(msg, s) => Diagnostic::error("Runtime", msg, Some(s)),
+            Error::Clang(msg) => Diagnostic::error("Clang", msg, None),
+            Error::Syntax(diagnostic) => *diagnostic,
+            Error::Lint(lint, msg, s) => Diagnostic::error(lint.name(), msg, Some(s)),
+            // The innermost module is the file the span points into
+            Error::Module(file, e) => {
+                let diagnostic = Diagnostic::from(*e);
 
-#[derive(Debug, Clone)]
-pub struct Compiler {
-    pub ast: Ast,
+                Diagnostic {
+                    file: diagnostic.file.or(Some(file)),
+                    ..diagnostic
+                }
+            }
+        }
+    }
 }
 
+pub type CompileResult<T> = Result<T, Error>;
+
+pub type Scopes = Vec<HashMap<String, Ident>>;
+
 #[derive(Debug, Clone)]
 pub struct State {
     pub errors: Vec<Error>,
-    pub scope_stack: Vec<HashMap<String, Ident>>,
+    /// Findings of the lints at the warn level
+    pub warnings: Vec<Error>,
+    pub lints: Lints,
+    pub scope_stack: Scopes,
+    pub loop_depth: usize,
+    /// Return type of the function being translated, `return` values are
+    /// translated as values of it
+    pub return_type: Option<Type>,
+    pub impls: HashMap<String, Vec<Method>>,
+    pub self_type: Option<String>,
+    /// Declarations of the generic functions of the module being translated,
+    /// their bodies are checked again for the type arguments of every call
+    pub generic_functions: HashMap<String, Statements>,
+    /// Instantiations already checked, like `max<i32>`
+    pub instantiations: HashSet<String>,
+    /// Headers required by the translated code, in order of inclusion
+    pub includes: Vec<String>,
+    /// Runtime helpers used by the translated code, emitted after the includes
+    pub helpers: Vec<String>,
+    /// Directory imports are resolved from
+    pub root: PathBuf,
+    pub modules: HashMap<String, Module>,
+    /// Modules being translated, the innermost last
+    pub module_stack: Vec<String>,
+    /// Translated code of the imported modules in dependency order
+    pub module_code: Vec<String>,
+    /// Scopes after every translated statement of the root module, only
+    /// recorded when set, editor tooling looks identifiers up in them
+    pub snapshots: Option<Vec<(SimpleSpan, Scopes)>>,
 }
 
 #[derive(Debug, Clone)]
 pub enum IdentValue {
-    Func(Vec<Argument>),
+    Func(Vec<String>, Vec<Argument>),
+    Struct(Vec<String>, Vec<Field>),
+    Enum(Vec<Variant>),
+    TypeParam,
     Variable,
 }
 
@@ -66,31 +140,318 @@ pub enum IdentValue {
 pub struct Ident {
     pub return_type: Type,
     pub value: IdentValue,
+    /// Declaration of a binding or function the unused lints report
+    pub declared: Option<(Lint, SimpleSpan)>,
+    pub used: bool,
+    /// Variable declared with `let mut`, the others can not be assigned to
+    pub mutable: bool,
+    /// Argument of the function being translated, arguments can not be declared `mut`
+    pub argument: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Method {
+    pub access_modifier: AccessModifier,
+    pub name: String,
+    /// Type of `self`, `None` for associated functions
+    pub receiver: Option<Type>,
+    pub args: Vec<Argument>,
+    pub return_type: Type,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl State {
     pub fn new() -> Self {
         Self {
             errors: Vec::new(),
+            warnings: Vec::new(),
+            lints: Lints::default(),
             scope_stack: vec![HashMap::new()],
+            loop_depth: 0,
+            return_type: None,
+            impls: HashMap::new(),
+            self_type: None,
+            generic_functions: HashMap::new(),
+            instantiations: HashSet::new(),
+            includes: vec![
+                "<iostream>".to_owned(),
+                "<stdint.h>".to_owned(),
+                "<string>".to_owned(),
+            ],
+            helpers: Vec::new(),
+            root: PathBuf::new(),
+            modules: HashMap::new(),
+            module_stack: Vec::new(),
+            module_code: Vec::new(),
+            snapshots: None,
+        }
+    }
+
+    /// Resolves imports relative to `input_file`, which is the root module
+    pub fn set_input_file(&mut self, input_file: &str) {
+        let input_file = Path::new(input_file);
+
+        self.root = input_file.parent().unwrap_or(Path::new("")).to_path_buf();
+        self.module_stack.push(
+            input_file
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        );
+    }
+
+    pub fn record_snapshot(&mut self, span: SimpleSpan) {
+        if let Some(snapshots) = &mut self.snapshots {
+            snapshots.push((span, self.scope_stack.clone()));
+        }
+    }
+
+    pub fn add_include(&mut self, header: &str) {
+        if !self.includes.iter().any(|h| h == header) {
+            self.includes.push(header.to_owned());
+        }
+    }
+
+    pub fn add_helper(&mut self, code: &str) {
+        if !self.helpers.iter().any(|h| h == code) {
+            self.helpers.push(code.to_owned());
         }
     }
 
     pub fn set_ident(&mut self, ident: &str, return_type: Type, value: IdentValue) {
-        self.scope_stack
-            .last_mut()
-            .unwrap()
-            .insert(ident.to_owned(), Ident { return_type, value });
+        let replaced = self.scope_stack.last_mut().unwrap().insert(
+            ident.to_owned(),
+            Ident {
+                return_type,
+                value,
+                declared: None,
+                used: false,
+                mutable: false,
+                argument: false,
+            },
+        );
+
+        // A binding redeclared in the same scope is never used after this
+        if let Some(replaced) = replaced {
+            self.report_unused(vec![(ident.to_owned(), replaced)]);
+        }
+    }
+
+    /// Declares a variable of the source, it is reported if it shadows
+    /// another variable or is never used
+    pub fn add_binding(&mut self, ident: &str, return_type: Type, mutable: bool, span: SimpleSpan) {
+        if let Ok(Ident {
+            value: IdentValue::Variable,
+            ..
+        }) = self.get_ident(ident, span)
+        {
+            self.lint(
+                Lint::ShadowedBindings,
+                format!("`{}` shadows a previous binding", ident),
+                span,
+            );
+        }
+
+        self.add_variable(ident, return_type, mutable);
+        self.track_usage(ident, Lint::UnusedVariables, span);
+    }
+
+    /// Reports `ident` of the innermost scope with `lint` if it is not used
+    /// before the scope ends, names starting with `_` are never reported
+    pub fn track_usage(&mut self, ident: &str, lint: Lint, span: SimpleSpan) {
+        if ident.starts_with('_') {
+            return;
+        }
+
+        if let Some(ident) = self.scope_stack.last_mut().unwrap().get_mut(ident) {
+            ident.declared = Some((lint, span));
+        }
+    }
+
+    pub fn use_ident(&mut self, ident: &str) {
+        if let Some(ident) = self
+            .scope_stack
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(ident))
+        {
+            ident.used = true;
+        }
+    }
+
+    /// Pops the innermost scope and reports its unused bindings
+    pub fn pop_scope(&mut self) {
+        if let Some(scope) = self.scope_stack.pop() {
+            self.report_unused(scope.into_iter().collect());
+        }
     }
 
-    pub fn add_variable(&mut self, ident: &str, return_type: Type) {
+    /// Reports the unused items of the outermost scope, which is never popped
+    pub fn report_unused_items(&mut self) {
+        if let Some(scope) = self.scope_stack.first().cloned() {
+            self.report_unused(scope.into_iter().collect());
+        }
+    }
+
+    fn report_unused(&mut self, idents: Vec<(String, Ident)>) {
+        let mut unused = idents
+            .into_iter()
+            .filter(|(_, ident)| !ident.used)
+            .filter_map(|(name, ident)| ident.declared.map(|(lint, span)| (span, lint, name)))
+            .collect::<Vec<_>>();
+
+        unused.sort_by_key(|(span, _, _)| span.start);
+
+        for (span, lint, name) in unused {
+            let message = match lint {
+                Lint::UnusedFunctions => format!("Function `{}` is never used", name),
+                _ => format!("Unused variable `{}`", name),
+            };
+
+            self.lint(lint, message, span);
+        }
+    }
+
+    /// Reports `message` at the level `lint` is set to
+    pub fn lint(&mut self, lint: Lint, message: String, span: SimpleSpan) {
+        // Types are resolved more than once, every finding is reported once
+        let reported = self
+            .warnings
+            .iter()
+            .chain(&self.errors)
+            .any(|e| matches!(e, Error::Lint(l, _, s) if *l == lint && *s == span));
+
+        if reported {
+            return;
+        }
+
+        let e = Error::Lint(lint, message, span);
+
+        match self.lints.level(lint) {
+            Level::Allow => {}
+            Level::Warn => self.warnings.push(e),
+            Level::Deny => self.errors.push(e),
+        }
+    }
+
+    /// Reports an error and keeps translating, like lints an error found
+    /// again when a type is resolved twice is reported once
+    pub fn error(&mut self, message: String, span: SimpleSpan) {
+        let reported = self
+            .errors
+            .iter()
+            .any(|e| matches!(e, Error::Compile(m, s) if *m == message && *s == span));
+
+        if !reported {
+            self.errors.push(Error::Compile(message, span));
+        }
+    }
+
+    pub fn add_variable(&mut self, ident: &str, return_type: Type, mutable: bool) {
         self.set_ident(ident, return_type, IdentValue::Variable);
+
+        if let Some(ident) = self.scope_stack.last_mut().unwrap().get_mut(ident) {
+            ident.mutable = mutable;
+        }
+    }
+
+    pub fn add_func(
+        &mut self,
+        ident: &str,
+        return_type: Type,
+        generics: Vec<String>,
+        args: Vec<Argument>,
+    ) {
+        self.set_ident(ident, return_type, IdentValue::Func(generics, args));
+    }
+
+    pub fn add_struct(
+        &mut self,
+        ident: &str,
+        generics: Vec<String>,
+        fields: Vec<Field>,
+        span: SimpleSpan,
+    ) {
+        let ty = Type::Custom(ident.to_owned(), span);
+
+        self.set_ident(ident, ty, IdentValue::Struct(generics, fields));
+    }
+
+    pub fn get_struct(&self, ident: &str, span: SimpleSpan) -> CompileResult<&[Field]> {
+        match self.get_ident(ident, span) {
+            Ok(Ident {
+                value: IdentValue::Struct(_, fields),
+                ..
+            }) => Ok(fields),
+            _ => Err(Error::Compile(format!("Unknown type: {}", ident), span)),
+        }
+    }
+
+    pub fn add_enum(&mut self, ident: &str, variants: Vec<Variant>, span: SimpleSpan) {
+        let ty = Type::Custom(ident.to_owned(), span);
+
+        self.set_ident(ident, ty, IdentValue::Enum(variants));
+    }
+
+    pub fn get_enum(&self, ident: &str, span: SimpleSpan) -> CompileResult<&[Variant]> {
+        match self.get_ident(ident, span) {
+            Ok(Ident {
+                value: IdentValue::Enum(variants),
+                ..
+            }) => Ok(variants),
+            _ => Err(Error::Compile(format!("Unknown enum: {}", ident), span)),
+        }
+    }
+
+    pub fn add_type_param(&mut self, ident: &str, span: SimpleSpan) {
+        let ty = Type::Custom(ident.to_owned(), span);
+
+        self.set_ident(ident, ty, IdentValue::TypeParam);
+    }
+
+    /// Type parameters of the function or struct named `ident`
+    pub fn get_generics(&self, ident: &str, span: SimpleSpan) -> CompileResult<&[String]> {
+        match self.get_ident(ident, span)? {
+            Ident {
+                value: IdentValue::Func(generics, _) | IdentValue::Struct(generics, _),
+                ..
+            } => Ok(generics),
+            _ => Ok(&[]),
+        }
+    }
+
+    /// Checks that `ident` names a user-defined type
+    pub fn get_type(&self, ident: &str, span: SimpleSpan) -> CompileResult<&Ident> {
+        match self.get_ident(ident, span) {
+            Ok(
+                ident @ Ident {
+                    value: IdentValue::Struct(_, _) | IdentValue::Enum(_) | IdentValue::TypeParam,
+                    ..
+                },
+            ) => Ok(ident),
+            _ => Err(Error::Compile(format!("Unknown type: {}", ident), span)),
+        }
+    }
+
+    pub fn get_method(&self, ty: &str, method: &str, span: SimpleSpan) -> CompileResult<&Method> {
+        self.impls
+            .get(ty)
+            .and_then(|methods| methods.iter().find(|m| m.name == method))
+            .ok_or_else(|| Error::Compile(format!("No method `{}` on type `{}`", method, ty), span))
     }
 
-    pub fn add_func(&mut self, ident: &str, return_type: Type, args: Vec<Argument>) {
-        self.set_ident(ident, return_type, IdentValue::Func(args));
+    /// Whether private members of `ty` are accessible from the current impl
+    pub fn can_access(&self, ty: &str, access_modifier: &AccessModifier) -> bool {
+        matches!(access_modifier, AccessModifier::Public(_))
+            || self.self_type.as_deref() == Some(ty)
     }
 
+    #[allow(dead_code)]
     pub fn get_ident_mut(&mut self, ident: &str, span: SimpleSpan) -> CompileResult<&mut Ident> {
         for scope in self.scope_stack.iter_mut().rev() {
             if let Some(ident) = scope.get_mut(ident) {
@@ -118,4 +479,72 @@ impl State {
     }
 }
 
-pub struct TypeInfo(pub bool, pub Option<u64>);
+const CPP_KEYWORDS: &[&str] = &[
+    "alignas",
+    "and",
+    "and_eq",
+    "asm",
+    "auto",
+    "bitand",
+    "bitor",
+    "case",
+    "catch",
+    "class",
+    "compl",
+    "const",
+    "constexpr",
+    "default",
+    "delete",
+    "do",
+    "double",
+    "enum",
+    "explicit",
+    "export",
+    "extern",
+    "float",
+    "friend",
+    "goto",
+    "inline",
+    "long",
+    "mutable",
+    "namespace",
+    "new",
+    "noexcept",
+    "not",
+    "not_eq",
+    "nullptr",
+    "operator",
+    "or",
+    "or_eq",
+    "private",
+    "protected",
+    "public",
+    "register",
+    "short",
+    "signed",
+    "static",
+    "switch",
+    "template",
+    "this",
+    "throw",
+    "try",
+    "typedef",
+    "typeid",
+    "typename",
+    "union",
+    "unsigned",
+    "using",
+    "virtual",
+    "volatile",
+    "xor",
+    "xor_eq",
+];
+
+/// Name of a Comfy identifier in the emitted C++, C++ keywords get a trailing `_`
+pub fn cpp_ident(name: &str) -> String {
+    if CPP_KEYWORDS.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_owned()
+    }
+}
@@ -0,0 +1,327 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use chumsky::span::SimpleSpan;
+use comfy_parser::parse;
+use comfy_types::{AccessModifier, Ast, Statements};
+use comfy_utils::inc_indent;
+
+use super::{cpp_ident, ComfyNode, CompileResult, Error, Ident, Method, State};
+
+#[derive(Debug, Clone)]
+pub struct Module {
+    /// Top-level identifiers of the module
+    pub idents: HashMap<String, Ident>,
+    /// Items declared with `pub`
+    pub exports: Vec<String>,
+    pub impls: HashMap<String, Vec<Method>>,
+}
+
+/// Names of the top-level items declared with `pub`
+fn exports(ast: &Ast) -> Vec<String> {
+    ast.iter()
+        .filter_map(|stmt| match stmt {
+            Statements::FunctionDeclaration(AccessModifier::Public(_), name, ..)
+            | Statements::StructDeclaration(AccessModifier::Public(_), name, ..)
+            | Statements::EnumDeclaration(AccessModifier::Public(_), name, ..) => {
+                Some(name.clone())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Attributes errors raised while translating a module to its file
+fn in_module(file: &Path, e: Error) -> Error {
+    match e {
+        Error::Compile(_, _) | Error::Syntax(_) | Error::Lint(_, _, _) => {
+            Error::Module(file.display().to_string(), Box::new(e))
+        }
+        e => e,
+    }
+}
+
+/// Parses and translates the module at `path` unless it was loaded before,
+/// its code is emitted before the code of the module importing it
+fn load_module(st: &mut State, path: &[String], span: SimpleSpan) -> CompileResult<()> {
+    let name = path.join("::");
+
+    if st.modules.contains_key(&name) {
+        return Ok(());
+    }
+
+    if let Some(i) = st.module_stack.iter().position(|m| m == &name) {
+        let cycle = [&st.module_stack[i..], std::slice::from_ref(&name)].concat();
+
+        Err(Error::Compile(
+            format!("Import cycle: {}", cycle.join(" -> ")),
+            span,
+        ))?
+    }
+
+    let file = path
+        .iter()
+        .fold(st.root.clone(), |file, segment| file.join(segment))
+        .with_extension("co");
+
+    let src = fs::read_to_string(&file).map_err(|_| {
+        Error::Compile(
+            format!("Module `{}` not found at {}", name, file.display()),
+            span,
+        )
+    })?;
+
+    let ast = parse(&src).map_err(|diagnostics| {
+        st.errors.extend(
+            diagnostics
+                .into_iter()
+                .map(|d| in_module(&file, Error::Syntax(Box::new(d)))),
+        );
+
+        Error::Compile(format!("Module `{}` could not be parsed", name), span)
+    })?;
+
+    // Every module is translated in its own namespace
+    let scope_stack = std::mem::replace(&mut st.scope_stack, vec![HashMap::new()]);
+    let impls = std::mem::take(&mut st.impls);
+    let self_type = st.self_type.take();
+    let generic_functions = std::mem::take(&mut st.generic_functions);
+    let instantiations = std::mem::take(&mut st.instantiations);
+    let loop_depth = std::mem::take(&mut st.loop_depth);
+    let errors = st.errors.len();
+    let warnings = st.warnings.len();
+    // Spans of the module refer to another file
+    let snapshots = st.snapshots.take();
+
+    st.module_stack.push(name.clone());
+    let code = ast.to_cpp(st);
+
+    if code.is_ok() {
+        st.report_unused_items();
+    }
+
+    st.module_stack.pop();
+
+    let idents = std::mem::replace(&mut st.scope_stack, scope_stack).remove(0);
+    let module_impls = std::mem::replace(&mut st.impls, impls);
+    st.self_type = self_type;
+    st.generic_functions = generic_functions;
+    st.instantiations = instantiations;
+    st.loop_depth = loop_depth;
+    st.snapshots = snapshots;
+
+    let module_errors = st.errors.split_off(errors);
+    st.errors
+        .extend(module_errors.into_iter().map(|e| in_module(&file, e)));
+
+    let module_warnings = st.warnings.split_off(warnings);
+    st.warnings
+        .extend(module_warnings.into_iter().map(|e| in_module(&file, e)));
+
+    let code = code.map_err(|e| in_module(&file, e))?;
+
+    st.module_code.push(format!(
+        "namespace {} {{\n{}\n}}\n\n",
+        cpp_path(path),
+        inc_indent(code)
+    ));
+
+    st.modules.insert(
+        name,
+        Module {
+            idents,
+            exports: exports(&ast),
+            impls: module_impls,
+        },
+    );
+
+    Ok(())
+}
+
+fn cpp_path(path: &[String]) -> String {
+    path.iter()
+        .map(|segment| cpp_ident(segment))
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// `use path::to::module::{items};`, brings public items of the module into scope
+pub fn use_statement(
+    st: &mut State,
+    path: &[String],
+    items: &[String],
+    span: SimpleSpan,
+) -> CompileResult<String> {
+    load_module(st, path, span)?;
+
+    let name = path.join("::");
+    let module = st.modules[&name].clone();
+
+    let mut usings = vec![];
+
+    for item in items {
+        let ident = module.idents.get(item).cloned().ok_or_else(|| {
+            Error::Compile(format!("No item `{}` in module `{}`", item, name), span)
+        })?;
+
+        if !module.exports.contains(item) {
+            Err(Error::Compile(
+                format!("`{}` is private in module `{}`", item, name),
+                span,
+            ))?
+        }
+
+        if let Some(methods) = module.impls.get(item) {
+            st.impls.insert(item.clone(), methods.clone());
+        }
+
+        st.set_ident(item, ident.return_type, ident.value);
+
+        usings.push(format!("using {}::{};", cpp_path(path), cpp_ident(item)));
+    }
+
+    Ok(format!("{}\n", usings.join("\n")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use comfy_types::{Diagnostic, Severity};
+
+    use crate::{compile_to_cpp, CppOutput, Options};
+
+    /// Writes the files of a project into a fresh directory named `name`
+    /// and compiles its `main.co`
+    fn compile(name: &str, files: &[(&str, &str)]) -> Result<CppOutput, Vec<Diagnostic>> {
+        let root: PathBuf = env::temp_dir().join(format!("comfy-modules-{}", name));
+        fs::remove_dir_all(&root).ok();
+
+        for (path, src) in files {
+            let file = root.join(path);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, src).unwrap();
+        }
+
+        let main = root.join("main.co");
+        let options = Options {
+            input_file: main.display().to_string(),
+            ..Options::default()
+        };
+
+        compile_to_cpp(&fs::read_to_string(&main).unwrap(), &options)
+    }
+
+    fn errors(name: &str, files: &[(&str, &str)]) -> Vec<String> {
+        match compile(name, files) {
+            Ok(output) => panic!("translation succeeded:\n{}", output.code),
+            Err(diagnostics) => diagnostics
+                .into_iter()
+                .filter(|d| d.severity == Severity::Error)
+                .map(|d| d.message)
+                .collect(),
+        }
+    }
+
+    const VEC: &str = "pub struct Vec2 { pub x: i32, pub y: i32 }
+        fn square(v: i32) -> i32 { return v * v; }
+        pub fn length_squared(v: Vec2) -> i32 { return square(v.x) + square(v.y); }
+        ";
+
+    #[test]
+    fn public_items() {
+        let main = "use math::vec::{Vec2, length_squared};
+            fn main() -> i32 { let v: Vec2 = Vec2 { x: 1, y: 2 }; return length_squared(v); }";
+        let code = compile("public", &[("main.co", main), ("math/vec.co", VEC)])
+            .unwrap()
+            .code;
+
+        assert!(code.contains("namespace math::vec {"));
+        assert!(code.contains("using math::vec::Vec2;"));
+        assert!(code.contains("using math::vec::length_squared;"));
+    }
+
+    #[test]
+    fn private_and_missing_items() {
+        let main =
+            |item: &str| format!("use math::vec::{};\nfn main() -> i32 {{ return 0; }}", item);
+
+        assert_eq!(
+            errors(
+                "private",
+                &[("main.co", &main("square")), ("math/vec.co", VEC)]
+            ),
+            ["`square` is private in module `math::vec`"]
+        );
+        assert_eq!(
+            errors(
+                "missing-item",
+                &[("main.co", &main("cube")), ("math/vec.co", VEC)]
+            ),
+            ["No item `cube` in module `math::vec`"]
+        );
+    }
+
+    #[test]
+    fn missing_module() {
+        let errors = errors(
+            "missing-module",
+            &[("main.co", "use nope::X;\nfn main() -> i32 { return 0; }")],
+        );
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("Module `nope` not found at "));
+    }
+
+    #[test]
+    fn import_cycle() {
+        let errors = errors(
+            "cycle",
+            &[
+                ("main.co", "use a::f;\nfn main() -> i32 { return f(); }"),
+                ("a.co", "use b::g;\npub fn f() -> i32 { return g(); }"),
+                ("b.co", "use a::f;\npub fn g() -> i32 { return 1; }"),
+            ],
+        );
+
+        assert!(errors.contains(&"Import cycle: a -> b -> a".to_owned()));
+    }
+
+    #[test]
+    fn modules_are_translated_once() {
+        let code = compile(
+            "once",
+            &[
+                (
+                    "main.co",
+                    "use a::f;\nuse b::g;\nfn main() -> i32 { return f() + g(); }",
+                ),
+                ("a.co", "use c::one;\npub fn f() -> i32 { return one(); }"),
+                ("b.co", "use c::one;\npub fn g() -> i32 { return one(); }"),
+                ("c.co", "pub fn one() -> i32 { return 1; }"),
+            ],
+        )
+        .unwrap()
+        .code;
+
+        assert_eq!(code.matches("namespace c {").count(), 1);
+    }
+
+    #[test]
+    fn errors_point_into_the_module() {
+        let diagnostics = compile(
+            "module-errors",
+            &[
+                ("main.co", "use m::f;\nfn main() -> i32 { return f(); }"),
+                ("m.co", "pub fn f() -> i32 { return zz; }"),
+            ],
+        )
+        .unwrap_err();
+
+        let error = diagnostics
+            .iter()
+            .find(|d| d.message == "Unknown identifier: zz")
+            .unwrap();
+
+        assert!(error.file.as_ref().unwrap().ends_with("m.co"));
+    }
+}
@@ -0,0 +1,160 @@
+use chumsky::span::SimpleSpan;
+use comfy_types::{Expr, Type};
+
+use super::{slices::is_slice, ComfyNode, CompileResult, Error, State};
+
+/// How a place is written, for the error on an immutable binding
+#[derive(Clone, Copy)]
+enum Write {
+    Assign,
+    BorrowMut,
+}
+
+/// Checks that the variable `name` was declared with `let mut`
+fn check_mutable(st: &State, name: &str, span: SimpleSpan, write: Write) -> CompileResult<()> {
+    let ident = st.get_ident(name, span)?;
+
+    if ident.mutable {
+        return Ok(());
+    }
+
+    let (kind, hint) = if ident.argument {
+        (
+            "argument",
+            "function arguments are immutable, copy it into a `let mut`",
+        )
+    } else {
+        ("immutable binding", "declare it with `let mut`")
+    };
+
+    let message = match write {
+        Write::Assign => format!("Cannot assign to {} `{}`", kind, name),
+        Write::BorrowMut => format!("Cannot borrow {} `{}` as mutable", kind, name),
+    };
+
+    Err(Error::Compile(format!("{}, {}", message, hint), span))
+}
+
+/// Checks that `e` is a place that can be assigned to, immutable bindings
+/// and writes through a shared reference are rejected
+pub fn check_writable(st: &mut State, e: &Expr) -> CompileResult<()> {
+    writable(st, e, Write::Assign)
+}
+
+fn writable(st: &mut State, e: &Expr, write: Write) -> CompileResult<()> {
+    match e {
+        Expr::Ident(name, s) => match &st.get_ident(name, *s)?.return_type {
+            Type::Reference(..) | Type::MutableRef(..) => Err(Error::Compile(
+                format!(
+                    "References cannot be reassigned, write through `*{}` instead",
+                    name
+                ),
+                *s,
+            )),
+            _ => check_mutable(st, name, *s, write),
+        },
+        Expr::Deref(r) => writable_through(st, r, write),
+        Expr::Member(l, _) | Expr::ArrMember(l, _) => writable_through(st, l, write),
+        Expr::Literal(l) => Err(Error::Compile(
+            "Cannot write to a literal".to_owned(),
+            l.span(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that the value `e` refers to can be written, like for `&mut e` or a
+/// method taking `&mut self`. `e` is the value itself unless it is a reference
+/// or a pointer
+pub fn check_borrow_mut(st: &mut State, e: &Expr) -> CompileResult<()> {
+    writable_through(st, e, Write::BorrowMut)
+}
+
+fn writable_through(st: &mut State, e: &Expr, write: Write) -> CompileResult<()> {
+    match e.resolve_type(st)? {
+        ty @ Type::Reference(..) if is_slice(&ty) => Err(Error::Compile(
+            format!("Cannot write through a shared slice of type {}", ty),
+            e.span(),
+        )),
+        ty @ Type::Reference(..) => Err(Error::Compile(
+            format!("Cannot write through a shared reference of type {}", ty),
+            e.span(),
+        )),
+        Type::MutableRef(..) | Type::Pointer(..) => Ok(()),
+        _ => match e {
+            Expr::Ident(name, s) => check_mutable(st, name, *s, write),
+            e => writable(st, e, write),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_utils::{errors, translate};
+
+    #[test]
+    fn assign_to_immutable_binding() {
+        let errors = errors("fn main() -> i32 { let x: i32 = 1; x = 2; return x; }");
+        assert_eq!(
+            errors,
+            ["Cannot assign to immutable binding `x`, declare it with `let mut`"]
+        );
+    }
+
+    #[test]
+    fn assign_to_argument() {
+        let found =
+            errors("fn f(a: i32) -> i32 { a = 2; return a; } fn main() -> i32 { return f(1); }");
+        assert_eq!(
+            found,
+            ["Cannot assign to argument `a`, function arguments are immutable, copy it into a `let mut`"]
+        );
+
+        let found = errors(
+            "fn f(a: i32) -> i32 { let r: &mut i32 = &mut a; return *r; } fn main() -> i32 { return f(1); }",
+        );
+        assert_eq!(
+            found,
+            ["Cannot borrow argument `a` as mutable, function arguments are immutable, copy it into a `let mut`"]
+        );
+
+        let found = translate(
+            "fn f(a: i32) -> i32 { let mut b: i32 = a; b = 2; return b; } fn main() -> i32 { return f(1); }",
+        );
+        assert!(found.contains("(b = 2);"), "{}", found);
+    }
+
+    #[test]
+    fn borrow_immutable_binding_as_mutable() {
+        let found =
+            errors("fn main() -> i32 { let x: i32 = 1; let r: &mut i32 = &mut x; return *r; }");
+        assert_eq!(
+            found,
+            ["Cannot borrow immutable binding `x` as mutable, declare it with `let mut`"]
+        );
+
+        let found = errors(
+            "fn main() -> i32 { let a: [i32; 2] = [1, 2]; let r: &mut i32 = &mut a[0]; return *r; }",
+        );
+        assert_eq!(
+            found,
+            ["Cannot borrow immutable binding `a` as mutable, declare it with `let mut`"]
+        );
+    }
+
+    #[test]
+    fn write_through_shared_reference() {
+        let errors = errors("fn set(r: &i32) -> void { *r = 2; } fn main() -> i32 { return 0; }");
+        assert_eq!(
+            errors,
+            ["Cannot write through a shared reference of type &i32"]
+        );
+    }
+
+    #[test]
+    fn mutable_bindings() {
+        translate(
+            "fn main() -> i32 { let mut x: i32 = 1; x = 2; let r: &mut i32 = &mut x; *r = 3; return x; }",
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use chumsky::span::SimpleSpan;
+use comfy_types::{Expr, Type};
+
+use super::{
+    coercion::{constant_value, is_integer},
+    places::check_borrow_mut,
+    ComfyNode, CompileResult, Error, State,
+};
+
+/// Element type of the array or slice behind `ty`, references are dereferenced automatically
+pub fn element_type(ty: &Type) -> Option<Type> {
+    match ty {
+        Type::Array(t, _, _) | Type::Slice(t, _) => Some(*t.clone()),
+        Type::Reference(t, _) | Type::MutableRef(t, _) => element_type(t),
+        _ => None,
+    }
+}
+
+/// Elements of `ty` when it can be passed where a slice is expected, arrays
+/// and borrowed arrays or slices are borrowed as `&[T]`, only mutable borrows
+/// as `&mut [T]`
+pub fn borrowed_elements(ty: &Type, mutable: bool) -> Option<&Type> {
+    match ty {
+        Type::Array(t, _, _) if !mutable => Some(t),
+        Type::Reference(t, _) if !mutable => match t.as_ref() {
+            Type::Array(t, _, _) | Type::Slice(t, _) => Some(t),
+            _ => None,
+        },
+        Type::MutableRef(t, _) => match t.as_ref() {
+            Type::Array(t, _, _) | Type::Slice(t, _) => Some(t),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether `ty` is a borrowed slice, `&[T]` or `&mut [T]`
+pub fn is_slice(ty: &Type) -> bool {
+    match ty {
+        Type::Reference(t, _) | Type::MutableRef(t, _) => matches!(t.as_ref(), Type::Slice(..)),
+        _ => false,
+    }
+}
+
+/// Whether `e` is a range, `arr[e]` is then a slice
+pub fn is_range(e: &Expr) -> bool {
+    matches!(e, Expr::Range(..) | Expr::RangeInclusive(..))
+}
+
+/// Length of the array behind `ty`, `None` for slices
+fn array_len(ty: &Type) -> Option<u64> {
+    match ty {
+        Type::Array(_, len, _) => Some(*len),
+        Type::Reference(t, _) | Type::MutableRef(t, _) => array_len(t),
+        _ => None,
+    }
+}
+
+/// `&arr[start..end]` or `&mut arr[start..end]`, returns the translated
+/// `std::span` and its type, `&[T]` or `&mut [T]`
+pub fn slice(
+    st: &mut State,
+    arr: &Expr,
+    range: &Expr,
+    mutable: bool,
+    span: SimpleSpan,
+) -> CompileResult<(String, Type)> {
+    let (start, end, inclusive) = match range {
+        Expr::Range(start, end) => (start, end, false),
+        Expr::RangeInclusive(start, end) => (start, end, true),
+        _ => Err(Error::Compile("Expected a range".to_owned(), range.span()))?,
+    };
+
+    let ty = arr.resolve_type(st)?;
+    let elements = element_type(&ty).ok_or_else(|| {
+        Error::Compile(format!("Cannot slice a value of type {}", ty), arr.span())
+    })?;
+
+    if mutable {
+        check_borrow_mut(st, arr)?;
+    }
+
+    for bound in [start, end] {
+        let bound_t = bound.resolve_type(st)?;
+
+        if !is_integer(&bound_t) {
+            Err(Error::Compile(
+                format!("Slice bounds must be integers, found {}", bound_t),
+                bound.span(),
+            ))?
+        }
+    }
+
+    if let (Some(from), Some(to)) = (constant_value(start), constant_value(end)) {
+        let to = if inclusive { to + 1 } else { to };
+
+        if from < 0 {
+            Err(Error::Compile(
+                format!("Slice cannot start at negative index {}", from),
+                start.span(),
+            ))?
+        }
+
+        if from > to {
+            Err(Error::Compile(
+                format!("Slice starts at {} but ends at {}", from, to),
+                range.span(),
+            ))?
+        }
+
+        if let Some(len) = array_len(&ty).filter(|len| to > *len as i128) {
+            Err(Error::Compile(
+                format!(
+                    "Slice end {} is out of bounds for array of length {}",
+                    to, len
+                ),
+                end.span(),
+            ))?
+        }
+    }
+
+    let (cstart, cend) = (start.to_cpp(st)?, end.to_cpp(st)?);
+    let count = if inclusive {
+        format!("{} - {} + 1", cend, cstart)
+    } else {
+        format!("{} - {}", cend, cstart)
+    };
+
+    let slice_t = Type::Slice(Box::new(elements.clone()), span);
+    let (constness, slice_t) = if mutable {
+        ("", Type::MutableRef(Box::new(slice_t), span))
+    } else {
+        ("const ", Type::Reference(Box::new(slice_t), span))
+    };
+
+    st.add_include("<span>");
+
+    Ok((
+        format!(
+            "std::span<{}{}>({}).subspan({}, {})",
+            constness,
+            elements.to_cpp(st)?,
+            arr.to_cpp(st)?,
+            cstart,
+            count
+        ),
+        slice_t,
+    ))
+}
+
+/// `value.len()` on arrays and slices, `None` if `ty` is neither
+pub fn array_method_call(
+    st: &mut State,
+    ty: &Type,
+    value: &Expr,
+    method: &Expr,
+    args: &[Expr],
+    span: SimpleSpan,
+) -> CompileResult<Option<(String, Type)>> {
+    if element_type(ty).is_none() {
+        return Ok(None);
+    }
+
+    match method {
+        Expr::Ident(name, _) if name == "len" && args.is_empty() => {
+            // Also marks the receiver as used when its length is known
+            let cvalue = value.to_cpp(st)?;
+
+            // The length of arrays is known at compile time
+            let len = match array_len(ty) {
+                Some(len) => len.to_string(),
+                None => format!("{}.size()", cvalue),
+            };
+
+            Ok(Some((len, Type::Uint(span))))
+        }
+        Expr::Ident(name, _) if name == "len" => {
+            Err(Error::Compile("`len` takes no arguments".to_owned(), span))
+        }
+        Expr::Ident(name, s) => Err(Error::Compile(
+            format!("No method `{}` on type {}", name, ty),
+            *s,
+        )),
+        _ => Err(Error::Compile(
+            "Expected method name".to_owned(),
+            method.span(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_utils::{errors, translate, warnings};
+
+    #[test]
+    fn slice_types() {
+        let cpp = translate(
+            "fn main() -> i32 {
+                let mut arr: [i32; 4] = [1, 2, 3, 4];
+                let s: &[i32] = &arr[1..3];
+                let m: &mut [i32] = &mut arr[2..4];
+                return s[0] + m[0];
+            }",
+        );
+
+        assert!(cpp.contains("std::span<const int32_t> s ="));
+        assert!(cpp.contains("std::span<int32_t> m ="));
+    }
+
+    #[test]
+    fn arrays_and_slices_as_shared_slices() {
+        let cpp = translate(
+            "fn count(v: &[i32]) -> u32 { return v.len(); }
+            fn main() -> i32 {
+                let mut arr: [i32; 4] = [1, 2, 3, 4];
+                let s = &arr[1..3];
+                let m = &mut arr[2..4];
+                return (count(arr) + count(&arr) + count(s) + count(m)) as i32;
+            }",
+        );
+
+        assert!(cpp.contains("count(std::span<const int32_t>(arr))"));
+        assert!(cpp.contains("count(s)"));
+        assert!(cpp.contains("count(std::span<const int32_t>(m))"));
+    }
+
+    #[test]
+    fn loops_over_borrowed_subslices() {
+        let cpp = translate(
+            "fn main() -> i32 {
+                let mut arr: [i32; 4] = [1, 2, 3, 4];
+                for x in &mut arr[1..3] { x = x * 10; }
+                let mut sum: i32 = 0;
+                for x in &arr[0..=2] { sum += x; }
+                return sum;
+            }",
+        );
+
+        assert!(cpp.contains("for (int32_t& x : std::span<int32_t>(arr).subspan(1, 3 - 1)) {"));
+        assert!(cpp.contains(
+            "for (const int32_t& x : std::span<const int32_t>(arr).subspan(0, 2 - 0 + 1)) {"
+        ));
+    }
+
+    #[test]
+    fn loops_over_mutable_subslices_of_immutable_arrays() {
+        let errors = errors(
+            "fn main() -> i32 {
+                let arr: [i32; 4] = [1, 2, 3, 4];
+                for x in &mut arr[1..3] { x = 0; }
+                return 0;
+            }",
+        );
+
+        assert_eq!(
+            errors,
+            ["Cannot borrow immutable binding `arr` as mutable, declare it with `let mut`"]
+        );
+    }
+
+    #[test]
+    fn mutable_slices_need_mutable_borrows() {
+        let errors = errors(
+            "fn fill(v: &mut [i32]) -> void { v[0] = 1; }
+            fn main() -> i32 {
+                let mut arr: [i32; 4] = [1, 2, 3, 4];
+                fill(&arr[0..2]);
+                return 0;
+            }",
+        );
+
+        assert_eq!(errors, ["Expected type &mut [i32], got &[i32]"]);
+    }
+
+    #[test]
+    fn write_through_shared_slice() {
+        let errors = errors(
+            "fn main() -> i32 {
+                let mut arr: [i32; 4] = [1, 2, 3, 4];
+                let s = &arr[0..2];
+                s[0] = 5;
+                return 0;
+            }",
+        );
+
+        assert_eq!(
+            errors,
+            ["Cannot write through a shared slice of type &[i32]"]
+        );
+    }
+
+    #[test]
+    fn unborrowed_slice_type() {
+        let errors =
+            errors("fn sum(v: [i32]) -> i32 { return v[0]; } fn main() -> i32 { return 0; }");
+
+        assert_eq!(
+            errors,
+            ["Slices must be borrowed, write `&[i32]` or `&mut [i32]`"]
+        );
+    }
+
+    #[test]
+    fn len_uses_the_receiver() {
+        let source = "fn main() -> i32 { let arr: [i32; 2] = [1, 2]; return arr.len() as i32; }";
+
+        assert!(translate(source).contains("return (static_cast<int32_t>(2));"));
+        assert!(warnings(source).is_empty());
+    }
+}
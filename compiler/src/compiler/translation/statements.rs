@@ -1,24 +1,35 @@
 use std::collections::HashMap;
 
 use chumsky::span::SimpleSpan;
-use comfy_types::{Argument, Expr, Statements, Type};
+use comfy_types::{AccessModifier, Argument, Expr, Statements, Type};
 use comfy_utils::inc_indent;
 
-use crate::compiler::{Error, TypeInfo};
-
-use super::{ComfyNode, CompileResult, State};
-
-fn get_real_type_of_argument(st: &mut State, ty: &Type, expr: &Expr) -> CompileResult<Type> {
-    let expr_ty = expr.resolve_type(st).unwrap_or_else(|_s| {
-        if let Type::Unknown(_) = ty {
-            st.errors.push(Error::Compile(
-                "Cannot infer type of expression".to_owned(),
-                expr.span(),
-            ));
-        }
-
-        Type::Unknown(expr.span())
-    });
+use crate::compiler::Error;
+
+use super::{
+    arrays::value_as,
+    coercion::{check_binding, common_type},
+    cpp_ident,
+    enums::{enum_declaration, match_statement},
+    externs::extern_block,
+    flow::{check_returns, diverges},
+    generics::template_head,
+    lints::Lint,
+    modules::use_statement,
+    places::check_borrow_mut,
+    slices::{element_type, is_range, slice},
+    structs::{declare_impls, impl_declaration, struct_declaration},
+    tuples::destructure,
+    ComfyNode, CompileResult, State,
+};
+
+pub fn get_real_type_of_argument(st: &mut State, ty: &Type, expr: &Expr) -> CompileResult<Type> {
+    // With a written type the value is checked against it later
+    let expr_ty = match expr.resolve_type(st) {
+        Ok(expr_ty) => expr_ty,
+        Err(e) if matches!(ty, Type::Unknown(_)) => Err(e)?,
+        Err(_) => Type::Unknown(expr.span()),
+    };
 
     let real_type = match ty {
         Type::Unknown(_) => expr_ty,
@@ -28,51 +39,175 @@ fn get_real_type_of_argument(st: &mut State, ty: &Type, expr: &Expr) -> CompileR
     Ok(real_type)
 }
 
-fn typed_name(st: &mut State, name: &str, ty: &Type, expr: &Expr) -> CompileResult<String> {
+fn typed_name(
+    st: &mut State,
+    name: &str,
+    mutable: bool,
+    ty: &Type,
+    expr: &Expr,
+    span: SimpleSpan,
+) -> CompileResult<String> {
     let real_type = get_real_type_of_argument(st, ty, expr)?;
 
+    // Translating the type checks its type arguments, before the value is compared to it
     let cty = real_type.to_cpp(st)?;
 
-    let is_default = if let Expr::Unknown = expr {
-        false
-    } else {
-        true
-    };
-
-    let type_name = format!("{} {}", cty.0, name);
+    if !matches!(ty, Type::Unknown(_)) && !matches!(expr, Expr::Unknown) {
+        check_binding(st, expr, ty)?;
+    }
 
-    let arr_desc = if cty.1 .0 {
-        let size = match cty.1 .1 {
-            Some(size) => size.to_string(),
-            None => "".to_owned(),
-        };
+    let is_default = !matches!(expr, Expr::Unknown);
 
-        format!("[{}]", size)
-    } else {
-        "".to_owned()
-    };
+    let type_name = format!("{} {}", cty, cpp_ident(name));
 
     let assign_default = if is_default {
-        format!(" = {}", expr.to_cpp(st)?)
+        format!(" = {}", value_as(st, expr, &real_type)?)
     } else {
         "".to_owned()
     };
 
-    st.add_variable(name, real_type);
+    st.add_binding(name, real_type, mutable, span);
 
-    Ok(format!("{}{}{}", type_name, arr_desc, assign_default,))
+    Ok(format!("{}{}", type_name, assign_default))
+}
+
+fn for_loop(
+    st: &mut State,
+    name: &str,
+    iterable: &Expr,
+    body: &Vec<Statements>,
+    span: SimpleSpan,
+) -> CompileResult<String> {
+    let (head, ty) = match iterable {
+        Expr::Range(start, end) | Expr::RangeInclusive(start, end) => {
+            let ty = common_type(st, start, end)?;
+            let cty = ty.to_cpp(st)?;
+            let cname = cpp_ident(name);
+            // The end is evaluated once, before the first iteration
+            let cend = format!("{}__end", cname);
+            let (cstart, cend_value) = (start.to_cpp(st)?, end.to_cpp(st)?);
+
+            let head = if let Expr::Range(_, _) = iterable {
+                format!(
+                    "{} {} = {}, {} = {}; {} < {}; {}++",
+                    cty, cname, cstart, cend, cend_value, cname, cend, cname
+                )
+            } else {
+                // `i <= end` always holds when `end` is the largest value of
+                // the type, the loop stops after the iteration with `i == end`
+                // instead, before the counter wraps
+                let last = format!("{}__last", cname);
+
+                format!(
+                    "{} {} = {}, {} = {}, {} = {} > {}; !{}; {} = {} == {}, {}++",
+                    cty,
+                    cname,
+                    cstart,
+                    cend,
+                    cend_value,
+                    last,
+                    cname,
+                    cend,
+                    last,
+                    last,
+                    cname,
+                    cend,
+                    cname
+                )
+            };
+
+            (head, ty)
+        }
+        _ => {
+            let (seq, by_ref) = match iterable {
+                Expr::Address(seq) => (seq.as_ref(), Some("const ")),
+                Expr::MutAddress(seq) => (seq.as_ref(), Some("")),
+                _ => (iterable, None),
+            };
+
+            let (cseq, seq_t) = match seq {
+                // `&arr[a..b]` is iterated as the borrowed slice
+                Expr::ArrMember(arr, range) if by_ref.is_some() && is_range(range) => {
+                    let mutable = matches!(iterable, Expr::MutAddress(_));
+
+                    slice(st, arr, range, mutable, iterable.span())?
+                }
+                _ => {
+                    if let Expr::MutAddress(_) = iterable {
+                        check_borrow_mut(st, seq)?;
+                    }
+
+                    let seq_t = seq.resolve_type(st)?;
+
+                    (seq.to_cpp(st)?, seq_t)
+                }
+            };
+
+            let ty = element_type(&seq_t).ok_or_else(|| {
+                Error::Compile(
+                    format!("Cannot iterate over non array type {}", seq_t),
+                    iterable.span(),
+                )
+            })?;
+
+            let cty = ty.to_cpp(st)?;
+            let cty = match by_ref {
+                Some(constness) => format!("{}{}&", constness, cty),
+                None => format!("const {}", cty),
+            };
+
+            (format!("{} {} : {}", cty, cpp_ident(name), cseq), ty)
+        }
+    };
+
+    st.scope_stack.push(HashMap::new());
+    // `for name in`
+    // Elements borrowed with `&mut` are written through the binding
+    let mutable = matches!(iterable, Expr::MutAddress(_));
+    st.add_binding(
+        name,
+        ty,
+        mutable,
+        SimpleSpan::new(span.start, iterable.span().start),
+    );
+    st.loop_depth += 1;
+    let cbody = body.to_cpp(st);
+    st.loop_depth -= 1;
+    st.pop_scope();
+
+    Ok(format!("for ({}) {{\n{}\n}}", head, inc_indent(cbody?)))
 }
 
 impl ComfyNode<String> for Statements {
     fn to_cpp(&self, st: &mut State) -> CompileResult<String> {
         Ok(match self {
             Statements::ExpressionStatement(e, _) => format!("{};", e.to_cpp(st)?),
-            Statements::LetStatement(name, ty, expr, _) => {
-                format!("{};", typed_name(st, name, ty, expr)?)
+            Statements::LetStatement(name, mutable, ty, expr, s) => {
+                let cpp = typed_name(st, name, *mutable, ty, expr, *s)?;
+
+                // References can not be rebound already and an uninitialized
+                // variable is assigned later
+                let constness = match st.get_ident(name, *s)?.return_type {
+                    _ if *mutable || matches!(expr, Expr::Unknown) => "",
+                    Type::Reference(..) | Type::MutableRef(..) => "",
+                    _ => "const ",
+                };
+
+                format!("{}{};", constness, cpp)
+            }
+            Statements::LetTupleStatement(names, ty, expr, s) => {
+                destructure(st, names, ty, expr, *s)?
             }
-            Statements::FunctionDeclaration(_access_modifier, name, args, ty, body, _s) => {
+            Statements::FunctionDeclaration(access_modifier, name, generics, args, ty, body, s) => {
+                if let Some(receiver) = args.iter().find(|a| a.0 == "self") {
+                    Err(Error::Compile(
+                        "`self` is only allowed in methods".to_owned(),
+                        receiver.span(),
+                    ))?
+                }
+
                 let des = args
-                    .into_iter()
+                    .iter()
                     .map(|a| {
                         let a = a.clone();
 
@@ -82,41 +217,79 @@ impl ComfyNode<String> for Statements {
                     })
                     .collect::<CompileResult<Vec<_>>>()?;
 
-                st.add_func(name, ty.clone(), des);
+                st.add_func(name, ty.clone(), generics.clone(), des);
 
-                st.scope_stack.push(HashMap::new());
+                if !generics.is_empty() {
+                    st.generic_functions.insert(name.clone(), self.clone());
+                }
 
-                let cty = ty.to_cpp(st);
+                // Public functions are used by the importing modules
+                if !matches!(access_modifier, AccessModifier::Public(_)) && name != "main" {
+                    st.track_usage(name, Lint::UnusedFunctions, *s);
+                }
 
-                let cty: (String, TypeInfo) = if let Err(_) = cty {
-                    st.errors.push(Error::Compile(
-                        "Return type of function cannot be resolved".to_owned(),
-                        self.span(),
-                    ));
-                    ("{unknown}".to_owned(), TypeInfo(false, None))
-                } else {
-                    cty.unwrap()
+                st.scope_stack.push(HashMap::new());
+                let loop_depth = std::mem::take(&mut st.loop_depth);
+                let return_type = st.return_type.replace(ty.clone());
+
+                let template = template_head(st, generics, self.span())?;
+
+                let cty = match ty.to_cpp(st) {
+                    Ok(cty) => cty,
+                    Err(_) => {
+                        st.errors.push(Error::Compile(
+                            "Return type of function cannot be resolved".to_owned(),
+                            self.span(),
+                        ));
+                        "{unknown}".to_owned()
+                    }
                 };
 
-                if cty.1 .0 {
-                    // TODO: support arrays
-                    return Err(Error::Compile("Cannot return arrays".to_owned(), ty.span()));
-                }
-
                 let cargs = args.to_cpp(st)?;
                 let cbody = body.to_cpp(st)?;
 
-                st.scope_stack.pop();
+                check_returns(st, name, ty, body, *s);
+
+                st.loop_depth = loop_depth;
+                st.return_type = return_type;
+                st.pop_scope();
+
+                let noreturn = match ty {
+                    Type::Never(_) => "[[noreturn]] ",
+                    _ => "",
+                };
 
                 format!(
-                    "{} {}({}) {{\n{}\n}}\n",
-                    cty.0,
-                    name,
+                    "{}{}{} {}({}) {{\n{}\n}}\n",
+                    template,
+                    noreturn,
+                    cty,
+                    cpp_ident(name),
                     cargs,
                     inc_indent(cbody)
                 )
             }
-            Statements::ReturnStatement(e, _) => format!("return {};", e.to_cpp(st)?),
+            Statements::ReturnStatement(e, _) => match st.return_type.clone() {
+                Some(ty) => {
+                    check_binding(st, e, &ty)?;
+
+                    format!("return {};", value_as(st, e, &ty)?)
+                }
+                None => format!("return {};", e.to_cpp(st)?),
+            },
+            Statements::StructDeclaration(_access_modifier, name, generics, fields, s) => {
+                struct_declaration(st, name, generics, fields, *s)?
+            }
+            Statements::EnumDeclaration(_access_modifier, name, variants, s) => {
+                enum_declaration(st, name, variants, *s)?
+            }
+            Statements::ImplDeclaration(name, methods, s) => {
+                impl_declaration(st, name, methods, *s)?
+            }
+            Statements::UseStatement(path, items, s) => use_statement(st, path, items, *s)?,
+            Statements::ExternBlock(abi, includes, functions, s) => {
+                extern_block(st, abi, includes, functions, *s)?
+            }
             Statements::IfStatement(condition, then, els, _) => {
                 let ccond = condition.to_cpp(st)?;
                 let cthen = then.to_cpp(st)?;
@@ -129,12 +302,52 @@ impl ComfyNode<String> for Statements {
                 };
 
                 let celse = if els.len() == 1 {
-                    format!("{}", celse)
+                    celse
                 } else {
                     format!("{{\n{}\n}}", inc_indent(celse))
                 };
 
-                format!("if {} {}else {}", ccond, cthen, celse)
+                format!("if ({}) {}else {}", ccond, cthen, celse)
+            }
+            Statements::MatchStatement(value, arms, s) => match_statement(st, value, arms, *s)?,
+            Statements::WhileStatement(condition, body, _) => {
+                let ccond = condition.to_cpp(st)?;
+
+                match condition.resolve_type(st)? {
+                    Type::Bool(_) => {}
+                    ty => st.errors.push(Error::Compile(
+                        format!("`while` condition must be a bool, found {}", ty),
+                        condition.span(),
+                    )),
+                }
+
+                st.scope_stack.push(HashMap::new());
+                st.loop_depth += 1;
+                let cbody = body.to_cpp(st);
+                st.loop_depth -= 1;
+                st.pop_scope();
+
+                format!("while ({}) {{\n{}\n}}", ccond, inc_indent(cbody?))
+            }
+            Statements::ForStatement(name, iterable, body, s) => {
+                for_loop(st, name, iterable, body, *s)?
+            }
+            Statements::BreakStatement(s) => {
+                if st.loop_depth == 0 {
+                    Err(Error::Compile("`break` outside of a loop".to_owned(), *s))?
+                }
+
+                "break;".to_owned()
+            }
+            Statements::ContinueStatement(s) => {
+                if st.loop_depth == 0 {
+                    Err(Error::Compile(
+                        "`continue` outside of a loop".to_owned(),
+                        *s,
+                    ))?
+                }
+
+                "continue;".to_owned()
             }
         })
     }
@@ -142,10 +355,21 @@ impl ComfyNode<String> for Statements {
     fn span(&self) -> SimpleSpan {
         match self {
             Statements::ExpressionStatement(_, s) => *s,
-            Statements::LetStatement(_, _, _, s) => *s,
-            Statements::FunctionDeclaration(_, _, _, _, _, s) => *s,
+            Statements::LetStatement(_, _, _, _, s) => *s,
+            Statements::LetTupleStatement(_, _, _, s) => *s,
+            Statements::FunctionDeclaration(_, _, _, _, _, _, s) => *s,
             Statements::ReturnStatement(_, s) => *s,
+            Statements::StructDeclaration(_, _, _, _, s) => *s,
+            Statements::EnumDeclaration(_, _, _, s) => *s,
+            Statements::ImplDeclaration(_, _, s) => *s,
+            Statements::UseStatement(_, _, s) => *s,
+            Statements::ExternBlock(_, _, _, s) => *s,
             Statements::IfStatement(_, _, _, s) => *s,
+            Statements::MatchStatement(_, _, s) => *s,
+            Statements::WhileStatement(_, _, s) => *s,
+            Statements::ForStatement(_, _, _, s) => *s,
+            Statements::BreakStatement(s) => *s,
+            Statements::ContinueStatement(s) => *s,
         }
     }
 
@@ -156,11 +380,28 @@ impl ComfyNode<String> for Statements {
 
 impl ComfyNode<String> for Vec<Statements> {
     fn to_cpp(&self, st: &mut State) -> CompileResult<String> {
-        Ok(self
-            .iter()
-            .map(|s| s.to_cpp(st))
-            .collect::<Result<Vec<_>, _>>()?
-            .join("\n"))
+        declare_impls(st, self)?;
+
+        let diverging = self.iter().position(|s| diverges(st, s));
+
+        if let Some(next) = diverging.and_then(|i| self.get(i + 1)) {
+            let end = self.last().unwrap().span().end;
+
+            st.lint(
+                Lint::UnreachableCode,
+                "Unreachable code".to_owned(),
+                SimpleSpan::new(next.span().start, end),
+            );
+        }
+
+        let mut code = vec![];
+
+        for s in self {
+            code.push(s.to_cpp(st)?);
+            st.record_snapshot(s.span());
+        }
+
+        Ok(code.join("\n"))
     }
 
     fn span(&self) -> SimpleSpan {
@@ -177,7 +418,13 @@ impl ComfyNode<String> for Vec<Statements> {
 
 impl ComfyNode<String> for Argument {
     fn to_cpp(&self, st: &mut State) -> CompileResult<String> {
-        typed_name(st, &self.0, &self.1, &self.2)
+        let cpp = typed_name(st, &self.0, false, &self.1, &self.2, self.3)?;
+
+        if let Some(ident) = st.scope_stack.last_mut().unwrap().get_mut(&self.0) {
+            ident.argument = true;
+        }
+
+        Ok(cpp)
     }
 
     fn span(&self) -> SimpleSpan {
@@ -209,3 +456,125 @@ impl ComfyNode<String> for Vec<Argument> {
         Ok(Type::Unknown(self.span()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_utils::{errors, translate};
+
+    #[test]
+    fn while_loop_with_break_and_continue() {
+        let code = translate(
+            "fn main() -> i32 {
+                let mut i: i32 = 0;
+                while i < 10 {
+                    i = i + 1;
+                    if i == 3 { continue; } else { }
+                    if i == 7 { break; } else { }
+                }
+                return i;
+            }",
+        );
+
+        assert!(code.contains("while ((i < 10)) {"));
+        assert!(code.contains("continue;"));
+        assert!(code.contains("break;"));
+    }
+
+    #[test]
+    fn while_condition_must_be_bool() {
+        let errors = errors("fn main() -> i32 { while 5 { break; } return 0; }");
+
+        assert_eq!(errors, ["`while` condition must be a bool, found u8"]);
+    }
+
+    #[test]
+    fn break_outside_of_loop() {
+        let errors = errors("fn main() -> i32 { break; return 0; }");
+
+        assert_eq!(errors, ["`break` outside of a loop"]);
+    }
+
+    #[test]
+    fn range_loops() {
+        let code = translate(
+            "fn main() -> i32 {
+                let mut total: i32 = 0;
+                for i in 0..300 { total += i as i32; }
+                for j in 1..=3 { total += j as i32; }
+                return total;
+            }",
+        );
+
+        assert!(code.contains("for (uint16_t i = 0, i__end = 300; i < i__end; i++) {"));
+        assert!(code.contains(
+            "for (uint8_t j = 1, j__end = 3, j__last = j > j__end; !j__last; j__last = j == j__end, j++) {"
+        ));
+    }
+
+    #[test]
+    fn inclusive_range_to_the_largest_value() {
+        let code = translate(
+            "fn main() -> i32 {
+                let mut total: i32 = 0;
+                for i in 0..=255 { total += i as i32; }
+                return total;
+            }",
+        );
+
+        // `i <= 255` would always hold for a u8
+        assert!(code.contains(
+            "for (uint8_t i = 0, i__end = 255, i__last = i > i__end; !i__last; i__last = i == i__end, i++) {"
+        ));
+    }
+
+    #[test]
+    fn array_loops() {
+        let code = translate(
+            "fn main() -> i32 {
+                let mut arr: [i32; 3] = [1, 2, 3];
+                let mut total: i32 = 0;
+                for x in arr { total += x; }
+                for x in &arr { total += x; }
+                for x in &mut arr { x = 0; }
+                return total;
+            }",
+        );
+
+        assert!(code.contains("for (const int32_t x : arr) {"));
+        assert!(code.contains("for (const int32_t& x : arr) {"));
+        assert!(code.contains("for (int32_t& x : arr) {"));
+    }
+
+    #[test]
+    fn loop_variable_is_scoped() {
+        let errors = errors("fn main() -> i32 { for i in 0..3 { } return i as i32; }");
+
+        assert_eq!(errors, ["Unknown identifier: i"]);
+    }
+
+    #[test]
+    fn loop_over_non_array() {
+        let errors = errors("fn main() -> i32 { let n: i32 = 3; for x in n { } return 0; }");
+
+        assert_eq!(errors, ["Cannot iterate over non array type i32"]);
+    }
+
+    #[test]
+    fn shared_elements_are_immutable() {
+        let errors = errors(
+            "fn main() -> i32 { let arr: [i32; 2] = [1, 2]; for x in &arr { x = 0; } return 0; }",
+        );
+
+        assert_eq!(
+            errors,
+            ["Cannot assign to immutable binding `x`, declare it with `let mut`"]
+        );
+    }
+
+    #[test]
+    fn failed_initializer_of_untyped_let() {
+        let errors = errors("fn main() -> i32 { let y = z + 1; return 0; }");
+
+        assert_eq!(errors, ["Unknown identifier: z"]);
+    }
+}
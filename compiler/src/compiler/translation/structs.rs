@@ -0,0 +1,623 @@
+use std::collections::HashMap;
+
+use chumsky::span::SimpleSpan;
+use comfy_types::{Argument, Expr, Field, FieldInit, Statements, Type};
+use comfy_utils::{b, inc_indent};
+
+use super::{
+    arrays::{args_as, value_as},
+    coercion::check_binding,
+    cpp_ident,
+    expression::check_args,
+    flow::check_returns,
+    generics::{infer_bindings, library_method_call, substitute, template_head, type_bindings},
+    places::check_borrow_mut,
+    slices::array_method_call,
+    statements::get_real_type_of_argument,
+    ComfyNode, CompileResult, Error, Method, State,
+};
+
+/// Name of the struct behind `ty`, references are dereferenced automatically
+fn struct_name(ty: &Type, what: &str, span: SimpleSpan) -> CompileResult<String> {
+    match ty {
+        Type::Custom(name, _) | Type::Generic(name, _, _) => Ok(name.clone()),
+        Type::Reference(t, _) | Type::MutableRef(t, _) => struct_name(t, what, span),
+        _ => Err(Error::Compile(format!("Type {} has no {}", ty, what), span)),
+    }
+}
+
+fn replace_self(ty: &Type, name: &str) -> Type {
+    match ty {
+        Type::Custom(s, span) if s == "Self" => Type::Custom(name.to_owned(), *span),
+        Type::Reference(t, s) => Type::Reference(b(replace_self(t, name)), *s),
+        Type::MutableRef(t, s) => Type::MutableRef(b(replace_self(t, name)), *s),
+        Type::Pointer(t, s) => Type::Pointer(b(replace_self(t, name)), *s),
+        Type::Array(t, size, s) => Type::Array(b(replace_self(t, name)), *size, *s),
+        Type::Slice(t, s) => Type::Slice(b(replace_self(t, name)), *s),
+        _ => ty.clone(),
+    }
+}
+
+pub fn struct_declaration(
+    st: &mut State,
+    name: &str,
+    generics: &[String],
+    fields: &[Field],
+    span: SimpleSpan,
+) -> CompileResult<String> {
+    if !generics.is_empty() && st.impls.contains_key(name) {
+        Err(Error::Compile(
+            format!(
+                "`impl` blocks for generic type `{}` are not supported",
+                name
+            ),
+            span,
+        ))?
+    }
+
+    st.scope_stack.push(HashMap::new());
+    let template = template_head(st, generics, span)?;
+
+    let mut members = vec![];
+    let mut params = vec![];
+    let mut inits = vec![];
+    let mut section = None;
+
+    for (i, Field(access_modifier, field, ty, s)) in fields.iter().enumerate() {
+        if fields[..i].iter().any(|f| &f.1 == field) {
+            Err(Error::Compile(
+                format!("Field `{}` is declared more than once", field),
+                *s,
+            ))?
+        }
+
+        let cty = ty.to_cpp(st)?;
+
+        let access = access_modifier.to_cpp(st)?;
+
+        if section.as_ref() != Some(&access) {
+            members.push(format!("{}:", access));
+            section = Some(access);
+        }
+
+        let field = cpp_ident(field);
+
+        members.push(format!("    {} {};", cty, field));
+        params.push(format!("{} {}", cty, field));
+        inits.push(format!("{}({})", field, field));
+    }
+
+    st.scope_stack.pop();
+
+    // Methods may refer to the struct itself
+    st.add_struct(name, generics.to_vec(), fields.to_vec(), span);
+
+    let cname = cpp_ident(name);
+
+    let ctor = if fields.is_empty() {
+        "".to_owned()
+    } else {
+        format!(
+            "\n{}({}) : {} {{}}",
+            cname,
+            params.join(", "),
+            inits.join(", ")
+        )
+    };
+
+    members.push(format!(
+        "\npublic:\n{}",
+        inc_indent(format!("{}() = default;{}", cname, ctor))
+    ));
+    members.extend(method_prototypes(st, name, Some("public".to_owned()))?);
+
+    Ok(format!(
+        "{}struct {} {{\n{}\n}};\n",
+        template,
+        cname,
+        members.join("\n")
+    ))
+}
+
+/// Declarations of the methods implemented for `ty`, `section` is the access
+/// section the type body ends with
+pub fn method_prototypes(
+    st: &mut State,
+    ty: &str,
+    mut section: Option<String>,
+) -> CompileResult<Vec<String>> {
+    let mut members = vec![];
+    let methods = st.impls.get(ty).cloned().unwrap_or_default();
+
+    if !methods.is_empty() {
+        members.push("".to_owned());
+    }
+
+    for method in methods {
+        let access = method.access_modifier.to_cpp(st)?;
+
+        if section.as_ref() != Some(&access) {
+            members.push(format!("{}:", access));
+            section = Some(access);
+        }
+
+        st.scope_stack.push(HashMap::new());
+        let signature = method_signature(st, ty, &method, true);
+        st.scope_stack.pop();
+
+        members.push(format!("    {};", signature?));
+    }
+
+    Ok(members)
+}
+
+/// Registers methods of every `impl` block in `stmts`, so structs can declare them
+pub fn declare_impls(st: &mut State, stmts: &[Statements]) -> CompileResult<()> {
+    for stmt in stmts {
+        let Statements::ImplDeclaration(ty, methods, _) = stmt else {
+            continue;
+        };
+
+        for method in methods {
+            let Statements::FunctionDeclaration(access_modifier, name, generics, args, ret, _, s) =
+                method
+            else {
+                Err(Error::Compile(
+                    "Only functions can be declared in impl blocks".to_owned(),
+                    method.span(),
+                ))?
+            };
+
+            if !generics.is_empty() {
+                Err(Error::Compile(
+                    "Methods cannot have type parameters".to_owned(),
+                    *s,
+                ))?
+            }
+
+            if st.get_method(ty, name, *s).is_ok() {
+                Err(Error::Compile(
+                    format!("Method `{}` is already defined for `{}`", name, ty),
+                    *s,
+                ))?
+            }
+
+            let mut args = args
+                .iter()
+                .map(|a| {
+                    let real_type = get_real_type_of_argument(st, &a.1, &a.2)?;
+
+                    Ok(Argument(
+                        a.0.clone(),
+                        replace_self(&real_type, ty),
+                        a.2.clone(),
+                        a.3,
+                    ))
+                })
+                .collect::<CompileResult<Vec<_>>>()?;
+
+            let receiver = match args.first() {
+                Some(Argument(name, _, _, _)) if name == "self" => Some(args.remove(0).1),
+                _ => None,
+            };
+
+            st.impls.entry(ty.clone()).or_default().push(Method {
+                access_modifier: access_modifier.clone(),
+                name: name.clone(),
+                receiver,
+                args,
+                return_type: replace_self(ret, ty),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+fn method_signature(
+    st: &mut State,
+    ty: &str,
+    method: &Method,
+    declaration: bool,
+) -> CompileResult<String> {
+    let cty = method.return_type.to_cpp(st)?;
+
+    // Default values can only be specified in the declaration
+    let args = if declaration {
+        method.args.clone()
+    } else {
+        method
+            .args
+            .iter()
+            .map(|a| Argument(a.0.clone(), a.1.clone(), Expr::Unknown, a.3))
+            .collect()
+    };
+
+    let cargs = args.to_cpp(st)?;
+    let name = cpp_ident(&method.name);
+
+    // A receiver taken by value is a copy, the method can be called on constants
+    let constness = match method.receiver {
+        Some(Type::MutableRef(_, _)) | None => "",
+        Some(_) => " const",
+    };
+
+    Ok(match (declaration, &method.receiver) {
+        (true, None) => format!("static {} {}({})", cty, name, cargs),
+        (true, Some(_)) => format!("{} {}({}){}", cty, name, cargs, constness),
+        (false, _) => format!(
+            "{} {}::{}({}){}",
+            cty,
+            cpp_ident(ty),
+            name,
+            cargs,
+            constness
+        ),
+    })
+}
+
+fn method_definition(st: &mut State, ty: &str, stmt: &Statements) -> CompileResult<String> {
+    let Statements::FunctionDeclaration(_, name, _, _, _, body, s) = stmt else {
+        unreachable!("impl blocks are checked by declare_impls")
+    };
+
+    let method = st.get_method(ty, name, *s)?.clone();
+
+    st.scope_stack.push(HashMap::new());
+    let loop_depth = std::mem::take(&mut st.loop_depth);
+    let return_type = st.return_type.replace(method.return_type.clone());
+
+    let signature = method_signature(st, ty, &method, false)?;
+
+    let this = match &method.receiver {
+        Some(receiver) => {
+            let cty = cpp_ident(ty);
+            let binding = match receiver {
+                Type::Reference(_, _) => format!("const {}& self = *this;", cty),
+                Type::MutableRef(_, _) => format!("{}& self = *this;", cty),
+                _ => format!("{} self = *this;", cty),
+            };
+
+            st.add_variable("self", receiver.clone(), false);
+
+            format!("{}\n", binding)
+        }
+        None => "".to_owned(),
+    };
+
+    let cbody = body.to_cpp(st)?;
+
+    check_returns(st, name, &method.return_type, body, *s);
+
+    st.loop_depth = loop_depth;
+    st.return_type = return_type;
+    st.pop_scope();
+
+    Ok(format!(
+        "{} {{\n{}\n}}\n",
+        signature,
+        inc_indent(format!("{}{}", this, cbody))
+    ))
+}
+
+pub fn impl_declaration(
+    st: &mut State,
+    ty: &str,
+    methods: &[Statements],
+    span: SimpleSpan,
+) -> CompileResult<String> {
+    st.get_type(ty, span)?;
+
+    let self_type = st.self_type.replace(ty.to_owned());
+
+    let cmethods = methods
+        .iter()
+        .map(|m| method_definition(st, ty, m))
+        .collect::<CompileResult<Vec<_>>>();
+
+    st.self_type = self_type;
+
+    Ok(cmethods?.join("\n"))
+}
+
+pub fn resolve_field(st: &mut State, l: &Expr, r: &Expr) -> CompileResult<Field> {
+    let Expr::Ident(field, s) = r else {
+        Err(Error::Compile("Expected field name".to_owned(), r.span()))?
+    };
+
+    let ty = l.resolve_type(st)?;
+    let name = struct_name(&ty, "fields", l.span())?;
+
+    let field = st
+        .get_struct(&name, l.span())?
+        .iter()
+        .find(|f| &f.1 == field)
+        .cloned()
+        .ok_or_else(|| Error::Compile(format!("No field `{}` on type `{}`", field, name), *s))?;
+
+    let bindings = type_bindings(st, &ty)?;
+    let field = Field(field.0, field.1, substitute(&field.2, &bindings), field.3);
+
+    if !st.can_access(&name, &field.0) {
+        Err(Error::Compile(
+            format!(
+                "Field `{}` of `{}` is {}",
+                field.1,
+                name,
+                field.0.to_cpp(st)?
+            ),
+            *s,
+        ))?
+    }
+
+    Ok(field)
+}
+
+/// `Name { field: value }`, returns the translated literal and its type
+pub fn struct_literal(
+    st: &mut State,
+    name: &str,
+    inits: &[FieldInit],
+    span: SimpleSpan,
+) -> CompileResult<(String, Type)> {
+    let fields = st.get_struct(name, span)?.to_vec();
+
+    for (i, FieldInit(field, _, s)) in inits.iter().enumerate() {
+        let Some(Field(access_modifier, ..)) = fields.iter().find(|f| &f.1 == field) else {
+            Err(Error::Compile(
+                format!("No field `{}` on type `{}`", field, name),
+                *s,
+            ))?
+        };
+
+        // Private fields are only set by the methods of the struct, like in field access
+        if !st.can_access(name, access_modifier) {
+            Err(Error::Compile(
+                format!(
+                    "Field `{}` of `{}` is {}",
+                    field,
+                    name,
+                    access_modifier.to_cpp(st)?
+                ),
+                *s,
+            ))?
+        }
+
+        if inits[..i].iter().any(|f| &f.0 == field) {
+            Err(Error::Compile(
+                format!("Field `{}` specified more than once", field),
+                *s,
+            ))?
+        }
+    }
+
+    let missing = fields
+        .iter()
+        .filter(|f| !inits.iter().any(|i| i.0 == f.1))
+        .map(|f| format!("`{}`", f.1))
+        .collect::<Vec<_>>();
+
+    if !missing.is_empty() {
+        Err(Error::Compile(
+            format!("Missing field(s) {} in `{}`", missing.join(", "), name),
+            span,
+        ))?
+    }
+
+    let values = fields
+        .iter()
+        .map(|f| &inits.iter().find(|i| i.0 == f.1).unwrap().1)
+        .collect::<Vec<_>>();
+
+    let generics = st.get_generics(name, span)?.to_vec();
+
+    let types = fields.iter().map(|f| f.2.clone()).collect::<Vec<_>>();
+
+    let (ty, bindings) = if generics.is_empty() {
+        (Type::Custom(name.to_owned(), span), HashMap::new())
+    } else {
+        let bindings = infer_bindings(st, name, &generics, &types, &values, span)?;
+
+        (
+            Type::Generic(
+                name.to_owned(),
+                generics.iter().map(|g| bindings[g].clone()).collect(),
+                span,
+            ),
+            bindings,
+        )
+    };
+
+    let types = types
+        .iter()
+        .map(|ty| substitute(ty, &bindings))
+        .collect::<Vec<_>>();
+
+    for (v, ty) in values.iter().zip(&types) {
+        check_binding(st, v, ty)?;
+    }
+
+    let cvalues = values
+        .iter()
+        .zip(&types)
+        .map(|(v, ty)| value_as(st, v, ty))
+        .collect::<CompileResult<Vec<_>>>()?;
+
+    // Type arguments of generic structs are deduced by the C++ compiler
+    Ok((format!("{}({})", cpp_ident(name), cvalues.join(", ")), ty))
+}
+
+fn resolve_method(st: &mut State, ty: &str, method: &Expr) -> CompileResult<Method> {
+    let Expr::Ident(name, s) = method else {
+        Err(Error::Compile(
+            "Expected method name".to_owned(),
+            method.span(),
+        ))?
+    };
+
+    let method = st.get_method(ty, name, *s)?.clone();
+
+    if !st.can_access(ty, &method.access_modifier) {
+        Err(Error::Compile(
+            format!(
+                "Method `{}` of `{}` is {}",
+                name,
+                ty,
+                method.access_modifier.to_cpp(st)?
+            ),
+            *s,
+        ))?
+    }
+
+    Ok(method)
+}
+
+/// `value.method(args)`, returns the translated call and its type
+pub fn method_call(
+    st: &mut State,
+    value: &Expr,
+    method: &Expr,
+    args: &[Expr],
+    span: SimpleSpan,
+) -> CompileResult<(String, Type)> {
+    let ty = value.resolve_type(st)?;
+
+    if let Some(call) = library_method_call(st, &ty, value, method, args, span)? {
+        return Ok(call);
+    }
+
+    if let Some(call) = array_method_call(st, &ty, value, method, args, span)? {
+        return Ok(call);
+    }
+
+    let ty = struct_name(&ty, "methods", value.span())?;
+
+    let resolved = resolve_method(st, &ty, method)?;
+
+    if resolved.receiver.is_none() {
+        Err(Error::Compile(
+            format!(
+                "`{}` is an associated function, call it as `{}::{}(...)`",
+                resolved.name, ty, resolved.name
+            ),
+            method.span(),
+        ))?
+    }
+
+    if let Some(Type::MutableRef(..)) = resolved.receiver {
+        check_borrow_mut(st, value)?;
+    }
+
+    check_args(st, &resolved.args, args, span)?;
+
+    let cargs = args_as(st, &resolved.args, args)?;
+
+    Ok((
+        format!(
+            "{}.{}({})",
+            value.to_cpp(st)?,
+            cpp_ident(&resolved.name),
+            cargs.join(", ")
+        ),
+        resolved.return_type,
+    ))
+}
+
+/// `Type::function(args)`, returns the translated call and its type
+pub fn associated_call(st: &mut State, l: &Expr, r: &Expr) -> CompileResult<(String, Type)> {
+    let ty = match l {
+        Expr::Ident(name, _) if name == "Self" => st.self_type.clone().ok_or_else(|| {
+            Error::Compile(
+                "`Self` is only available inside impl blocks".to_owned(),
+                l.span(),
+            )
+        })?,
+        Expr::Ident(name, s) => {
+            st.get_type(name, *s)?;
+
+            name.clone()
+        }
+        _ => Err(Error::Compile("Expected type name".to_owned(), l.span()))?,
+    };
+
+    let Expr::Call(method, args, span) = r else {
+        Err(Error::Compile(
+            "Expected associated function call".to_owned(),
+            r.span(),
+        ))?
+    };
+
+    let resolved = resolve_method(st, &ty, method)?;
+
+    if resolved.receiver.is_some() {
+        Err(Error::Compile(
+            format!("`{}` is a method, call it on a value", resolved.name),
+            method.span(),
+        ))?
+    }
+
+    check_args(st, &resolved.args, args, *span)?;
+
+    let cargs = args_as(st, &resolved.args, args)?;
+
+    Ok((
+        format!(
+            "{}::{}({})",
+            cpp_ident(&ty),
+            cpp_ident(&resolved.name),
+            cargs.join(", ")
+        ),
+        resolved.return_type,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_utils::{errors, translate};
+
+    const P: &str = "struct P { pub x: i32, pub y: i32 }
+        impl P { pub fn new(x: i32) -> P { return P { x: x, y: 0 }; } }
+        ";
+
+    #[test]
+    fn struct_literal_field_types() {
+        let errors_of =
+            |main: &str| errors(&format!("{}fn main() -> i32 {{ {} return 0; }}", P, main));
+
+        assert_eq!(
+            errors_of("let p: P = P { x: true, y: 1 };"),
+            ["Expected type i32, got bool"]
+        );
+        assert_eq!(
+            errors_of("let p: P = P { x: P::new(1), y: 1 };"),
+            ["Expected type i32, got P"]
+        );
+    }
+
+    #[test]
+    fn struct_literal_widens_fields() {
+        let code = translate(&format!(
+            "{}fn main() -> i32 {{ let b: u8 = 1; let p: P = P {{ x: b, y: 2 }}; return p.x; }}",
+            P
+        ));
+
+        assert!(code.contains("P(b, 2)"));
+    }
+
+    #[test]
+    fn struct_literal_private_fields() {
+        let src = "struct S { pub x: i32, count: i32 }
+            impl S { pub fn new(x: i32) -> S { return S { x: x, count: 0 }; } }
+            fn main() -> i32 { let s: S = S { x: 1, count: 2 }; return s.x; }";
+
+        assert_eq!(errors(src), ["Field `count` of `S` is private"]);
+
+        let code = translate(
+            "struct S { pub x: i32, count: i32 }
+            impl S { pub fn new(x: i32) -> S { return S { x: x, count: 0 }; } }
+            fn main() -> i32 { let s: S = S::new(1); return s.x; }",
+        );
+
+        assert!(code.contains("S(x, 0)"), "{}", code);
+    }
+}
@@ -0,0 +1,39 @@
+//! Helpers for the tests of the translation
+
+use comfy_types::Severity;
+
+use crate::{compile_to_cpp, Options};
+
+/// Translates `source`, panicking with the diagnostics when it fails
+pub fn translate(source: &str) -> String {
+    match compile_to_cpp(source, &Options::default()) {
+        Ok(output) => output.code,
+        Err(diagnostics) => panic!("translation failed: {:#?}", diagnostics),
+    }
+}
+
+/// Messages of the errors reported for `source`, panicking when it translates
+pub fn errors(source: &str) -> Vec<String> {
+    match compile_to_cpp(source, &Options::default()) {
+        Ok(output) => panic!("translation succeeded:\n{}", output.code),
+        Err(diagnostics) => diagnostics
+            .into_iter()
+            .filter(|d| d.severity == Severity::Error)
+            .map(|d| d.message)
+            .collect(),
+    }
+}
+
+/// Messages of the warnings reported for `source`
+pub fn warnings(source: &str) -> Vec<String> {
+    let diagnostics = match compile_to_cpp(source, &Options::default()) {
+        Ok(output) => output.warnings,
+        Err(diagnostics) => diagnostics,
+    };
+
+    diagnostics
+        .into_iter()
+        .filter(|d| d.severity == Severity::Warning)
+        .map(|d| d.message)
+        .collect()
+}
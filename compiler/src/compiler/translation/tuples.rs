@@ -0,0 +1,114 @@
+use chumsky::span::SimpleSpan;
+use comfy_types::{Expr, Literal, Type};
+
+use super::{cpp_ident, ComfyNode, CompileResult, Error, State};
+
+/// Element types of the tuple behind `ty`, references are dereferenced automatically
+fn tuple_types(ty: &Type, span: SimpleSpan) -> CompileResult<Vec<Type>> {
+    match ty {
+        Type::Tuple(types, _) => Ok(types.clone()),
+        Type::Reference(t, _) | Type::MutableRef(t, _) => tuple_types(t, span),
+        _ => Err(Error::Compile(format!("Type {} is not a tuple", ty), span)),
+    }
+}
+
+/// `value.0`, returns the translated access and its type. Nested accesses
+/// like `value.0.1` are lexed as a single decimal literal
+pub fn tuple_field(st: &mut State, value: &Expr, index: &Literal) -> CompileResult<(String, Type)> {
+    let Literal::Decimal(indices, None, s) = index else {
+        Err(Error::Compile(
+            "Expected tuple index".to_owned(),
+            index.span(),
+        ))?
+    };
+
+    let mut cvalue = value.to_cpp(st)?;
+    let mut ty = value.resolve_type(st)?;
+
+    for index in indices.split('.') {
+        let types = tuple_types(&ty, value.span())?;
+
+        let i = index
+            .parse::<usize>()
+            .ok()
+            .filter(|i| *i < types.len())
+            .ok_or_else(|| Error::Compile(format!("No field `{}` on type {}", index, ty), *s))?;
+
+        cvalue = format!("std::get<{}>({})", i, cvalue);
+        ty = types[i].clone();
+    }
+
+    Ok((cvalue, ty))
+}
+
+/// `let (a, b) = value;`, translated to a structured binding
+pub fn destructure(
+    st: &mut State,
+    names: &[String],
+    ty: &Type,
+    value: &Expr,
+    span: SimpleSpan,
+) -> CompileResult<String> {
+    let value_t = value.resolve_type(st)?;
+
+    if !matches!(ty, Type::Unknown(_)) {
+        // Checks the type arguments of the written type
+        ty.to_cpp(st)?;
+    }
+
+    if !matches!(ty, Type::Unknown(_)) && !value.casted_to(ty, st) {
+        Err(Error::Compile(
+            format!("Expected type {}, got {}", ty, value_t),
+            value.span(),
+        ))?
+    }
+
+    let types = tuple_types(&value_t, value.span())?;
+
+    if types.len() != names.len() {
+        Err(Error::Compile(
+            format!(
+                "Expected a tuple of {} element(s), got {}",
+                names.len(),
+                value_t
+            ),
+            span,
+        ))?
+    }
+
+    // C++ needs a distinct name for every element, even ignored ones
+    let bindings = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| match name.as_str() {
+            "_" => format!("_{}", i),
+            _ => cpp_ident(name),
+        })
+        .collect::<Vec<_>>();
+
+    let cvalue = value.to_cpp(st)?;
+
+    for (name, ty) in names.iter().zip(types) {
+        if name != "_" {
+            st.add_binding(name, ty, false, span);
+        }
+    }
+
+    Ok(format!("auto [{}] = {};", bindings.join(", "), cvalue))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_utils::translate;
+
+    #[test]
+    fn tuple_of_variables() {
+        let code = translate(
+            "fn swap(a: i32, b: i32) -> (i32, i32) { return (b, a); }
+            fn main() -> i32 { let a: i32 = 1; let t: (i32, i32) = (a, a); return swap(a, t.1).0; }",
+        );
+
+        assert!(code.contains("return std::make_tuple(b,a);"));
+        assert!(code.contains("t = std::make_tuple(a,a);"));
+    }
+}
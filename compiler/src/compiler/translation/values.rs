@@ -1,48 +1,102 @@
-use chumsky::{
-    error::Simple,
-    span::{SimpleSpan, Span},
-};
-use comfy_types::{Literal, Type};
+use std::num::IntErrorKind;
 
-use super::{ComfyNode, CompileResult, Error, State, TypeInfo};
+use chumsky::span::SimpleSpan;
+use comfy_types::{Literal, Type};
 
-impl ComfyNode<(String, TypeInfo)> for Type {
-    fn to_cpp(&self, st: &mut State) -> CompileResult<(String, TypeInfo)> {
-        let not_arr = TypeInfo(false, None);
-        let empty_arr = TypeInfo(true, None);
+use super::{
+    coercion::fits, cpp_ident, generics::generic_type, ComfyNode, CompileResult, Error, State,
+};
 
+impl ComfyNode<String> for Type {
+    fn to_cpp(&self, st: &mut State) -> CompileResult<String> {
         match self {
-            Type::Bool(_) => Ok(("bool".to_owned(), not_arr)),
-            Type::I8(_) => Ok(("int8_t".to_owned(), not_arr)),
-            Type::I16(_) => Ok(("int16_t".to_owned(), not_arr)),
-            Type::I32(_) => Ok(("int32_t".to_owned(), not_arr)),
-            Type::I64(_) => Ok(("int64_t".to_owned(), not_arr)),
-            Type::U8(_) => Ok(("uint8_t".to_owned(), not_arr)),
-            Type::U16(_) => Ok(("uint16_t".to_owned(), not_arr)),
-            Type::U32(_) => Ok(("uint32_t".to_owned(), not_arr)),
-            Type::U64(_) => Ok(("uint64_t".to_owned(), not_arr)),
-            Type::F32(_) => Ok(("float".to_owned(), not_arr)),
-            Type::F64(_) => Ok(("double".to_owned(), not_arr)),
-            Type::Int(_) => Ok(("int".to_owned(), not_arr)),
-            Type::Uint(_) => Ok(("unsigned int".to_owned(), not_arr)),
-            Type::Char(_) => Ok(("char".to_owned(), not_arr)),
-            Type::Str(_) => Ok(("std::string".to_owned(), not_arr)),
-            Type::Void(_) => Ok(("void".to_owned(), not_arr)),
-            Type::Never(_) => Ok(("void".to_owned(), not_arr)),
+            Type::Bool(_) => Ok("bool".to_owned()),
+            Type::I8(_) => Ok("int8_t".to_owned()),
+            Type::I16(_) => Ok("int16_t".to_owned()),
+            Type::I32(_) => Ok("int32_t".to_owned()),
+            Type::I64(_) => Ok("int64_t".to_owned()),
+            Type::U8(_) => Ok("uint8_t".to_owned()),
+            Type::U16(_) => Ok("uint16_t".to_owned()),
+            Type::U32(_) => Ok("uint32_t".to_owned()),
+            Type::U64(_) => Ok("uint64_t".to_owned()),
+            Type::F32(_) => Ok("float".to_owned()),
+            Type::F64(_) => Ok("double".to_owned()),
+            Type::Int(_) => Ok("int".to_owned()),
+            Type::Uint(_) => Ok("unsigned int".to_owned()),
+            Type::Char(_) => Ok("char".to_owned()),
+            Type::Str(_) => Ok("std::string".to_owned()),
+            Type::Void(_) => Ok("void".to_owned()),
+            Type::Never(_) => Ok("void".to_owned()),
             Type::Unknown(s) => Err(Error::Compile(
                 "Type can't be inferred, you need to specify it".to_owned(),
                 *s,
             )),
-            Type::Tuple(_, _) => todo!(),
+            Type::Tuple(types, _) => {
+                let ctypes = types
+                    .iter()
+                    .map(|ty| ty.to_cpp(st))
+                    .collect::<CompileResult<Vec<_>>>()?;
+
+                st.add_include("<tuple>");
+
+                Ok(format!("std::tuple<{}>", ctypes.join(", ")))
+            }
             Type::Array(ty, size, _) => {
-                Ok((format!("{}", ty.to_cpp(st)?.0), TypeInfo(true, Some(*size))))
+                st.add_include("<array>");
+
+                Ok(format!("std::array<{}, {}>", ty.to_cpp(st)?, size))
             }
-            Type::Slice(ty, _) => Ok((format!("{}", ty.to_cpp(st)?.0), empty_arr)),
-            Type::Custom(name, _) => Ok((name.into(), not_arr)),
-            Type::Pointer(ty, _) => Ok((format!("{}*", ty.to_cpp(st)?.0), not_arr)),
-            Type::MutableRef(ty, _) => Ok((format!("{}&", ty.to_cpp(st)?.0), not_arr)),
-            Type::Reference(ty, _) => Ok((format!("{}&", ty.to_cpp(st)?.0), not_arr)),
-            Type::Generic(_, _, _) => todo!(),
+            Type::Slice(ty, s) => Err(Error::Compile(
+                format!(
+                    "Slices must be borrowed, write `&[{}]` or `&mut [{}]`",
+                    ty, ty
+                ),
+                *s,
+            )),
+            Type::Custom(name, s) => {
+                st.get_type(name, *s)?;
+
+                let generics = st.get_generics(name, *s)?.len();
+
+                if generics > 0 {
+                    Err(Error::Compile(
+                        format!("Type `{}` expects {} type argument(s)", name, generics),
+                        *s,
+                    ))?
+                }
+
+                Ok(cpp_ident(name))
+            }
+            Type::Pointer(ty, _) => Ok(format!("{}*", ty.to_cpp(st)?)),
+            Type::MutableRef(ty, s) | Type::Reference(ty, s)
+                if matches!(ty.as_ref(), Type::Reference(..) | Type::MutableRef(..)) =>
+            {
+                Err(Error::Compile(
+                    format!(
+                        "Type {} is a reference to a reference, which is not supported",
+                        self
+                    ),
+                    *s,
+                ))
+            }
+            Type::MutableRef(ty, _) => match ty.as_ref() {
+                // Spans are views already, `&mut` only drops the const of the elements
+                Type::Slice(ty, _) => {
+                    st.add_include("<span>");
+
+                    Ok(format!("std::span<{}>", ty.to_cpp(st)?))
+                }
+                _ => Ok(format!("{}&", ty.to_cpp(st)?)),
+            },
+            Type::Reference(ty, _) => match ty.as_ref() {
+                Type::Slice(ty, _) => {
+                    st.add_include("<span>");
+
+                    Ok(format!("std::span<const {}>", ty.to_cpp(st)?))
+                }
+                _ => Ok(format!("const {}&", ty.to_cpp(st)?)),
+            },
+            Type::Generic(name, args, s) => generic_type(st, name, args, *s),
         }
     }
 
@@ -82,15 +136,145 @@ impl ComfyNode<(String, TypeInfo)> for Type {
     }
 }
 
+/// Type of an integer literal without a suffix, the smallest unsigned type it fits in
+fn unsigned_type(v: &str, radix: u32, s: SimpleSpan) -> CompileResult<Type> {
+    match u64::from_str_radix(v, radix) {
+        Ok(v) if v <= u8::MAX as u64 => Ok(Type::U8(s)),
+        Ok(v) if v <= u16::MAX as u64 => Ok(Type::U16(s)),
+        Ok(v) if v <= u32::MAX as u64 => Ok(Type::U32(s)),
+        Ok(_) => Ok(Type::U64(s)),
+        Err(e) if *e.kind() == IntErrorKind::PosOverflow => Err(out_of_range(v, radix, s)),
+        Err(_) => Err(Error::Compile("Invalid number literal".to_owned(), s)),
+    }
+}
+
+/// Error for an integer literal larger than any integer type
+fn out_of_range(v: &str, radix: u32, s: SimpleSpan) -> Error {
+    Error::Compile(
+        format!(
+            "Integer literal `{}` is out of range, the largest is u64::MAX",
+            written_literal(v, radix, false)
+        ),
+        s,
+    )
+}
+
+/// Digits and radix of an integer literal, `None` for other literals
+fn integer_digits(l: &Literal) -> Option<(&str, u32)> {
+    match l {
+        Literal::Decimal(v, _, _) => Some((v, 10)),
+        Literal::Hex(v, _, _) => Some((v, 16)),
+        Literal::Octal(v, _, _) => Some((v, 8)),
+        Literal::Binary(v, _, _) => Some((v, 2)),
+        _ => None,
+    }
+}
+
+/// Integer literal as written in Comfy, `-` included when it is negated
+fn written_literal(v: &str, radix: u32, negative: bool) -> String {
+    let prefix = match radix {
+        16 => "0x",
+        8 => "0o",
+        2 => "0b",
+        _ => "",
+    };
+
+    format!("{}{}{}", if negative { "-" } else { "" }, prefix, v)
+}
+
+/// Checks that the value of a literal with a suffix is a value of the suffix
+/// type, `negative` for a negated literal like `-128i8`
+fn suffixed_type(
+    v: &str,
+    radix: u32,
+    negative: bool,
+    ty: &Type,
+    s: SimpleSpan,
+) -> CompileResult<Type> {
+    let float = radix == 10 && v.contains(['.', 'e', 'E']);
+
+    match ty {
+        Type::F32(_) | Type::F64(_) if radix != 10 => Err(Error::Compile(
+            "Only decimal literals can have a float suffix".to_owned(),
+            ty.span(),
+        )),
+        Type::F32(_) | Type::F64(_) => Ok(ty.clone()),
+        _ if float => Err(Error::Compile(
+            format!("Float literal `{}` cannot have the suffix {}", v, ty),
+            ty.span(),
+        )),
+        _ => match i128::from_str_radix(v, radix) {
+            Ok(value) if fits(if negative { -value } else { value }, ty) => Ok(ty.clone()),
+            _ => Err(Error::Compile(
+                format!(
+                    "Literal `{}` does not fit in {}",
+                    written_literal(v, radix, negative),
+                    ty
+                ),
+                s,
+            )),
+        },
+    }
+}
+
+/// `-l` for a literal with an integer suffix, the sign is part of the value
+/// checked against the suffix. `None` for other literals
+pub fn negated_literal(st: &mut State, l: &Literal) -> Option<CompileResult<(String, Type)>> {
+    let (v, radix) = integer_digits(l)?;
+
+    let ty = match l {
+        Literal::Decimal(_, Some(ty), _)
+        | Literal::Hex(_, Some(ty), _)
+        | Literal::Octal(_, Some(ty), _)
+        | Literal::Binary(_, Some(ty), _) => ty,
+        _ => return None,
+    };
+
+    let translated = suffixed_type(v, radix, true, ty, l.span()).and_then(|ty| {
+        let cpp = format!(
+            "static_cast<{}>(-{})",
+            ty.to_cpp(st)?,
+            cpp_literal(v, radix)
+        );
+
+        Ok((cpp, ty))
+    });
+
+    Some(translated)
+}
+
+/// Integer literal as written in C++, octal literals start with `0`
+fn cpp_literal(v: &str, radix: u32) -> String {
+    match radix {
+        16 => format!("0x{}", v),
+        8 => format!("0{}", v),
+        2 => format!("0b{}", v),
+        _ => v.to_owned(),
+    }
+}
+
 impl ComfyNode<String> for Literal {
-    fn to_cpp(&self, _: &mut State) -> CompileResult<String> {
+    fn to_cpp(&self, st: &mut State) -> CompileResult<String> {
         Ok(match self {
             Literal::True(_) => "true".to_owned(),
             Literal::False(_) => "false".to_owned(),
-            Literal::Decimal(v, _) => v.into(),
-            Literal::Hex(v, _) => format!("0x{}", v),
-            Literal::Octal(v, _) => format!("0{}", v),
-            Literal::Binary(v, _) => format!("0b{}", v),
+            Literal::Decimal(v, ty, _)
+            | Literal::Hex(v, ty, _)
+            | Literal::Octal(v, ty, _)
+            | Literal::Binary(v, ty, _) => {
+                let radix = integer_digits(self).map_or(10, |(_, radix)| radix);
+                let v = cpp_literal(v, radix);
+
+                match ty {
+                    // The value is checked against the suffix
+                    Some(ty) => {
+                        self.resolve_type(st)?;
+
+                        format!("static_cast<{}>({})", ty.to_cpp(st)?, v)
+                    }
+                    None => v,
+                }
+            }
             Literal::Char(v, _) => format!("'{}'", v),
             Literal::Str(v, _) => format!("\"{}\"", v),
         })
@@ -100,10 +284,10 @@ impl ComfyNode<String> for Literal {
         match self {
             Literal::True(s) => *s,
             Literal::False(s) => *s,
-            Literal::Decimal(_, s) => *s,
-            Literal::Hex(_, s) => *s,
-            Literal::Octal(_, s) => *s,
-            Literal::Binary(_, s) => *s,
+            Literal::Decimal(_, _, s) => *s,
+            Literal::Hex(_, _, s) => *s,
+            Literal::Octal(_, _, s) => *s,
+            Literal::Binary(_, _, s) => *s,
             Literal::Char(_, s) => *s,
             Literal::Str(_, s) => *s,
         }
@@ -113,7 +297,11 @@ impl ComfyNode<String> for Literal {
         match self {
             Literal::True(s) => Ok(Type::Bool(*s)),
             Literal::False(s) => Ok(Type::Bool(*s)),
-            Literal::Decimal(v, s) => {
+            Literal::Decimal(v, Some(ty), s) => suffixed_type(v, 10, false, ty, *s),
+            Literal::Hex(v, Some(ty), s) => suffixed_type(v, 16, false, ty, *s),
+            Literal::Octal(v, Some(ty), s) => suffixed_type(v, 8, false, ty, *s),
+            Literal::Binary(v, Some(ty), s) => suffixed_type(v, 2, false, ty, *s),
+            Literal::Decimal(v, None, s) => {
                 let u8 = v.parse::<u8>().is_ok();
                 let u16 = v.parse::<u16>().is_ok();
                 let u32 = v.parse::<u32>().is_ok();
@@ -124,6 +312,7 @@ impl ComfyNode<String> for Literal {
                 let i64 = v.parse::<i64>().is_ok();
                 let f32 = v.parse::<f32>().is_ok();
                 let f64 = v.parse::<f64>().is_ok();
+                let float = v.contains(['.', 'e', 'E']);
 
                 match true {
                     _ if u8 => Ok(Type::U8(*s)),
@@ -134,16 +323,104 @@ impl ComfyNode<String> for Literal {
                     _ if i16 => Ok(Type::I16(*s)),
                     _ if i32 => Ok(Type::I32(*s)),
                     _ if i64 => Ok(Type::I64(*s)),
+                    _ if !float && f64 => Err(out_of_range(v, 10, *s)),
                     _ if f32 => Ok(Type::F32(*s)),
                     _ if f64 => Ok(Type::F64(*s)),
                     _ => Err(Error::Compile("Invalid number literal".to_owned(), *s)),
                 }
             }
-            Literal::Hex(_, _) => todo!(),
-            Literal::Octal(_, _) => todo!(),
-            Literal::Binary(_, _) => todo!(),
+            Literal::Hex(v, None, s) => unsigned_type(v, 16, *s),
+            Literal::Octal(v, None, s) => unsigned_type(v, 8, *s),
+            Literal::Binary(v, None, s) => unsigned_type(v, 2, *s),
             Literal::Char(_, s) => Ok(Type::Char(*s)),
             Literal::Str(_, s) => Ok(Type::Str(*s)),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_utils::{errors, translate};
+
+    fn binding(ty: &str, value: &str) -> String {
+        format!(
+            "fn main() -> i32 {{ let a: {} = {}; return a as i32; }}",
+            ty, value
+        )
+    }
+
+    #[test]
+    fn suffixed_literal_bounds() {
+        let cases = [
+            ("i8", "-128i8", "static_cast<int8_t>(-128)"),
+            ("i8", "127i8", "static_cast<int8_t>(127)"),
+            ("i8", "-0x80i8", "static_cast<int8_t>(-0x80)"),
+            ("u8", "255u8", "static_cast<uint8_t>(255)"),
+            ("u8", "0xFF_u8", "static_cast<uint8_t>(0xFF)"),
+            ("u8", "1_u8", "static_cast<uint8_t>(1)"),
+            (
+                "i64",
+                "-9223372036854775808i64",
+                "static_cast<int64_t>(-9223372036854775808)",
+            ),
+        ];
+
+        for (ty, value, cpp) in cases {
+            assert!(translate(&binding(ty, value)).contains(cpp), "{}", value);
+        }
+    }
+
+    #[test]
+    fn suffixed_literal_out_of_range() {
+        let cases = [
+            ("i8", "128i8", "Literal `128` does not fit in i8"),
+            ("i8", "-129i8", "Literal `-129` does not fit in i8"),
+            ("u8", "256u8", "Literal `256` does not fit in u8"),
+            ("u8", "-1u8", "Literal `-1` does not fit in u8"),
+            ("i8", "0xFFi8", "Literal `0xFF` does not fit in i8"),
+            ("i8", "-0x81i8", "Literal `-0x81` does not fit in i8"),
+            ("u8", "0o400u8", "Literal `0o400` does not fit in u8"),
+            (
+                "u8",
+                "0b1_0000_0000u8",
+                "Literal `0b100000000` does not fit in u8",
+            ),
+        ];
+
+        for (ty, value, message) in cases {
+            assert_eq!(errors(&binding(ty, value)), [message], "{}", value);
+        }
+    }
+
+    #[test]
+    fn unsuffixed_literal_out_of_range() {
+        let cases = [
+            (
+                "18446744073709551616",
+                "Integer literal `18446744073709551616` is out of range, the largest is u64::MAX",
+            ),
+            (
+                "0x1_0000_0000_0000_0000",
+                "Integer literal `0x10000000000000000` is out of range, the largest is u64::MAX",
+            ),
+        ];
+
+        for (value, message) in cases {
+            assert_eq!(errors(&binding("u64", value)), [message], "{}", value);
+        }
+
+        let found = translate(&binding("u64", "18446744073709551615"));
+        assert!(
+            found.contains("uint64_t a = 18446744073709551615"),
+            "{}",
+            found
+        );
+
+        let found = translate(&binding("f64", "18446744073709551616.0"));
+        assert!(
+            found.contains("double a = 18446744073709551616.0"),
+            "{}",
+            found
+        );
+    }
+}
@@ -0,0 +1,241 @@
+use comfy_types::{Expr, FieldInit, MatchExprArm, Pattern};
+
+use crate::compiler::ComfyNode;
+
+use super::{Formatter, INDENT};
+
+/// Binding power of the operator at the root of `e`, the same as in the parser
+fn precedence(e: &Expr) -> u8 {
+    match e {
+        Expr::Literal(_)
+        | Expr::Type(_)
+        | Expr::CppCode(_, _)
+        | Expr::Ident(_, _)
+        | Expr::Tuple(_, _)
+        | Expr::Array(_, _)
+        | Expr::Repeat(_, _, _)
+        | Expr::Struct(_, _, _)
+        | Expr::Match(_, _, _)
+        | Expr::Unknown => 20,
+        Expr::Call(_, _, _) => 19,
+        Expr::ArrMember(_, _) => 18,
+        Expr::Path(_, _) => 16,
+        Expr::Member(_, _) | Expr::IncR(_) | Expr::DecR(_) | Expr::Factorial(_) => 15,
+        Expr::Cast(_, _)
+        | Expr::IncL(_)
+        | Expr::DecL(_)
+        | Expr::Neg(_)
+        | Expr::Pos(_)
+        | Expr::Not(_)
+        | Expr::BitNot(_)
+        | Expr::Deref(_)
+        | Expr::Address(_)
+        | Expr::MutAddress(_)
+        | Expr::Size(_)
+        | Expr::Align(_) => 14,
+        Expr::Mul(_, _) | Expr::Div(_, _) | Expr::Mod(_, _) => 13,
+        Expr::Add(_, _) | Expr::Sub(_, _) => 12,
+        Expr::Shl(_, _) | Expr::Shr(_, _) => 11,
+        Expr::Lt(_, _) | Expr::Le(_, _) | Expr::Gt(_, _) | Expr::Ge(_, _) => 10,
+        Expr::Eq(_, _) | Expr::Ne(_, _) => 9,
+        Expr::BitAnd(_, _) => 8,
+        Expr::BitXor(_, _) => 7,
+        Expr::BitOr(_, _) => 6,
+        Expr::And(_, _) => 5,
+        Expr::Or(_, _) => 4,
+        Expr::Range(_, _) | Expr::RangeInclusive(_, _) => 3,
+        Expr::Assign(_, _)
+        | Expr::AddAssign(_, _)
+        | Expr::SubAssign(_, _)
+        | Expr::MulAssign(_, _)
+        | Expr::DivAssign(_, _)
+        | Expr::ModAssign(_, _)
+        | Expr::ShlAssign(_, _)
+        | Expr::ShrAssign(_, _)
+        | Expr::BitAndAssign(_, _)
+        | Expr::BitXorAssign(_, _)
+        | Expr::BitOrAssign(_, _) => 2,
+    }
+}
+
+impl Formatter<'_> {
+    /// `e` in parentheses unless it binds at least as tight as `min`
+    fn operand(&self, e: &Expr, min: u8) -> String {
+        match precedence(e) < min {
+            true => format!("({})", self.expr(e)),
+            false => self.expr(e),
+        }
+    }
+
+    fn list(&self, exprs: &[Expr]) -> String {
+        exprs
+            .iter()
+            .map(|e| self.expr(e))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn infix(&self, l: &Expr, op: &str, r: &Expr, power: u8) -> String {
+        format!(
+            "{} {} {}",
+            self.operand(l, power),
+            op,
+            self.operand(r, power + 1)
+        )
+    }
+
+    fn assign(&self, l: &Expr, op: &str, r: &Expr) -> String {
+        format!("{} {} {}", self.operand(l, 3), op, self.operand(r, 2))
+    }
+
+    fn prefix(&self, op: &str, e: &Expr) -> String {
+        // Casts bind as tight as prefix operators, parentheses keep them inside
+        let operand = match e {
+            Expr::Cast(_, _) => format!("({})", self.expr(e)),
+            e => self.operand(e, 14),
+        };
+
+        // `- -x` must not become `--x`
+        match operand.chars().next() == op.chars().last() {
+            true => format!("{} {}", op, operand),
+            false => format!("{}{}", op, operand),
+        }
+    }
+
+    fn postfix(&self, e: &Expr, op: &str) -> String {
+        format!("{}{}", self.operand(e, 15), op)
+    }
+
+    pub(super) fn expr(&self, e: &Expr) -> String {
+        match e {
+            Expr::Literal(l) => self.src[l.span().into_range()].to_owned(),
+            Expr::Type(ty) => ty.to_string(),
+            Expr::CppCode(code, _) => format!("raw_cpp#{}#", code),
+            Expr::Ident(name, _) => name.clone(),
+
+            Expr::Add(l, r) => self.infix(l, "+", r, 12),
+            Expr::Sub(l, r) => self.infix(l, "-", r, 12),
+            Expr::Mul(l, r) => self.infix(l, "*", r, 13),
+            Expr::Div(l, r) => self.infix(l, "/", r, 13),
+            Expr::Mod(l, r) => self.infix(l, "%", r, 13),
+
+            Expr::Neg(e) => self.prefix("-", e),
+            Expr::Pos(e) => self.prefix("+", e),
+            Expr::IncL(e) => self.prefix("++", e),
+            Expr::DecL(e) => self.prefix("--", e),
+            Expr::Not(e) => self.prefix("!", e),
+            Expr::BitNot(e) => self.prefix("~", e),
+            Expr::Deref(e) => self.prefix("*", e),
+            Expr::Address(e) => self.prefix("&", e),
+            Expr::MutAddress(e) => self.prefix("&mut ", e),
+            Expr::Size(e) => self.prefix("sizeof ", e),
+            Expr::Align(e) => self.prefix("alignof ", e),
+
+            Expr::IncR(e) => self.postfix(e, "++"),
+            Expr::DecR(e) => self.postfix(e, "--"),
+            Expr::Factorial(e) => self.postfix(e, "!"),
+
+            Expr::Eq(l, r) => self.infix(l, "==", r, 9),
+            Expr::Ne(l, r) => self.infix(l, "!=", r, 9),
+            Expr::Lt(l, r) => self.infix(l, "<", r, 10),
+            Expr::Le(l, r) => self.infix(l, "<=", r, 10),
+            Expr::Gt(l, r) => self.infix(l, ">", r, 10),
+            Expr::Ge(l, r) => self.infix(l, ">=", r, 10),
+
+            Expr::And(l, r) => self.infix(l, "&&", r, 5),
+            Expr::Or(l, r) => self.infix(l, "||", r, 4),
+
+            Expr::BitAnd(l, r) => self.infix(l, "&", r, 8),
+            Expr::BitOr(l, r) => self.infix(l, "|", r, 6),
+            Expr::BitXor(l, r) => self.infix(l, "^", r, 7),
+            Expr::Shl(l, r) => self.infix(l, "<<", r, 11),
+            Expr::Shr(l, r) => self.infix(l, ">>", r, 11),
+
+            Expr::Member(l, r) => format!("{}.{}", self.operand(l, 15), self.operand(r, 16)),
+            Expr::Path(l, r) => format!("{}::{}", self.operand(l, 16), self.operand(r, 17)),
+
+            Expr::Cast(e, ty) => format!("{} as {}", self.operand(e, 15), self.operand(ty, 14)),
+
+            Expr::Assign(l, r) => self.assign(l, "=", r),
+            Expr::AddAssign(l, r) => self.assign(l, "+=", r),
+            Expr::SubAssign(l, r) => self.assign(l, "-=", r),
+            Expr::MulAssign(l, r) => self.assign(l, "*=", r),
+            Expr::DivAssign(l, r) => self.assign(l, "/=", r),
+            Expr::ModAssign(l, r) => self.assign(l, "%=", r),
+            Expr::ShlAssign(l, r) => self.assign(l, "<<=", r),
+            Expr::ShrAssign(l, r) => self.assign(l, ">>=", r),
+            Expr::BitAndAssign(l, r) => self.assign(l, "&=", r),
+            Expr::BitXorAssign(l, r) => self.assign(l, "^=", r),
+            Expr::BitOrAssign(l, r) => self.assign(l, "|=", r),
+
+            Expr::Call(f, args, _) => format!("{}({})", self.operand(f, 19), self.list(args)),
+            Expr::ArrMember(arr, i) => format!("{}[{}]", self.operand(arr, 18), self.expr(i)),
+
+            Expr::Range(l, r) => format!("{}..{}", self.operand(l, 3), self.operand(r, 4)),
+            Expr::RangeInclusive(l, r) => {
+                format!("{}..={}", self.operand(l, 3), self.operand(r, 4))
+            }
+
+            // A single element needs the comma to stay a tuple
+            Expr::Tuple(items, _) if items.len() == 1 => format!("({},)", self.expr(&items[0])),
+            Expr::Tuple(items, _) => format!("({})", self.list(items)),
+            Expr::Array(items, _) => format!("[{}]", self.list(items)),
+            Expr::Repeat(value, len, _) => format!("[{}; {}]", self.expr(value), len),
+            Expr::Struct(name, fields, _) => format!(
+                "{} {{ {} }}",
+                name,
+                fields
+                    .iter()
+                    .map(|FieldInit(name, value, _)| format!("{}: {}", name, self.expr(value)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            // One arm per line, the lines after the first are indented from
+            // the statement the match is in
+            Expr::Match(value, arms, _) => {
+                let indent = INDENT.repeat(self.depth);
+
+                format!(
+                    "match {} {{\n{}{}}}",
+                    self.expr(value),
+                    arms.iter()
+                        .map(|MatchExprArm(pattern, value, _)| {
+                            format!(
+                                "{}{}{} => {},\n",
+                                indent,
+                                INDENT,
+                                self.pattern(pattern),
+                                // A nested match is one level deeper
+                                self.expr(value).replace('\n', &format!("\n{}", INDENT))
+                            )
+                        })
+                        .collect::<String>(),
+                    indent
+                )
+            }
+
+            Expr::Unknown => "".to_owned(),
+        }
+    }
+
+    pub(super) fn pattern(&self, pattern: &Pattern) -> String {
+        match pattern {
+            Pattern::Wildcard(_) => "_".to_owned(),
+            Pattern::Binding(name, _) => name.clone(),
+            Pattern::Literal(l) => self.src[l.span().into_range()].to_owned(),
+            Pattern::Variant(name, variant, patterns, _) => match patterns.len() {
+                0 => format!("{}::{}", name, variant),
+                _ => format!(
+                    "{}::{}({})",
+                    name,
+                    variant,
+                    patterns
+                        .iter()
+                        .map(|p| self.pattern(p))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            },
+        }
+    }
+}
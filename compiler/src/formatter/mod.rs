@@ -0,0 +1,494 @@
+use std::collections::VecDeque;
+
+use chumsky::{span::SimpleSpan, Parser};
+use comfy_parser::lexer::tokens_with_trivia;
+use comfy_types::{
+    tokens::Kind, AccessModifier, Argument, Ast, Expr, ExternFunction, Field, MatchArm, Statements,
+    Type, Variant,
+};
+
+use crate::compiler::ComfyNode;
+
+mod expression;
+
+const INDENT: &str = "  ";
+
+/// Writes an `Ast` back as Comfy source, comments are kept from the tokens
+/// and written before the item that follows them
+struct Formatter<'a> {
+    src: &'a str,
+    tokens: Vec<(Kind, SimpleSpan)>,
+    /// Comments not written yet, in source order
+    comments: VecDeque<(String, SimpleSpan)>,
+    out: String,
+    depth: usize,
+    /// End of the previous item in the current block, blank lines after it are kept
+    last_end: Option<usize>,
+}
+
+/// Formats `src`, which has been parsed into `ast`
+pub fn format(ast: &Ast, src: &str) -> String {
+    let tokens = tokens_with_trivia()
+        .parse(src)
+        .into_result()
+        .unwrap_or_default();
+
+    let comments = tokens
+        .iter()
+        .filter_map(|(kind, s)| match kind {
+            Kind::Comment(_) | Kind::DocComment(_) => Some((kind.to_string(), *s)),
+            _ => None,
+        })
+        .collect();
+
+    let mut f = Formatter {
+        src,
+        tokens,
+        comments,
+        out: String::new(),
+        depth: 0,
+        last_end: None,
+    };
+
+    f.statements(ast);
+    f.flush(src.len());
+
+    f.out
+}
+
+fn generics(generics: &[String]) -> String {
+    match generics.len() {
+        0 => "".to_owned(),
+        _ => format!("<{}>", generics.join(", ")),
+    }
+}
+
+fn annotation(ty: &Type) -> String {
+    match ty {
+        Type::Unknown(_) => "".to_owned(),
+        ty => format!(": {}", ty),
+    }
+}
+
+fn return_type(ty: &Type) -> String {
+    match ty {
+        Type::Unknown(_) => "".to_owned(),
+        ty => format!(" -> {}", ty),
+    }
+}
+
+/// Modifier written before a declaration, the parser gives an omitted one the span of the item
+fn access(modifier: &AccessModifier, item: SimpleSpan) -> &'static str {
+    match modifier {
+        AccessModifier::Public(_) => "pub ",
+        AccessModifier::Protected(_) => "prot ",
+        AccessModifier::Private(s) if *s == item => "",
+        AccessModifier::Private(_) => "priv ",
+    }
+}
+
+impl Formatter<'_> {
+    fn indent(&mut self) {
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn line(&mut self, text: &str) {
+        self.indent();
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    /// Keeps a single blank line where the source has any before `start`
+    fn separate(&mut self, start: usize) {
+        let blank = self
+            .last_end
+            .and_then(|end| self.src.get(end..start))
+            .is_some_and(|gap| gap.matches('\n').count() > 1);
+
+        if blank {
+            self.out.push('\n');
+        }
+    }
+
+    /// Writes the comments that start before `pos`
+    fn flush(&mut self, pos: usize) {
+        while let Some((text, s)) = self.comments.pop_front() {
+            if s.start >= pos {
+                self.comments.push_front((text, s));
+                break;
+            }
+
+            self.separate(s.start);
+            self.line(&text);
+            self.last_end = Some(s.end);
+        }
+    }
+
+    fn begin(&mut self, s: SimpleSpan) {
+        self.flush(s.start);
+        self.separate(s.start);
+    }
+
+    /// Ends an item, a comment on the same line stays behind it. A comment
+    /// after other tokens, like the `}` of `if c { a; } // c`, belongs to
+    /// the item those tokens end
+    fn end(&mut self, s: SimpleSpan) {
+        self.last_end = Some(s.end);
+
+        let Some((text, c)) = self.comments.front().cloned() else {
+            return;
+        };
+
+        let trailing = c.start >= s.end
+            && self
+                .src
+                .get(s.end..c.start)
+                .is_some_and(|gap| !gap.contains('\n'))
+            && !self
+                .tokens
+                .iter()
+                .any(|(_, t)| t.start >= s.end && t.end <= c.start);
+
+        if trailing {
+            self.comments.pop_front();
+            self.out.pop();
+            self.out.push(' ');
+            self.out.push_str(&text);
+            self.out.push('\n');
+            self.last_end = Some(c.end);
+        }
+    }
+
+    /// Opens a block after `header`, on the line of the previous closing brace if there is one
+    fn open(&mut self, header: &str) -> (usize, Option<usize>) {
+        if self.out.ends_with('}') {
+            self.out.push(' ');
+        } else {
+            self.indent();
+        }
+
+        self.out.push_str(header);
+        self.out.push_str(" {\n");
+        self.depth += 1;
+
+        (self.out.len(), self.last_end.take())
+    }
+
+    /// Closes a block that ends at `end`, the line is left open for what follows the brace
+    fn close(&mut self, (opened, last_end): (usize, Option<usize>), end: usize) {
+        self.flush(end);
+        self.depth -= 1;
+        self.last_end = last_end;
+
+        if self.out.len() == opened {
+            self.out.pop();
+        } else {
+            self.indent();
+        }
+
+        self.out.push('}');
+    }
+
+    fn block(&mut self, header: &str, stmts: &[Statements], end: usize) {
+        let block = self.open(header);
+        self.statements(stmts);
+        self.close(block, end);
+    }
+
+    fn args(&self, args: &[Argument]) -> String {
+        args.iter()
+            .map(|Argument(name, ty, default, _)| match (name.as_str(), ty) {
+                ("self", Type::Reference(_, _)) => "&self".to_owned(),
+                ("self", Type::MutableRef(_, _)) => "&mut self".to_owned(),
+                ("self", _) => "self".to_owned(),
+                _ => match default {
+                    Expr::Unknown => format!("{}{}", name, annotation(ty)),
+                    default => format!("{}{} = {}", name, annotation(ty), self.expr(default)),
+                },
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn statements(&mut self, stmts: &[Statements]) {
+        for stmt in stmts {
+            self.statement(stmt);
+        }
+    }
+
+    fn statement(&mut self, stmt: &Statements) {
+        let s = stmt.span();
+
+        self.begin(s);
+
+        match stmt {
+            Statements::ExpressionStatement(e, _) => self.line(&format!("{};", self.expr(e))),
+            Statements::LetStatement(name, mutable, ty, e, _) => self.line(&format!(
+                "let {}{}{} = {};",
+                if *mutable { "mut " } else { "" },
+                name,
+                annotation(ty),
+                self.expr(e)
+            )),
+            Statements::LetTupleStatement(names, ty, e, _) => self.line(&format!(
+                "let ({}){} = {};",
+                names.join(", "),
+                annotation(ty),
+                self.expr(e)
+            )),
+            Statements::FunctionDeclaration(modifier, name, g, args, ty, body, _) => {
+                let header = format!(
+                    "{}fn {}{}({}){}",
+                    access(modifier, s),
+                    name,
+                    generics(g),
+                    self.args(args),
+                    return_type(ty)
+                );
+
+                self.block(&header, body, s.end);
+                self.out.push('\n');
+            }
+            Statements::StructDeclaration(modifier, name, g, fields, _) => {
+                let block = self.open(&format!(
+                    "{}struct {}{}",
+                    access(modifier, s),
+                    name,
+                    generics(g)
+                ));
+
+                for Field(modifier, name, ty, fs) in fields {
+                    self.begin(*fs);
+                    self.line(&format!("{}{}: {},", access(modifier, *fs), name, ty));
+                    self.end(*fs);
+                }
+
+                self.close(block, s.end);
+                self.out.push('\n');
+            }
+            Statements::EnumDeclaration(modifier, name, variants, _) => {
+                let block = self.open(&format!("{}enum {}", access(modifier, s), name));
+
+                for Variant(name, types, vs) in variants {
+                    self.begin(*vs);
+
+                    match types.len() {
+                        0 => self.line(&format!("{},", name)),
+                        _ => self.line(&format!(
+                            "{}({}),",
+                            name,
+                            types
+                                .iter()
+                                .map(ToString::to_string)
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        )),
+                    }
+
+                    self.end(*vs);
+                }
+
+                self.close(block, s.end);
+                self.out.push('\n');
+            }
+            Statements::ImplDeclaration(name, methods, _) => {
+                self.block(&format!("impl {}", name), methods, s.end);
+                self.out.push('\n');
+            }
+            Statements::UseStatement(path, items, _) => {
+                let items = match items.as_slice() {
+                    [item] => item.clone(),
+                    items => format!("{{{}}}", items.join(", ")),
+                };
+
+                self.line(&format!("use {}::{};", path.join("::"), items));
+            }
+            Statements::ExternBlock(abi, includes, functions, _) => {
+                let header = includes.iter().fold(format!("extern \"{}\"", abi), |h, i| {
+                    format!("{} include \"{}\"", h, i)
+                });
+
+                let block = self.open(&header);
+
+                for ExternFunction(name, args, ty, fs) in functions {
+                    self.begin(*fs);
+                    self.line(&format!(
+                        "fn {}({}){};",
+                        name,
+                        self.args(args),
+                        return_type(ty)
+                    ));
+                    self.end(*fs);
+                }
+
+                self.close(block, s.end);
+                self.out.push('\n');
+            }
+            Statements::IfStatement(condition, then, els, _) => {
+                self.if_statement("if", condition, then, els, s);
+                self.out.push('\n');
+            }
+            Statements::MatchStatement(value, arms, _) => {
+                let block = self.open(&format!("match {}", self.expr(value)));
+
+                for MatchArm(pattern, body, arm) in arms {
+                    self.begin(*arm);
+                    self.block(&format!("{} =>", self.pattern(pattern)), body, arm.end);
+                    self.out.push_str(",\n");
+                    self.end(*arm);
+                }
+
+                self.close(block, s.end);
+                self.out.push('\n');
+            }
+            Statements::WhileStatement(condition, body, _) => {
+                self.block(&format!("while {}", self.expr(condition)), body, s.end);
+                self.out.push('\n');
+            }
+            Statements::ForStatement(name, iterable, body, _) => {
+                self.block(
+                    &format!("for {} in {}", name, self.expr(iterable)),
+                    body,
+                    s.end,
+                );
+                self.out.push('\n');
+            }
+            Statements::BreakStatement(_) => self.line("break;"),
+            Statements::ContinueStatement(_) => self.line("continue;"),
+            Statements::ReturnStatement(e, _) => {
+                // The last expression of a block returns without the keyword
+                let keyword = self
+                    .tokens
+                    .iter()
+                    .any(|(kind, t)| *kind == Kind::Return && t.start == s.start);
+
+                match keyword {
+                    true => self.line(&format!("return {};", self.expr(e))),
+                    false => self.line(&self.expr(e)),
+                }
+            }
+        }
+
+        self.end(s);
+    }
+
+    /// Writes an `if` and its `else` branches, a lone `if` in the `else` block is chained
+    fn if_statement(
+        &mut self,
+        header: &str,
+        condition: &Expr,
+        then: &[Statements],
+        els: &[Statements],
+        s: SimpleSpan,
+    ) {
+        let after_then = then.last().map_or(s.start, |stmt| stmt.span().end);
+
+        let else_at = self
+            .tokens
+            .iter()
+            .find(|(kind, t)| *kind == Kind::Else && t.start >= after_then && t.end <= s.end)
+            .map_or(s.end, |(_, t)| t.start);
+
+        self.block(
+            &format!("{} {}", header, self.expr(condition)),
+            then,
+            else_at,
+        );
+
+        match els {
+            [] => {}
+            [Statements::IfStatement(condition, then, els, es)]
+                if self
+                    .comments
+                    .front()
+                    .is_none_or(|(_, c)| c.start >= es.start) =>
+            {
+                self.if_statement("else if", condition, then, els, *es)
+            }
+            els => self.block("else", els, s.end),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format;
+
+    /// Formats `src` and checks that formatting the result changes nothing
+    fn round_trip(src: &str) -> String {
+        let formatted = format(&comfy_parser::parse(src).unwrap(), src);
+        let again = format(&comfy_parser::parse(&formatted).unwrap(), &formatted);
+
+        assert_eq!(formatted, again);
+
+        formatted
+    }
+
+    #[test]
+    fn trailing_comment_after_else() {
+        let src = "fn main() -> i32 {
+  let x = 1;
+  if x > 0 { x; } else { x; } // c
+  0
+}
+";
+
+        assert_eq!(
+            round_trip(src),
+            "fn main() -> i32 {
+  let x = 1;
+  if x > 0 {
+    x;
+  } else {
+    x;
+  } // c
+  0
+}
+"
+        );
+    }
+
+    #[test]
+    fn trailing_comments_are_kept() {
+        let src = "fn main() -> i32 {
+  let x = 1; // a
+  if x > 0 {
+    x; // b
+  } else if x < 0 {
+    x;
+  } else {
+    x;
+  } // c
+  0
+}
+";
+
+        assert_eq!(round_trip(src), src);
+    }
+
+    #[test]
+    fn match_expressions() {
+        let src = "fn main() -> i32 {
+  let a = match n { 1 => match m { true => 1, false => 2 }, _ => 3 };
+  a
+}
+";
+
+        assert_eq!(
+            round_trip(src),
+            "fn main() -> i32 {
+  let a = match n {
+    1 => match m {
+      true => 1,
+      false => 2,
+    },
+    _ => 3,
+  };
+  a
+}
+"
+        );
+    }
+}
@@ -0,0 +1,316 @@
+use std::{
+    io::{self, Write},
+    iter::Peekable,
+    str::Chars,
+};
+
+use super::Value;
+
+/// Width or precision of a conversion, C's `printf` fails when it does not fit in an `int`
+fn digits(chars: &mut Peekable<Chars>) -> Result<Option<usize>, String> {
+    let mut n = None;
+
+    while let Some(d) = chars.next_if(char::is_ascii_digit) {
+        n = Some(
+            n.unwrap_or(0usize)
+                .checked_mul(10)
+                .and_then(|n| n.checked_add(d.to_digit(10).unwrap() as usize))
+                .filter(|n| *n <= i32::MAX as usize)
+                .ok_or("Field width or precision is too large")?,
+        );
+    }
+
+    Ok(n)
+}
+
+/// `1.5e+00` like C instead of `1.5e0`
+fn exponent(v: f64, precision: usize, upper: bool) -> String {
+    let s = format!("{:.*e}", precision, v);
+    let (mantissa, exp) = s.split_once('e').unwrap();
+    let exp = exp.parse::<i32>().unwrap();
+    let s = format!(
+        "{}e{}{:02}",
+        mantissa,
+        if exp < 0 { '-' } else { '+' },
+        exp.abs()
+    );
+
+    if upper {
+        s.to_uppercase()
+    } else {
+        s
+    }
+}
+
+/// `%g`, the shorter of `%f` and `%e` without trailing zeros
+fn general(v: f64, precision: usize, upper: bool) -> String {
+    let precision = precision.max(1);
+    let exp = if v == 0.0 {
+        0
+    } else {
+        v.abs().log10().floor() as i32
+    };
+
+    let trim = |s: String| match s.find(['e', 'E']) {
+        Some(i) if s[..i].contains('.') => {
+            let (mantissa, exp) = s.split_at(i);
+
+            format!(
+                "{}{}",
+                mantissa.trim_end_matches('0').trim_end_matches('.'),
+                exp
+            )
+        }
+        None if s.contains('.') => s.trim_end_matches('0').trim_end_matches('.').to_owned(),
+        _ => s,
+    };
+
+    if exp < -4 || exp >= precision as i32 {
+        trim(exponent(v, precision - 1, upper))
+    } else {
+        trim(format!("{:.*}", (precision as i32 - 1 - exp) as usize, v))
+    }
+}
+
+/// Formats `args` like C's `printf`, the first argument is the format string
+fn sprintf(args: &[Value]) -> Result<String, String> {
+    let Some(Value::Str(format)) = args.first() else {
+        return Err("Expected a format string".to_owned());
+    };
+
+    let mut args = args[1..].iter();
+    let mut out = String::new();
+    let mut chars = format.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+
+        let mut flags = String::new();
+
+        while let Some(flag) = chars.next_if(|c| "-+ 0#".contains(*c)) {
+            flags.push(flag);
+        }
+
+        let width = digits(&mut chars)?.unwrap_or(0);
+        let precision = match chars.next_if_eq(&'.') {
+            Some(_) => Some(digits(&mut chars)?.unwrap_or(0)),
+            None => None,
+        };
+
+        let mut long = false;
+
+        while let Some(length) = chars.next_if(|c| "hlLqjzt".contains(*c)) {
+            long |= length != 'h';
+        }
+
+        let conversion = chars.next().ok_or("Incomplete format specifier")?;
+
+        if conversion == '%' {
+            out.push('%');
+            continue;
+        }
+
+        let arg = args
+            .next()
+            .ok_or_else(|| format!("Missing argument for `%{}`", conversion))?;
+
+        let unsigned = |v: &Value| {
+            if long {
+                v.as_int() as u64
+            } else {
+                v.as_int() as u32 as u64
+            }
+        };
+
+        let (sign, body) = match conversion {
+            'd' | 'i' => {
+                let v = if long {
+                    arg.as_int() as i64
+                } else {
+                    arg.as_int() as i32 as i64
+                };
+
+                (v < 0, v.unsigned_abs().to_string())
+            }
+            'u' => (false, unsigned(arg).to_string()),
+            'x' => (false, format!("{:x}", unsigned(arg))),
+            'X' => (false, format!("{:X}", unsigned(arg))),
+            'o' => (false, format!("{:o}", unsigned(arg))),
+            'f' | 'F' | 'e' | 'E' | 'g' | 'G' => {
+                let v = arg.as_float();
+                let precision = precision.unwrap_or(6);
+
+                let upper = conversion.is_ascii_uppercase();
+
+                let body = match conversion {
+                    _ if v.is_infinite() && upper => "INF".to_owned(),
+                    _ if v.is_infinite() => "inf".to_owned(),
+                    _ if v.is_nan() && upper => "NAN".to_owned(),
+                    _ if v.is_nan() => "nan".to_owned(),
+                    'e' | 'E' => exponent(v.abs(), precision, upper),
+                    'g' | 'G' => general(v.abs(), precision, upper),
+                    _ => format!("{:.*}", precision, v.abs()),
+                };
+
+                (v.is_sign_negative() && v != 0.0, body)
+            }
+            'c' => (
+                false,
+                match arg {
+                    Value::Char(c) => c.to_string(),
+                    v => char::from_u32(v.as_int() as u32)
+                        .unwrap_or('\0')
+                        .to_string(),
+                },
+            ),
+            's' => {
+                let s = arg.to_string();
+
+                (
+                    false,
+                    match precision {
+                        Some(p) => s.chars().take(p).collect(),
+                        None => s,
+                    },
+                )
+            }
+            c => Err(format!("Unsupported format specifier `%{}`", c))?,
+        };
+
+        let numeric = !matches!(conversion, 'c' | 's');
+        // `inf` and `nan` are padded with spaces
+        let zeros = numeric && !["inf", "nan"].iter().any(|s| body.eq_ignore_ascii_case(s));
+        let sign = match sign {
+            true => "-",
+            false if numeric && flags.contains('+') => "+",
+            false if numeric && flags.contains(' ') => " ",
+            false => "",
+        };
+
+        let len = sign.chars().count() + body.chars().count();
+        let padding = width.saturating_sub(len);
+
+        if flags.contains('-') {
+            out.push_str(&format!("{}{}{}", sign, body, " ".repeat(padding)));
+        } else if flags.contains('0') && zeros {
+            out.push_str(&format!("{}{}{}", sign, "0".repeat(padding), body));
+        } else {
+            out.push_str(&format!("{}{}{}", " ".repeat(padding), sign, body));
+        }
+    }
+
+    Ok(out)
+}
+
+fn print(s: &str) {
+    print!("{}", s);
+    io::stdout().flush().ok();
+}
+
+/// Implementations of the C functions programs commonly declare in `extern`
+/// blocks, `None` if `name` is not one of them
+pub fn call(name: &str, args: &[Value]) -> Option<Result<Value, String>> {
+    let float = |i: usize| args.get(i).map(Value::as_float).unwrap_or_default();
+    let int = |i: usize| args.get(i).map(Value::as_int).unwrap_or_default();
+
+    Some(Ok(match name {
+        "printf" => match sprintf(args) {
+            Ok(out) => {
+                print(&out);
+
+                Value::Int(out.len() as i128)
+            }
+            Err(e) => return Some(Err(e)),
+        },
+        "puts" => {
+            print(&format!(
+                "{}\n",
+                args.first().cloned().unwrap_or(Value::Void)
+            ));
+
+            Value::Int(0)
+        }
+        "putchar" => {
+            print(&char::from(int(0) as u8).to_string());
+
+            Value::Int(int(0))
+        }
+        "abs" | "labs" | "llabs" => Value::Int(int(0).abs()),
+        "fabs" | "fabsf" => Value::Float(float(0).abs()),
+        "sqrt" | "sqrtf" => Value::Float(float(0).sqrt()),
+        "pow" | "powf" => Value::Float(float(0).powf(float(1))),
+        "floor" | "floorf" => Value::Float(float(0).floor()),
+        "ceil" | "ceilf" => Value::Float(float(0).ceil()),
+        "round" | "roundf" => Value::Float(float(0).round()),
+        "exp" | "expf" => Value::Float(float(0).exp()),
+        "log" | "logf" => Value::Float(float(0).ln()),
+        "sin" | "sinf" => Value::Float(float(0).sin()),
+        "cos" | "cosf" => Value::Float(float(0).cos()),
+        "tan" | "tanf" => Value::Float(float(0).tan()),
+        _ => return None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sprintf, Value};
+
+    fn format(format: &str, args: &[Value]) -> Result<String, String> {
+        let args = [vec![Value::Str(format.to_owned())], args.to_vec()].concat();
+
+        sprintf(&args)
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(
+            format(
+                "%d %5.2f %e %g %04x",
+                &[
+                    Value::Int(-3),
+                    Value::Float(1.5),
+                    Value::Float(1234.5),
+                    Value::Float(0.0001),
+                    Value::Int(255)
+                ]
+            ),
+            Ok("-3  1.50 1.234500e+03 0.0001 00ff".to_owned())
+        );
+    }
+
+    #[test]
+    fn infinity_and_nan() {
+        let inf = Value::Float(f64::INFINITY);
+        let minus_inf = Value::Float(f64::NEG_INFINITY);
+        let nan = Value::Float(f64::NAN);
+
+        assert_eq!(
+            format(
+                "%e %E %f %g",
+                &[inf.clone(), inf, minus_inf.clone(), minus_inf]
+            ),
+            Ok("inf INF -inf -inf".to_owned())
+        );
+        assert_eq!(
+            format("%e|%05f|%-4G|", &[nan.clone(), nan.clone(), nan]),
+            Ok("nan|  nan|NAN |".to_owned())
+        );
+    }
+
+    #[test]
+    fn width_overflow() {
+        let too_large = "Field width or precision is too large".to_owned();
+
+        assert_eq!(
+            format("%99999999999999999999d", &[Value::Int(1)]),
+            Err(too_large.clone())
+        );
+        assert_eq!(
+            format("%.2147483648f", &[Value::Float(1.0)]),
+            Err(too_large)
+        );
+    }
+}
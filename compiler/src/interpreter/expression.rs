@@ -0,0 +1,376 @@
+use std::{cell::RefCell, cmp::Ordering, rc::Rc};
+
+use chumsky::{input::Input, span::SimpleSpan, Parser};
+use comfy_parser::{expression, lexer::tokens};
+use comfy_types::{Expr, Literal, Type};
+
+use super::{
+    builtins, unsupported,
+    value::{promoted, Value},
+    Interpreter, RunResult,
+};
+use crate::compiler::{ComfyNode, Error};
+
+/// Replaces the escape sequences of a character or string literal
+fn unescape(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('0') => out.push('\0'),
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+
+    out
+}
+
+fn literal(l: &Literal) -> RunResult<Value> {
+    let int = |v: &str, radix: u32| {
+        i128::from_str_radix(&v.replace('_', ""), radix)
+            .map(Value::Int)
+            .map_err(|_| Error::Runtime("Invalid number literal".to_owned(), l.span()))
+    };
+
+    match l {
+        Literal::True(_) => Ok(Value::Bool(true)),
+        Literal::False(_) => Ok(Value::Bool(false)),
+        Literal::Decimal(v, ty, s)
+            if v.contains(['.', 'e', 'E']) || matches!(ty, Some(Type::F32(_) | Type::F64(_))) =>
+        {
+            v.parse()
+                .map(Value::Float)
+                .map_err(|_| Error::Runtime("Invalid number literal".to_owned(), *s))
+        }
+        Literal::Decimal(v, _, _) => int(v, 10),
+        Literal::Hex(v, _, _) => int(v, 16),
+        Literal::Octal(v, _, _) => int(v, 8),
+        Literal::Binary(v, _, _) => int(v, 2),
+        Literal::Char(v, _) => Ok(Value::Char(unescape(v).chars().next().unwrap_or('\0'))),
+        Literal::Str(v, _) => Ok(Value::Str(unescape(v))),
+    }
+}
+
+/// Arithmetic and bitwise operators, compound assignments use the operator they are named after
+fn arithmetic(op: &Expr, l: Value, r: Value) -> RunResult<Value> {
+    let span = op.span();
+
+    if let (Value::Str(l), Value::Str(r), Expr::Add(_, _) | Expr::AddAssign(_, _)) = (&l, &r, op) {
+        return Ok(Value::Str(format!("{}{}", l, r)));
+    }
+
+    if matches!(l, Value::Float(_)) || matches!(r, Value::Float(_)) {
+        let (l, r) = (l.as_float(), r.as_float());
+
+        return Ok(Value::Float(match op {
+            Expr::Add(_, _) | Expr::AddAssign(_, _) => l + r,
+            Expr::Sub(_, _) | Expr::SubAssign(_, _) => l - r,
+            Expr::Mul(_, _) | Expr::MulAssign(_, _) => l * r,
+            Expr::Div(_, _) | Expr::DivAssign(_, _) => l / r,
+            Expr::Mod(_, _) | Expr::ModAssign(_, _) => l % r,
+            _ => unsupported("bitwise operations on floats", span)?,
+        }));
+    }
+
+    let (l, r) = (l.as_int(), r.as_int());
+
+    Ok(Value::Int(match op {
+        Expr::Add(_, _) | Expr::AddAssign(_, _) => l.wrapping_add(r),
+        Expr::Sub(_, _) | Expr::SubAssign(_, _) => l.wrapping_sub(r),
+        Expr::Mul(_, _) | Expr::MulAssign(_, _) => l.wrapping_mul(r),
+        Expr::Div(_, _) | Expr::DivAssign(_, _) | Expr::Mod(_, _) | Expr::ModAssign(_, _)
+            if r == 0 =>
+        {
+            Err(Error::Runtime("Division by zero".to_owned(), span))?
+        }
+        Expr::Div(_, _) | Expr::DivAssign(_, _) => l.wrapping_div(r),
+        Expr::Mod(_, _) | Expr::ModAssign(_, _) => l.wrapping_rem(r),
+        Expr::BitAnd(_, _) | Expr::BitAndAssign(_, _) => l & r,
+        Expr::BitOr(_, _) | Expr::BitOrAssign(_, _) => l | r,
+        Expr::BitXor(_, _) | Expr::BitXorAssign(_, _) => l ^ r,
+        Expr::Shl(_, _) | Expr::ShlAssign(_, _) => l.wrapping_shl(r as u32),
+        Expr::Shr(_, _) | Expr::ShrAssign(_, _) => l.wrapping_shr(r as u32),
+        _ => unsupported("this operator", span)?,
+    }))
+}
+
+/// Arrays are compared element by element, like `std::array`
+fn ordering(l: &Value, r: &Value) -> Option<Ordering> {
+    match (l, r) {
+        (Value::Str(l), Value::Str(r)) => Some(l.cmp(r)),
+        (Value::Array(l), Value::Array(r)) => {
+            let (l, r) = (l.borrow(), r.borrow());
+
+            for (l, r) in l.iter().zip(r.iter()) {
+                match ordering(l, r) {
+                    Some(Ordering::Equal) => {}
+                    ordering => return ordering,
+                }
+            }
+
+            Some(l.len().cmp(&r.len()))
+        }
+        (Value::Float(_), _) | (_, Value::Float(_)) => l.as_float().partial_cmp(&r.as_float()),
+        _ => Some(l.as_int().cmp(&r.as_int())),
+    }
+}
+
+fn compare(op: &Expr, l: Value, r: Value) -> Value {
+    let ordering = ordering(&l, &r);
+
+    Value::Bool(match op {
+        Expr::Eq(_, _) => ordering == Some(Ordering::Equal),
+        Expr::Ne(_, _) => ordering != Some(Ordering::Equal),
+        Expr::Lt(_, _) => ordering == Some(Ordering::Less),
+        Expr::Le(_, _) => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        Expr::Gt(_, _) => ordering == Some(Ordering::Greater),
+        _ => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+    })
+}
+
+/// `.len()` of arrays and slices
+fn is_len_call(method: &Expr) -> bool {
+    matches!(method, Expr::Call(f, args, _)
+        if args.is_empty() && matches!(f.as_ref(), Expr::Ident(name, _) if name == "len"))
+}
+
+impl Interpreter {
+    /// Converts the result of `expr` to its type after the integer promotions
+    fn promote(&mut self, expr: &Expr, value: Value) -> RunResult<Value> {
+        Ok(value.cast(&promoted(expr.resolve_type(&mut self.st)?)))
+    }
+
+    fn binary(&mut self, expr: &Expr, l: &Expr, r: &Expr) -> RunResult<Value> {
+        let l = self.eval(l)?;
+        let r = self.eval(r)?;
+        let value = arithmetic(expr, l, r)?;
+
+        self.promote(expr, value)
+    }
+
+    /// The array behind `arr` and the checked position of `index` in it
+    fn element(&mut self, arr: &Expr, index: &Expr) -> RunResult<(Rc<RefCell<Vec<Value>>>, usize)> {
+        let Value::Array(values) = self.eval(arr)? else {
+            unsupported("indexing this value", arr.span())?
+        };
+
+        let i = self.eval(index)?.as_int();
+        let len = values.borrow().len();
+
+        if i < 0 || i >= len as i128 {
+            Err(Error::Runtime(
+                format!("Index {} out of bounds for array of length {}", i, len),
+                index.span(),
+            ))?
+        }
+
+        Ok((values, i as usize))
+    }
+
+    /// Assigns `value` converted to the type of `target`, returns the stored value
+    fn store(&mut self, target: &Expr, value: Value) -> RunResult<Value> {
+        let value = value.cast(&target.resolve_type(&mut self.st)?);
+
+        match target {
+            Expr::Ident(name, s) => self.update(name, value.clone(), *s)?,
+            Expr::ArrMember(arr, index) => {
+                let (values, i) = self.element(arr, index)?;
+
+                values.borrow_mut()[i] = value.clone();
+            }
+            _ => unsupported("assigning to this expression", target.span())?,
+        }
+
+        Ok(value)
+    }
+
+    fn step(&mut self, target: &Expr, delta: i128, postfix: bool) -> RunResult<Value> {
+        let old = self.eval(target)?;
+
+        let new = match &old {
+            Value::Float(v) => Value::Float(v + delta as f64),
+            v => Value::Int(v.as_int() + delta),
+        };
+
+        let new = self.store(target, new)?;
+
+        Ok(if postfix { old } else { new })
+    }
+
+    /// Inline C++ is only understood when it is a call of a builtin, like `printf("%d", x)`
+    fn raw_cpp(&mut self, code: &str, span: SimpleSpan) -> RunResult<Value> {
+        let expr = tokens().parse(code).into_result().ok().and_then(|tokens| {
+            expression()
+                .parse(tokens.as_slice().spanned((0..code.len()).into()))
+                .into_result()
+                .ok()
+        });
+
+        let Some(Expr::Call(f, args, _)) = expr else {
+            unsupported("inline C++", span)?
+        };
+
+        let Expr::Ident(name, _) = f.as_ref() else {
+            unsupported("inline C++", span)?
+        };
+
+        let values = args
+            .iter()
+            .map(|arg| self.eval(arg))
+            .collect::<RunResult<Vec<_>>>()
+            .map_err(|_| Error::Runtime("Invalid arguments in inline C++".to_owned(), span))?;
+
+        match builtins::call(name, &values) {
+            Some(result) => result.map_err(|msg| Error::Runtime(msg, span)),
+            None => unsupported("inline C++", span),
+        }
+    }
+
+    pub(super) fn eval(&mut self, expr: &Expr) -> RunResult<Value> {
+        Ok(match expr {
+            Expr::Literal(l) => literal(l)?,
+            Expr::Ident(name, s) => self.lookup(name, *s)?,
+            Expr::Add(l, r)
+            | Expr::Sub(l, r)
+            | Expr::Mul(l, r)
+            | Expr::Div(l, r)
+            | Expr::Mod(l, r)
+            | Expr::BitAnd(l, r)
+            | Expr::BitOr(l, r)
+            | Expr::BitXor(l, r)
+            | Expr::Shl(l, r)
+            | Expr::Shr(l, r) => self.binary(expr, l, r)?,
+            Expr::Neg(v) => {
+                let value = match self.eval(v)? {
+                    Value::Float(v) => Value::Float(-v),
+                    v => Value::Int(v.as_int().wrapping_neg()),
+                };
+
+                self.promote(expr, value)?
+            }
+            Expr::Pos(v) => {
+                let value = self.eval(v)?;
+
+                self.promote(expr, value)?
+            }
+            Expr::BitNot(v) => {
+                let value = Value::Int(!self.eval(v)?.as_int());
+
+                self.promote(expr, value)?
+            }
+            Expr::Factorial(v) => {
+                let n = self.eval(v)?.as_int();
+                let value = (2..=n).fold(1i128, |acc, i| acc.wrapping_mul(i));
+
+                self.promote(expr, Value::Int(value))?
+            }
+            Expr::Not(v) => Value::Bool(!self.eval(v)?.as_bool()),
+            Expr::IncR(v) => self.step(v, 1, true)?,
+            Expr::IncL(v) => self.step(v, 1, false)?,
+            Expr::DecR(v) => self.step(v, -1, true)?,
+            Expr::DecL(v) => self.step(v, -1, false)?,
+            Expr::Eq(l, r)
+            | Expr::Ne(l, r)
+            | Expr::Lt(l, r)
+            | Expr::Le(l, r)
+            | Expr::Gt(l, r)
+            | Expr::Ge(l, r) => {
+                let l = self.eval(l)?;
+                let r = self.eval(r)?;
+
+                compare(expr, l, r)
+            }
+            Expr::And(l, r) => Value::Bool(self.eval(l)?.as_bool() && self.eval(r)?.as_bool()),
+            Expr::Or(l, r) => Value::Bool(self.eval(l)?.as_bool() || self.eval(r)?.as_bool()),
+            Expr::Cast(v, ty) => {
+                let ty = ty.resolve_type(&mut self.st)?;
+
+                self.eval(v)?.cast(&ty)
+            }
+            Expr::Assign(l, r) => {
+                let value = self.eval(r)?;
+
+                self.store(l, value)?
+            }
+            Expr::AddAssign(l, r)
+            | Expr::SubAssign(l, r)
+            | Expr::MulAssign(l, r)
+            | Expr::DivAssign(l, r)
+            | Expr::ModAssign(l, r)
+            | Expr::ShlAssign(l, r)
+            | Expr::ShrAssign(l, r)
+            | Expr::BitAndAssign(l, r)
+            | Expr::BitXorAssign(l, r)
+            | Expr::BitOrAssign(l, r) => {
+                let current = self.eval(l)?;
+                let value = self.eval(r)?;
+                let value = arithmetic(expr, current, value)?;
+
+                self.store(l, value)?
+            }
+            Expr::Call(f, args, s) => match f.as_ref() {
+                Expr::Ident(name, _) => self.call(name, args, *s)?,
+                _ => unsupported("calling this expression", *s)?,
+            },
+            Expr::ArrMember(arr, index) => {
+                let (values, i) = self.element(arr, index)?;
+                let value = values.borrow()[i].clone();
+
+                value
+            }
+            Expr::Array(values, _) => Value::array(
+                values
+                    .iter()
+                    .map(|v| self.eval(v))
+                    .collect::<RunResult<Vec<_>>>()?,
+            ),
+            Expr::Repeat(value, len, _) => {
+                let value = self.eval(value)?;
+                let ty = expr.resolve_type(&mut self.st)?;
+
+                // Every element is a copy of the value
+                Value::array(vec![value; *len as usize]).cast(&ty)
+            }
+            Expr::CppCode(code, s) => self.raw_cpp(code, *s)?,
+            Expr::Unknown => Err(Error::Runtime("Unknown expression".to_owned(), expr.span()))?,
+            Expr::Range(_, _) | Expr::RangeInclusive(_, _) => {
+                unsupported("ranges outside of `for` loops", expr.span())?
+            }
+            Expr::Tuple(_, _) => unsupported("tuples", expr.span())?,
+            Expr::Member(v, method) if is_len_call(method) => match self.eval(v)? {
+                Value::Array(values) => Value::Int(values.borrow().len() as i128),
+                _ => unsupported("`len` of this value", expr.span())?,
+            },
+            Expr::Struct(_, _, _) | Expr::Member(_, _) => unsupported("structs", expr.span())?,
+            Expr::Path(_, _) => unsupported("paths", expr.span())?,
+            Expr::Match(_, _, _) => unsupported("`match`", expr.span())?,
+            Expr::Deref(_) | Expr::Address(_) | Expr::MutAddress(_) => {
+                unsupported("pointers", expr.span())?
+            }
+            Expr::Size(e) | Expr::Align(e) => {
+                let ty = match e.as_ref() {
+                    Expr::Type(ty) => ty.clone(),
+                    e => e.resolve_type(&mut self.st)?,
+                };
+
+                // Other layouts are chosen by the C++ compiler
+                match ty.layout() {
+                    Some((size, _)) if matches!(expr, Expr::Size(_)) => Value::Int(size.into()),
+                    Some((_, align)) => Value::Int(align.into()),
+                    None => unsupported("`sizeof` and `alignof` of this type", expr.span())?,
+                }
+            }
+            Expr::Type(_) => unsupported("types as values", expr.span())?,
+        })
+    }
+}
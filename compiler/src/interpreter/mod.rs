@@ -0,0 +1,512 @@
+use std::{collections::HashMap, rc::Rc};
+
+use chumsky::span::SimpleSpan;
+use comfy_types::{Ast, Expr, Statements, Type};
+
+use crate::compiler::{
+    coercion::common_type,
+    slices::{element_type, is_range},
+    statements::get_real_type_of_argument,
+    ComfyNode, Error, Ident, IdentValue, State,
+};
+
+mod builtins;
+mod expression;
+mod repl;
+mod value;
+
+pub use repl::{is_incomplete, Repl};
+pub use value::Value;
+
+pub type RunResult<T> = Result<T, Error>;
+
+/// How the execution of a statement continues
+enum Flow {
+    Normal,
+    Break,
+    Continue,
+    Return(Value),
+}
+
+/// Evaluates the `Ast` directly, identifiers are resolved through the same
+/// `State` as in the translator
+pub struct Interpreter {
+    st: State,
+    /// Values of the variables in scope, mirrors `st.scope_stack`
+    scopes: Vec<HashMap<String, Value>>,
+    /// Bodies of the declared functions, extern functions have none
+    bodies: HashMap<String, Rc<Vec<Statements>>>,
+}
+
+fn unsupported<T>(what: &str, span: SimpleSpan) -> RunResult<T> {
+    Err(Error::Runtime(
+        format!("The interpreter does not support {}", what),
+        span,
+    ))
+}
+
+/// Type-checks `ast` like the translator and runs its `main` function,
+/// returns the exit code of the program
+pub fn interpret(ast: &Ast, input_file: &str) -> Result<i32, Vec<Error>> {
+    let mut st = State::new();
+    st.set_input_file(input_file);
+
+    if let Err(e) = ast.to_cpp(&mut st) {
+        st.errors.push(e);
+    }
+
+    if !st.errors.is_empty() {
+        return Err(st.errors);
+    }
+
+    let mut interpreter = Interpreter {
+        st,
+        scopes: vec![HashMap::new()],
+        bodies: HashMap::new(),
+    };
+
+    interpreter.run(ast).map_err(|e| vec![e])
+}
+
+impl Interpreter {
+    fn run(&mut self, ast: &Ast) -> RunResult<i32> {
+        for stmt in ast {
+            match stmt {
+                Statements::FunctionDeclaration(_, name, _, _, _, body, _) => {
+                    self.bodies.insert(name.clone(), Rc::new(body.clone()));
+                }
+                // Signatures of extern functions are already registered in the state
+                Statements::ExternBlock(..) => {}
+                stmt => {
+                    self.execute(stmt)?;
+                }
+            }
+        }
+
+        let span = SimpleSpan::new(0, 0);
+
+        if !self.bodies.contains_key("main") {
+            Err(Error::Runtime("No `main` function".to_owned(), span))?
+        }
+
+        Ok(match self.call("main", &[], span)? {
+            Value::Void => 0,
+            code => code.as_int() as i32,
+        })
+    }
+
+    fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+        self.st.scope_stack.push(HashMap::new());
+    }
+
+    fn pop_scope(&mut self) {
+        self.scopes.pop();
+        self.st.scope_stack.pop();
+    }
+
+    /// Declares a variable in the innermost scope
+    fn define(&mut self, name: &str, ty: Type, mutable: bool, value: Value) {
+        let value = value.cast(&ty);
+
+        self.st.add_variable(name, ty, mutable);
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(name.to_owned(), value);
+    }
+
+    fn lookup(&self, name: &str, span: SimpleSpan) -> RunResult<Value> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+            .ok_or_else(|| Error::Runtime(format!("Unknown identifier: {}", name), span))
+    }
+
+    fn update(&mut self, name: &str, value: Value, span: SimpleSpan) -> RunResult<()> {
+        let slot = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name))
+            .ok_or_else(|| Error::Runtime(format!("Unknown identifier: {}", name), span))?;
+
+        *slot = value;
+
+        Ok(())
+    }
+
+    /// Calls the function `name`, arguments and defaults are evaluated in the scope of the caller
+    fn call(&mut self, name: &str, args: &[Expr], span: SimpleSpan) -> RunResult<Value> {
+        let (return_type, params) = match self.st.get_ident(name, span)? {
+            Ident {
+                return_type,
+                value: IdentValue::Func(_, params),
+                ..
+            } => (return_type.clone(), params.clone()),
+            _ => Err(Error::Runtime(
+                format!("`{}` is not a function", name),
+                span,
+            ))?,
+        };
+
+        let mut values = vec![];
+
+        for (i, param) in params.iter().enumerate() {
+            let value = match args.get(i) {
+                Some(arg) => self.eval(arg)?,
+                None => self.eval(&param.2)?,
+            };
+
+            values.push(value.cast(&param.1));
+        }
+
+        let Some(body) = self.bodies.get(name).cloned() else {
+            return builtins::call(name, &values)
+                .unwrap_or_else(|| {
+                    Err(format!(
+                        "Extern function `{}` is not available in the interpreter",
+                        name
+                    ))
+                })
+                .map(|value| value.cast(&return_type))
+                .map_err(|msg| Error::Runtime(msg, span));
+        };
+
+        // Only globals are visible from the callee
+        let scopes = self.scopes.split_off(1);
+        let st_scopes = self.st.scope_stack.split_off(1);
+
+        self.push_scope();
+
+        for (param, value) in params.iter().zip(values) {
+            self.define(&param.0, param.1.clone(), false, value);
+        }
+
+        let flow = self.execute_all(&body);
+
+        self.scopes.truncate(1);
+        self.scopes.extend(scopes);
+        self.st.scope_stack.truncate(1);
+        self.st.scope_stack.extend(st_scopes);
+
+        Ok(match flow? {
+            Flow::Return(value) => value.cast(&return_type),
+            _ => Value::Void,
+        })
+    }
+
+    fn execute_all(&mut self, stmts: &[Statements]) -> RunResult<Flow> {
+        for stmt in stmts {
+            match self.execute(stmt)? {
+                Flow::Normal => {}
+                flow => return Ok(flow),
+            }
+        }
+
+        Ok(Flow::Normal)
+    }
+
+    fn execute_block(&mut self, stmts: &[Statements]) -> RunResult<Flow> {
+        self.push_scope();
+        let flow = self.execute_all(stmts);
+        self.pop_scope();
+
+        flow
+    }
+
+    fn execute(&mut self, stmt: &Statements) -> RunResult<Flow> {
+        Ok(match stmt {
+            Statements::ExpressionStatement(e, _) => {
+                self.eval(e)?;
+
+                Flow::Normal
+            }
+            Statements::LetStatement(name, mutable, ty, expr, _) => {
+                let ty = get_real_type_of_argument(&mut self.st, ty, expr)?;
+                let value = self.eval(expr)?;
+
+                self.define(name, ty, *mutable, value);
+
+                Flow::Normal
+            }
+            Statements::ReturnStatement(e, _) => Flow::Return(self.eval(e)?),
+            Statements::IfStatement(condition, then, els, _) => {
+                if self.eval(condition)?.as_bool() {
+                    self.execute_block(then)?
+                } else {
+                    self.execute_block(els)?
+                }
+            }
+            Statements::WhileStatement(condition, body, _) => {
+                while self.eval(condition)?.as_bool() {
+                    match self.execute_block(body)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Normal | Flow::Continue => {}
+                    }
+                }
+
+                Flow::Normal
+            }
+            Statements::ForStatement(name, iterable, body, _) => {
+                self.push_scope();
+                let flow = self.for_loop(name, iterable, body);
+                self.pop_scope();
+
+                flow?
+            }
+            Statements::BreakStatement(_) => Flow::Break,
+            Statements::ContinueStatement(_) => Flow::Continue,
+            Statements::FunctionDeclaration(..) | Statements::ExternBlock(..) => {
+                unsupported("nested declarations", stmt.span())?
+            }
+            Statements::LetTupleStatement(..) => unsupported("tuples", stmt.span())?,
+            Statements::StructDeclaration(..) => unsupported("structs", stmt.span())?,
+            Statements::EnumDeclaration(..) => unsupported("enums", stmt.span())?,
+            Statements::ImplDeclaration(..) => unsupported("impls", stmt.span())?,
+            Statements::UseStatement(..) => unsupported("imports", stmt.span())?,
+            Statements::MatchStatement(..) => unsupported("`match`", stmt.span())?,
+        })
+    }
+
+    /// Runs a `for` loop in its own scope, the end of a range is evaluated
+    /// once like in the translated C++ loop
+    fn for_loop(&mut self, name: &str, iterable: &Expr, body: &[Statements]) -> RunResult<Flow> {
+        let values = match iterable {
+            Expr::Range(start, end) | Expr::RangeInclusive(start, end) => {
+                let inclusive = matches!(iterable, Expr::RangeInclusive(_, _));
+                let ty = common_type(&mut self.st, start, end)?;
+                let span = iterable.span();
+
+                let value = self.eval(start)?;
+                let end = self.eval(end)?.as_int();
+                self.define(name, ty.clone(), false, value);
+
+                loop {
+                    let i = self.lookup(name, span)?.as_int();
+
+                    if i > end || (i == end && !inclusive) {
+                        break;
+                    }
+
+                    match self.execute_block(body)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Normal | Flow::Continue => {}
+                    }
+
+                    // The counter would wrap past the largest value of its type
+                    if i == end {
+                        break;
+                    }
+
+                    let next = Value::Int(self.lookup(name, span)?.as_int() + 1).cast(&ty);
+                    self.update(name, next, span)?;
+                }
+
+                return Ok(Flow::Normal);
+            }
+            Expr::Address(seq) | Expr::MutAddress(seq) => seq,
+            seq => seq,
+        };
+        let by_mut_ref = matches!(iterable, Expr::MutAddress(_));
+
+        // `&arr[a..b]` iterates over the elements of `arr` from `a` to `b`
+        let (values, range) = match values {
+            Expr::ArrMember(arr, range) if is_range(range) => (arr.as_ref(), Some(range)),
+            values => (values, None),
+        };
+
+        let ty = match element_type(&values.resolve_type(&mut self.st)?) {
+            Some(ty) => ty,
+            None => unsupported("iterating over this value", iterable.span())?,
+        };
+
+        let Value::Array(values) = self.eval(values)? else {
+            unsupported("iterating over this value", iterable.span())?
+        };
+
+        let len = values.borrow().len();
+        let span = iterable.span();
+
+        let indices = match range.map(|r| r.as_ref()) {
+            Some(Expr::Range(start, end)) => self.eval(start)?.as_int()..self.eval(end)?.as_int(),
+            Some(Expr::RangeInclusive(start, end)) => {
+                self.eval(start)?.as_int()..self.eval(end)?.as_int() + 1
+            }
+            _ => 0..len as i128,
+        };
+
+        if indices.start < 0 || indices.start > indices.end || indices.end > len as i128 {
+            Err(Error::Runtime(
+                format!(
+                    "Slice {}..{} out of bounds for array of length {}",
+                    indices.start, indices.end, len
+                ),
+                span,
+            ))?
+        }
+
+        for i in indices.start as usize..indices.end as usize {
+            // The elements are copied, like `for (T x : arr)` in C++
+            let value = values.borrow()[i].clone();
+            self.define(name, ty.clone(), by_mut_ref, value);
+
+            let flow = self.execute_block(body)?;
+
+            // Elements borrowed with `&mut` get the value written to the binding
+            if by_mut_ref {
+                values.borrow_mut()[i] = self.lookup(name, span)?;
+            }
+
+            match flow {
+                Flow::Break => break,
+                Flow::Return(value) => return Ok(Flow::Return(value)),
+                Flow::Normal | Flow::Continue => {}
+            }
+        }
+
+        Ok(Flow::Normal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::interpret;
+
+    /// Exit code of the program
+    fn run(source: &str) -> i32 {
+        let ast = comfy_parser::parse(source).unwrap();
+
+        match interpret(&ast, "test.co") {
+            Ok(code) => code,
+            Err(errors) => panic!("interpretation failed: {:#?}", errors),
+        }
+    }
+
+    #[test]
+    fn range_takes_the_type_of_both_bounds() {
+        let code = run("fn main() -> i32 {
+            let n: i32 = 300;
+            let mut count: i32 = 0;
+            for i in 0..n { count = count + 1; }
+            return count;
+        }");
+
+        assert_eq!(code, 300);
+    }
+
+    #[test]
+    fn inclusive_range() {
+        let code = run("fn main() -> i32 {
+            let mut sum: i32 = 0;
+            for i in 1..=10 { sum = sum + i; }
+            return sum;
+        }");
+
+        assert_eq!(code, 55);
+    }
+
+    #[test]
+    fn inclusive_range_to_the_largest_value() {
+        let code = run("fn main() -> i32 {
+            let mut count: i32 = 0;
+            for i in 0..=255 { count = count + 1; }
+            return count;
+        }");
+
+        assert_eq!(code, 256);
+    }
+
+    #[test]
+    fn range_end_is_evaluated_once() {
+        let code = run("fn main() -> i32 {
+            let mut n: i32 = 3;
+            let mut count: i32 = 0;
+            for i in 0..n { n = n + 1; count = count + 1; }
+            return count;
+        }");
+
+        assert_eq!(code, 3);
+    }
+
+    #[test]
+    fn writes_through_mutable_elements() {
+        let code = run("fn main() -> i32 {
+            let mut arr: [i32; 3] = [1, 2, 3];
+            for x in &mut arr { x = x * 10; }
+            return arr[0] + arr[2];
+        }");
+
+        assert_eq!(code, 40);
+    }
+
+    #[test]
+    fn shared_elements_are_copies() {
+        let code = run("fn main() -> i32 {
+            let arr: [i32; 3] = [1, 2, 3];
+            let mut sum: i32 = 0;
+            for x in &arr { sum = sum + x; }
+            return sum;
+        }");
+
+        assert_eq!(code, 6);
+    }
+
+    #[test]
+    fn borrowed_subslices() {
+        let code = run("fn main() -> i32 {
+            let mut arr: [i32; 4] = [1, 2, 3, 4];
+            for x in &mut arr[1..3] { x = x * 10; }
+            let mut sum: i32 = 0;
+            for x in &arr[0..=2] { sum = sum + x; }
+            return sum + arr[3];
+        }");
+
+        assert_eq!(code, 55);
+    }
+
+    #[test]
+    fn array_len() {
+        let code = run("fn main() -> i32 {
+            let arr: [[u8; 2]; 5] = [[0; 2]; 5];
+            return (arr.len() * 10 + arr[0].len()) as i32;
+        }");
+
+        assert_eq!(code, 52);
+    }
+
+    #[test]
+    fn break_and_continue() {
+        let code = run("fn main() -> i32 {
+            let mut i: i32 = 0;
+            let mut odd: i32 = 0;
+            while true {
+                i = i + 1;
+                if i > 9 { break; } else { }
+                if i % 2 == 0 { continue; } else { }
+                odd = odd + 1;
+            }
+            return odd;
+        }");
+
+        assert_eq!(code, 5);
+    }
+
+    #[test]
+    fn printf_of_infinity() {
+        let code = run("extern \"C\" include \"<cstdio>\" {
+            fn printf(format: str, value: f64) -> i32;
+        }
+
+        fn main() -> i32 {
+            let zero: f64 = 0.0;
+            return printf(\"%e\\n\", 1.0 / zero);
+        }");
+
+        assert_eq!(code, "inf\n".len() as i32);
+    }
+}
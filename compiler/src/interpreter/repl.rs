@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+
+use chumsky::{input::Input, Parser};
+use comfy_parser::{lexer::tokens, statements};
+use comfy_types::{tokens::Kind, Ast, Statements, Type};
+
+use super::{Interpreter, Value};
+use crate::compiler::{structs::declare_impls, ComfyNode, Error, State};
+
+/// Whether `src` stops in the middle of a statement, like an unclosed block,
+/// and the REPL has to read more lines
+pub fn is_incomplete(src: &str) -> bool {
+    let tokens = match tokens().parse(src).into_result() {
+        Ok(tokens) => tokens,
+        Err(errs) => return errs.iter().any(|e| e.found().is_none()),
+    };
+
+    // Errors inside an unclosed block are not always reported at the end of the input
+    let depth = tokens.iter().fold(0, |depth, (kind, _)| match kind {
+        Kind::LParen | Kind::LSquare | Kind::LAngle => depth + 1,
+        Kind::RParen | Kind::RSquare | Kind::RAngle => depth - 1,
+        _ => depth,
+    });
+
+    let incomplete = statements()
+        .parse(tokens.as_slice().spanned((src.len()..src.len()).into()))
+        .into_result()
+        .is_err_and(|errs| depth > 0 || errs.iter().any(|e| e.found().is_none()));
+
+    incomplete
+}
+
+/// Session of `comfy repl`, functions and variables are kept between inputs
+pub struct Repl {
+    interpreter: Interpreter,
+    /// Translated C++ of the last input
+    cpp: String,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        let mut st = State::new();
+        st.set_input_file("repl");
+
+        Self {
+            interpreter: Interpreter {
+                st,
+                scopes: vec![HashMap::new()],
+                bodies: HashMap::new(),
+            },
+            cpp: String::new(),
+        }
+    }
+
+    pub fn cpp(&self) -> &str {
+        &self.cpp
+    }
+
+    /// Type-checks and runs one input, returns the value and type of every
+    /// expression statement in it. Nothing is kept from an input that does
+    /// not type-check
+    pub fn eval(&mut self, ast: &Ast) -> Result<Vec<(Value, Type)>, Vec<Error>> {
+        let st = &mut self.interpreter.st;
+        let saved = st.clone();
+
+        let mut code = vec![];
+        let mut types = vec![];
+
+        if let Err(e) = declare_impls(st, ast) {
+            st.errors.push(e);
+        }
+
+        for stmt in ast {
+            let cpp = match stmt {
+                Statements::ExpressionStatement(e, _) | Statements::ReturnStatement(e, _) => {
+                    e.to_cpp(st).and_then(|cpp| {
+                        types.push(e.resolve_type(st)?);
+
+                        Ok(format!("{};", cpp))
+                    })
+                }
+                stmt => stmt.to_cpp(st),
+            };
+
+            match cpp {
+                Ok(cpp) => code.push(cpp),
+                Err(e) => st.errors.push(e),
+            }
+        }
+
+        if !st.errors.is_empty() {
+            return Err(std::mem::replace(st, saved).errors);
+        }
+
+        self.cpp = code.join("\n");
+
+        let mut types = types.into_iter();
+        let mut values = vec![];
+
+        for stmt in ast {
+            match stmt {
+                Statements::FunctionDeclaration(_, name, _, _, _, body, _) => {
+                    self.interpreter
+                        .bodies
+                        .insert(name.clone(), body.clone().into());
+                }
+                // Signatures of extern functions are already registered in the state
+                Statements::ExternBlock(..) => {}
+                Statements::ExpressionStatement(e, _) | Statements::ReturnStatement(e, _) => {
+                    let value = self.interpreter.eval(e).map_err(|e| vec![e])?;
+
+                    values.push((value, types.next().unwrap()));
+                }
+                stmt => {
+                    self.interpreter.execute(stmt).map_err(|e| vec![e])?;
+                }
+            }
+        }
+
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_incomplete, Repl};
+
+    #[test]
+    fn mutable_bindings_between_inputs() {
+        let mut repl = Repl::new();
+
+        for input in ["let mut y: i32 = 3;", "y = y + 1;"] {
+            let ast = comfy_parser::parse(input).unwrap();
+            repl.eval(&ast).unwrap();
+        }
+
+        let ast = comfy_parser::parse("y;").unwrap();
+        let values = repl.eval(&ast).unwrap();
+        assert_eq!(values[0].0.as_int(), 4);
+    }
+
+    #[test]
+    fn immutable_bindings_between_inputs() {
+        let mut repl = Repl::new();
+
+        let ast = comfy_parser::parse("let y: i32 = 3;").unwrap();
+        repl.eval(&ast).unwrap();
+
+        let ast = comfy_parser::parse("y = y + 1;").unwrap();
+        assert!(repl.eval(&ast).is_err());
+    }
+
+    /// Values and types of the expressions in `input`, like the REPL prints them
+    fn eval(repl: &mut Repl, input: &str) -> Vec<String> {
+        let ast = comfy_parser::parse(input).unwrap();
+
+        match repl.eval(&ast) {
+            Ok(values) => values
+                .into_iter()
+                .map(|(value, ty)| format!("{}: {}", value, ty))
+                .collect(),
+            Err(errors) => panic!("evaluation failed: {:#?}", errors),
+        }
+    }
+
+    #[test]
+    fn expressions_print_their_type() {
+        let mut repl = Repl::new();
+
+        assert_eq!(eval(&mut repl, "1 + 2;"), ["3: u8"]);
+        assert_eq!(eval(&mut repl, "[1, 2][1] as i32 * 300;"), ["600: i32"]);
+        assert_eq!(eval(&mut repl, "true && false;"), ["false: bool"]);
+    }
+
+    #[test]
+    fn functions_between_inputs() {
+        let mut repl = Repl::new();
+
+        eval(&mut repl, "fn twice(x: i32) -> i32 { return x * 2; }");
+        eval(&mut repl, "let a: i32 = twice(4);");
+
+        assert_eq!(eval(&mut repl, "twice(a);"), ["16: i32"]);
+        assert_eq!(repl.cpp(), "twice(a);");
+    }
+
+    #[test]
+    fn failed_inputs_are_dropped() {
+        let mut repl = Repl::new();
+
+        let ast = comfy_parser::parse("let a: i32 = 1; let b: bool = 2;").unwrap();
+        assert!(repl.eval(&ast).is_err());
+
+        // `a` was not kept either
+        let ast = comfy_parser::parse("a;").unwrap();
+        assert!(repl.eval(&ast).is_err());
+    }
+
+    #[test]
+    fn incomplete_inputs() {
+        assert!(is_incomplete("fn f() -> i32 {"));
+        assert!(is_incomplete("let a: [i32; 2] = [1,"));
+        assert!(is_incomplete("if true {\n  1;\n"));
+        assert!(!is_incomplete("let a: i32 = 1;"));
+        assert!(!is_incomplete("fn f() -> i32 { return 1; }"));
+    }
+}
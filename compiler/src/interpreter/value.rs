@@ -0,0 +1,121 @@
+use std::{cell::RefCell, rc::Rc};
+
+use comfy_types::Type;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Void,
+    Bool(bool),
+    /// Integers of every width, wrapped to their type by [`Value::cast`]
+    Int(i128),
+    Float(f64),
+    Char(char),
+    Str(String),
+    /// Shared by slices and `&mut` borrows, [`Value::cast`] copies arrays into
+    /// the variables, arguments and return values of array type
+    Array(Rc<RefCell<Vec<Value>>>),
+}
+
+impl Value {
+    pub fn array(values: Vec<Value>) -> Self {
+        Value::Array(Rc::new(RefCell::new(values)))
+    }
+
+    pub fn as_bool(&self) -> bool {
+        match self {
+            Value::Bool(v) => *v,
+            Value::Int(v) => *v != 0,
+            Value::Float(v) => *v != 0.0,
+            Value::Char(v) => *v != '\0',
+            _ => true,
+        }
+    }
+
+    pub fn as_int(&self) -> i128 {
+        match self {
+            Value::Bool(v) => *v as i128,
+            Value::Int(v) => *v,
+            Value::Float(v) => *v as i128,
+            Value::Char(v) => *v as i128,
+            _ => 0,
+        }
+    }
+
+    pub fn as_float(&self) -> f64 {
+        match self {
+            Value::Float(v) => *v,
+            v => v.as_int() as f64,
+        }
+    }
+
+    /// Converts the value the way C++ converts it to `ty`
+    pub fn cast(self, ty: &Type) -> Value {
+        match ty {
+            Type::Bool(_) => Value::Bool(self.as_bool()),
+            Type::I8(_) => Value::Int(self.as_int() as i8 as i128),
+            Type::I16(_) => Value::Int(self.as_int() as i16 as i128),
+            Type::I32(_) | Type::Int(_) => Value::Int(self.as_int() as i32 as i128),
+            Type::I64(_) => Value::Int(self.as_int() as i64 as i128),
+            Type::U8(_) => Value::Int(self.as_int() as u8 as i128),
+            Type::U16(_) => Value::Int(self.as_int() as u16 as i128),
+            Type::U32(_) | Type::Uint(_) => Value::Int(self.as_int() as u32 as i128),
+            Type::U64(_) => Value::Int(self.as_int() as u64 as i128),
+            Type::F32(_) => Value::Float(self.as_float() as f32 as f64),
+            Type::F64(_) => Value::Float(self.as_float()),
+            Type::Char(_) => Value::Char(char::from_u32(self.as_int() as u32).unwrap_or('\0')),
+            Type::Void(_) => Value::Void,
+            Type::Array(ty, size, _) => match &self {
+                Value::Array(values) => {
+                    let mut values = values
+                        .borrow()
+                        .iter()
+                        .map(|value| value.clone().cast(ty))
+                        .collect::<Vec<_>>();
+
+                    // Missing elements are zero-initialized
+                    while (values.len() as u64) < *size {
+                        values.push(Value::Int(0).cast(ty));
+                    }
+
+                    Value::array(values)
+                }
+                _ => self,
+            },
+            _ => self,
+        }
+    }
+}
+
+/// Type of an arithmetic result after the integer promotions of C++,
+/// integers narrower than `int` are computed as `int`
+pub fn promoted(ty: Type) -> Type {
+    match ty {
+        Type::Bool(s) | Type::Char(s) | Type::I8(s) | Type::I16(s) | Type::U8(s) | Type::U16(s) => {
+            Type::I32(s)
+        }
+        ty => ty,
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Void => write!(f, "void"),
+            Value::Bool(v) => write!(f, "{}", v),
+            Value::Int(v) => write!(f, "{}", v),
+            Value::Float(v) => write!(f, "{}", v),
+            Value::Char(v) => write!(f, "{}", v),
+            Value::Str(v) => write!(f, "{}", v),
+            Value::Array(values) => write!(
+                f,
+                "[{}]",
+                values
+                    .borrow()
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
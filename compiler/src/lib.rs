@@ -0,0 +1,6 @@
+pub mod cli;
+pub mod compiler;
+pub mod formatter;
+pub mod interpreter;
+
+pub use compiler::{build, compile_to_cpp, CppOutput, Options};
@@ -1,68 +1,282 @@
-use std::fs;
+use std::{
+    borrow::Cow,
+    env, fs,
+    io::{self, Write},
+    process,
+    time::Instant,
+};
 
-use ariadne::{Color, Label, Report, ReportKind, Source};
-
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
 use colored::*;
-use comfy_parser::parse;
+use comfy_compiler::{
+    build,
+    cli::{json_message, lints, Args, Command, MessageFormat},
+    compiler::{translate, Error},
+    formatter::format,
+    interpreter::{interpret, is_incomplete, Repl},
+    Options,
+};
+use comfy_parser::{parse, report};
+use comfy_types::{Ast, Diagnostic, Type};
 use comfy_utils::inc_indent;
-use compiler::{Compiler, Error};
 
-mod cli;
-mod compiler;
+/// Prints the diagnostics of `src_file` as reports or as one JSON object per line
+fn emit(
+    diagnostics: impl IntoIterator<Item = Diagnostic>,
+    src_file: &str,
+    src: &str,
+    format: MessageFormat,
+) {
+    for diagnostic in diagnostics {
+        // Errors of imported modules point into their own file
+        let (file, src) = match &diagnostic.file {
+            Some(file) => (
+                file.as_str(),
+                Cow::Owned(fs::read_to_string(file).unwrap_or_default()),
+            ),
+            None => (src_file, Cow::Borrowed(src)),
+        };
+
+        match format {
+            MessageFormat::Human => report(&diagnostic, file, &src),
+            MessageFormat::Json => println!("{}", json_message(&diagnostic, file, &src)),
+        }
+    }
+}
+
+fn diagnostics(errors: Vec<Error>) -> Vec<Diagnostic> {
+    errors.into_iter().map(Diagnostic::from).collect()
+}
+
+fn emit_errors(errors: Vec<Error>, src_file: &str, src: &str, format: MessageFormat) {
+    emit(diagnostics(errors), src_file, src, format)
+}
+
+/// `comfy run`, compiles the program to a temporary binary or interprets it,
+/// exits with the exit code of the program
+fn run(src: &str, ast: Ast, options: &Options, interpreted: bool, format: MessageFormat) -> ! {
+    let src_file = options.input_file.as_str();
+
+    let code = if interpreted {
+        interpret(&ast, src_file).map_err(diagnostics)
+    } else {
+        let output_file = env::temp_dir()
+            .join(format!("comfy-{}", process::id()))
+            .display()
+            .to_string();
+
+        translate(&ast, options)
+            .and_then(|cpp| {
+                emit(cpp.warnings.clone(), src_file, src, format);
+
+                build(&cpp, &output_file)
+            })
+            .map(|_| {
+                let status = process::Command::new(&output_file)
+                    .status()
+                    .expect("Could not run the program");
+
+                fs::remove_file(&output_file).ok();
+
+                status.code().unwrap_or(1)
+            })
+    };
+
+    match code {
+        Ok(code) => process::exit(code),
+        Err(e) => {
+            emit(e, src_file, src, format);
+            process::exit(1)
+        }
+    }
+}
+
+/// `comfy fmt`, formats the files in place or only checks them,
+/// exits with 1 if a file does not parse or is not formatted
+fn fmt(input_files: &[String], check: bool, message_format: MessageFormat) -> ! {
+    let mut failed = false;
+
+    for src_file in input_files {
+        let src = fs::read_to_string(src_file).expect("Could not read file");
+
+        let ast = match parse(&src) {
+            Ok(ast) => ast,
+            Err(diagnostics) => {
+                emit(diagnostics, src_file, &src, message_format);
+                failed = true;
+                continue;
+            }
+        };
+
+        let formatted = format(&ast, &src);
+
+        // The output is only written if it parses again
+        let parses = parse(&formatted).is_ok();
+
+        if !parses {
+            eprintln!(
+                "{} Could not format {}, the output does not parse",
+                "[Formatter]".red().bold(),
+                src_file
+            );
+            failed = true;
+        } else if formatted != src {
+            if check {
+                println!("{} {}", "Not formatted:".yellow().bold(), src_file);
+                failed = true;
+            } else {
+                fs::write(src_file, formatted).expect("Could not write file");
+            }
+        }
+    }
+
+    process::exit(if failed { 1 } else { 0 })
+}
+
+/// `comfy repl`, evaluates statements line by line until `:quit` or the end of the input
+fn repl() -> ! {
+    const SRC_FILE: &str = "<repl>";
+
+    let mut session = Repl::new();
+    let mut src = String::new();
+
+    println!("Comfy REPL, `:cpp` shows the C++ of the last input, `:quit` exits");
+
+    loop {
+        print!("{}", if src.is_empty() { ">> " } else { ".. " });
+        io::stdout().flush().ok();
+
+        let mut line = String::new();
+
+        if io::stdin().read_line(&mut line).unwrap_or(0) == 0 {
+            println!();
+            process::exit(0);
+        }
+
+        if src.is_empty() {
+            match line.trim() {
+                "" => continue,
+                ":quit" | ":q" => process::exit(0),
+                ":cpp" => {
+                    println!("{}", session.cpp());
+                    continue;
+                }
+                command if command.starts_with(':') => {
+                    eprintln!("{} {}", "Unknown command:".red().bold(), command);
+                    continue;
+                }
+                _ => {}
+            }
+        }
+
+        src.push_str(&line);
+
+        // Blocks continue on the next lines until they are closed
+        if is_incomplete(&src) {
+            continue;
+        }
+
+        let input = std::mem::take(&mut src);
+
+        let ast = match parse(&input) {
+            Ok(ast) => ast,
+            Err(diagnostics) => {
+                emit(diagnostics, SRC_FILE, &input, MessageFormat::Human);
+                continue;
+            }
+        };
+
+        match session.eval(&ast) {
+            Ok(values) => {
+                for (value, ty) in values {
+                    if !matches!(ty, Type::Void(_)) {
+                        println!("{}: {}", value, ty.to_string().cyan());
+                    }
+                }
+            }
+            Err(e) => emit_errors(e, SRC_FILE, &input, MessageFormat::Human),
+        }
+    }
+}
 
 fn main() {
-    let args = cli::Args::parse();
+    // The matches are kept for the order of the lint levels
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
 
-    let src_file = &args.input_file.clone();
+    // Messages of clang++ are colored, JSON output must not contain escape codes
+    if args.message_format == MessageFormat::Json {
+        colored::control::set_override(false);
+    }
+
+    let src_file = &match &args.command {
+        Some(Command::Repl) => repl(),
+        Some(Command::Fmt { input_files, check }) => fmt(input_files, *check, args.message_format),
+        Some(Command::Run { input_file, .. }) => input_file.clone(),
+        None => args.input_file.clone().unwrap_or_default(),
+    };
     let src = fs::read_to_string(src_file).expect("Could not read file");
 
-    let ast = parse(src_file, src.clone());
-
-    if let Ok(ast) = ast {
-        let mut compiler = Compiler::new(ast);
-
-        let compiled = compiler.compile(args.clone());
-
-        match compiled {
-            Ok(compiled) => {
-                if args.verbose {
-                    println!(
-                        "\n{}\n \n\n{:#?}\n",
-                        "State:".bold().green(),
-                        compiled.state
-                    );
-                    println!(
-                        "\n{} \n\n{}\n",
-                        "Translated code:".bold().green(),
-                        inc_indent(compiled.code)
-                    );
-                    println!(
-                        "{} {}s",
-                        "Translation time:".bold().green(),
-                        compiled.translation_time.as_secs_f64()
-                    );
-                    println!(
-                        "{} {}s",
-                        "Compilation time:".bold().green(),
-                        compiled.compile_time.as_secs_f64()
-                    );
-                }
+    let ast = match parse(&src) {
+        Ok(ast) => ast,
+        Err(diagnostics) => {
+            emit(diagnostics, src_file, &src, args.message_format);
+            process::exit(1)
+        }
+    };
+
+    let options = Options {
+        input_file: src_file.clone(),
+        lints: lints(&matches),
+    };
+
+    if let Some(Command::Run { interpret, .. }) = args.command {
+        run(&src, ast, &options, interpret, args.message_format)
+    }
+
+    let output_file = args.output_file.as_deref().unwrap_or("a.out");
+
+    let compiled = translate(&ast, &options).and_then(|cpp| {
+        emit(cpp.warnings.clone(), src_file, &src, args.message_format);
+
+        let compile_time = Instant::now();
+
+        if !args.dry_run {
+            build(&cpp, output_file)?;
+        }
+
+        Ok((cpp, compile_time.elapsed()))
+    });
+
+    match compiled {
+        Ok((compiled, compile_time)) => {
+            if args.verbose {
+                println!(
+                    "\n{}\n \n\n{:#?}\n",
+                    "State:".bold().green(),
+                    compiled.state
+                );
+                println!(
+                    "\n{} \n\n{}\n",
+                    "Translated code:".bold().green(),
+                    inc_indent(compiled.code)
+                );
+                println!(
+                    "{} {}s",
+                    "Translation time:".bold().green(),
+                    compiled.translation_time.as_secs_f64()
+                );
+                println!(
+                    "{} {}s",
+                    "Compilation time:".bold().green(),
+                    compile_time.as_secs_f64()
+                );
             }
-            Err(e) => e.into_iter().for_each(|e| match e {
-                Error::Compile(msg, s) => Report::build(ReportKind::Error, src_file, s.start)
-                    .with_code("Compiler")
-                    .with_message(msg.clone())
-                    .with_label(
-                        Label::new((src_file, s.into_range()))
-                            .with_message(msg.to_string())
-                            .with_color(Color::Red),
-                    )
-                    .finish()
-                    .print((src_file, Source::from(&src)))
-                    .unwrap(),
-                Error::Clang(msg) => eprintln!("{} {}", "[Clang]".red().bold(), msg.bold()),
-            }),
+        }
+        // Denied lints are reported as errors too
+        Err(e) => {
+            emit(e, src_file, &src, args.message_format);
+            process::exit(1)
         }
     }
 }
@@ -0,0 +1,305 @@
+use chumsky::{span::SimpleSpan, Parser};
+use comfy_compiler::compiler::{ComfyNode, Ident, IdentValue, Scopes, State};
+use comfy_parser::{lexer::tokens, parse};
+use comfy_types::{
+    tokens::Kind, Diagnostic, ExternFunction, MatchArm, Pattern, Severity, Statements,
+};
+
+/// A function, type or variable and the part of the file it is visible in
+struct Declaration {
+    name: String,
+    span: SimpleSpan,
+    scope: SimpleSpan,
+    /// Visible before its declaration, like top-level items
+    hoisted: bool,
+}
+
+pub struct Analysis {
+    /// Scopes of the translator after every statement
+    snapshots: Vec<(SimpleSpan, Scopes)>,
+    declarations: Vec<Declaration>,
+}
+
+/// Lexes, parses and translates `text`, returns the analysis if it parses and the errors
+pub fn analyze(path: &str, text: &str) -> (Option<Analysis>, Vec<Diagnostic>) {
+    let ast = match parse(text) {
+        Ok(ast) => ast,
+        Err(diagnostics) => return (None, diagnostics),
+    };
+
+    let mut st = State::new();
+    st.set_input_file(path);
+    st.snapshots = Some(vec![]);
+
+    let result = ast.to_cpp(&mut st);
+
+    if result.is_ok() {
+        st.report_unused_items();
+    }
+
+    let mut errors = std::mem::take(&mut st.errors);
+
+    if let Err(e) = result {
+        errors.push(e);
+    }
+
+    let warnings = st.warnings.drain(..).map(|e| Diagnostic {
+        severity: Severity::Warning,
+        ..Diagnostic::from(e)
+    });
+
+    let mut declarations = vec![];
+    collect_declarations(
+        &ast,
+        SimpleSpan::new(0, text.len()),
+        true,
+        &mut declarations,
+    );
+
+    (
+        Some(Analysis {
+            snapshots: st.snapshots.unwrap_or_default(),
+            declarations,
+        }),
+        errors
+            .into_iter()
+            .map(Diagnostic::from)
+            .chain(warnings)
+            .collect(),
+    )
+}
+
+fn collect_patterns(pattern: &Pattern, scope: SimpleSpan, out: &mut Vec<Declaration>) {
+    match pattern {
+        Pattern::Binding(name, s) => out.push(Declaration {
+            name: name.clone(),
+            span: *s,
+            scope,
+            hoisted: false,
+        }),
+        Pattern::Variant(_, _, patterns, _) => {
+            for pattern in patterns {
+                collect_patterns(pattern, scope, out);
+            }
+        }
+        Pattern::Wildcard(_) | Pattern::Literal(_) => {}
+    }
+}
+
+/// Collects the declarations of `stmts`, which are visible until the end of `parent`
+fn collect_declarations(
+    stmts: &[Statements],
+    parent: SimpleSpan,
+    top_level: bool,
+    out: &mut Vec<Declaration>,
+) {
+    let mut declare = |name: &str, span: SimpleSpan, scope: SimpleSpan, hoisted: bool| {
+        out.push(Declaration {
+            name: name.to_owned(),
+            span,
+            scope,
+            hoisted,
+        })
+    };
+
+    let mut nested = vec![];
+
+    for stmt in stmts {
+        let rest = SimpleSpan::new(stmt.span().start, parent.end);
+
+        match stmt {
+            Statements::FunctionDeclaration(_, name, _, args, _, body, s) => {
+                declare(name, *s, parent, top_level);
+
+                for arg in args {
+                    declare(&arg.0, arg.3, *s, false);
+                }
+
+                nested.push((body, *s));
+            }
+            Statements::StructDeclaration(_, name, _, _, s)
+            | Statements::EnumDeclaration(_, name, _, s) => declare(name, *s, parent, top_level),
+            Statements::ExternBlock(_, _, functions, _) => {
+                for ExternFunction(name, _, _, s) in functions {
+                    declare(name, *s, parent, top_level);
+                }
+            }
+            Statements::UseStatement(_, items, s) => {
+                for item in items {
+                    declare(item, *s, parent, top_level);
+                }
+            }
+            Statements::LetStatement(name, _, _, _, s) => declare(name, *s, rest, false),
+            Statements::LetTupleStatement(names, _, _, s) => {
+                for name in names {
+                    declare(name, *s, rest, false);
+                }
+            }
+            Statements::ForStatement(name, _, body, s) => {
+                declare(name, *s, *s, false);
+                nested.push((body, *s));
+            }
+            Statements::ImplDeclaration(_, methods, s) => nested.push((methods, *s)),
+            Statements::IfStatement(_, then, els, s) => {
+                nested.push((then, *s));
+                nested.push((els, *s));
+            }
+            Statements::WhileStatement(_, body, s) => nested.push((body, *s)),
+            Statements::MatchStatement(_, arms, _) => {
+                for MatchArm(pattern, body, s) in arms {
+                    let mut bindings = vec![];
+                    collect_patterns(pattern, *s, &mut bindings);
+
+                    for binding in bindings {
+                        declare(&binding.name, binding.span, binding.scope, false);
+                    }
+
+                    nested.push((body, *s));
+                }
+            }
+            Statements::ExpressionStatement(..)
+            | Statements::ReturnStatement(..)
+            | Statements::BreakStatement(_)
+            | Statements::ContinueStatement(_) => {}
+        }
+    }
+
+    for (body, s) in nested {
+        collect_declarations(body, s, false, out);
+    }
+}
+
+/// Name of the identifier at `offset` and its span
+pub fn ident_at(text: &str, offset: usize) -> Option<(String, SimpleSpan)> {
+    tokens()
+        .parse(text)
+        .into_result()
+        .ok()?
+        .into_iter()
+        .find_map(|(kind, s)| match kind {
+            Kind::Ident(name) if s.start <= offset && offset <= s.end => Some((name, s)),
+            _ => None,
+        })
+}
+
+/// Signature of an identifier as shown on hover
+pub fn describe(name: &str, ident: &Ident) -> String {
+    let generics = |generics: &[String]| match generics.len() {
+        0 => "".to_owned(),
+        _ => format!("<{}>", generics.join(", ")),
+    };
+
+    match &ident.value {
+        IdentValue::Func(g, args) => format!(
+            "fn {}{}({}) -> {}",
+            name,
+            generics(g),
+            args.iter()
+                .map(|a| format!("{}: {}", a.0, a.1))
+                .collect::<Vec<_>>()
+                .join(", "),
+            ident.return_type
+        ),
+        IdentValue::Struct(g, _) => format!("struct {}{}", name, generics(g)),
+        IdentValue::Enum(_) => format!("enum {}", name),
+        IdentValue::TypeParam => format!("type {}", name),
+        IdentValue::Variable => format!("{}: {}", name, ident.return_type),
+    }
+}
+
+impl Analysis {
+    /// Scopes after the innermost statement around `offset`, or after the last statement before it
+    pub fn scopes_at(&self, offset: usize) -> Option<&Scopes> {
+        self.snapshots
+            .iter()
+            .filter(|(s, _)| s.start <= offset && offset <= s.end)
+            .min_by_key(|(s, _)| s.end - s.start)
+            .or_else(|| {
+                self.snapshots
+                    .iter()
+                    .filter(|(s, _)| s.end <= offset)
+                    .max_by_key(|(s, _)| s.end)
+            })
+            .map(|(_, scopes)| scopes)
+    }
+
+    pub fn lookup(&self, name: &str, offset: usize) -> Option<&Ident> {
+        self.scopes_at(offset)?
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+    }
+
+    /// Span of the innermost declaration of `name` visible at `offset`
+    pub fn definition(&self, name: &str, offset: usize) -> Option<SimpleSpan> {
+        self.declarations
+            .iter()
+            .filter(|d| {
+                d.name == name
+                    && d.scope.start <= offset
+                    && offset <= d.scope.end
+                    && (d.hoisted || d.span.start <= offset)
+            })
+            .min_by_key(|d| (d.scope.end - d.scope.start, usize::MAX - d.span.start))
+            .map(|d| d.span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use comfy_types::Severity;
+
+    use super::analyze;
+
+    fn errors(text: &str) -> Vec<String> {
+        analyze("test.co", text)
+            .1
+            .into_iter()
+            .filter(|d| d.severity == Severity::Error)
+            .map(|d| d.message)
+            .collect()
+    }
+
+    #[test]
+    fn undefined_names() {
+        assert_eq!(
+            errors("fn main() -> i32 { let y: i32 = zz; return y; }"),
+            ["Unknown identifier: zz"]
+        );
+        assert_eq!(
+            errors("fn main() -> i32 { return c; }"),
+            ["Unknown identifier: c"]
+        );
+    }
+
+    #[test]
+    fn empty_array() {
+        assert!(errors("fn main() -> i32 { let a: [i32; 0] = []; return 0; }").is_empty());
+        assert_eq!(
+            errors("fn main() -> i32 { let a = []; return 0; }"),
+            ["Type can't be inferred, you need to specify it"]
+        );
+    }
+
+    #[test]
+    fn hover_and_definition() {
+        let text = "fn main() -> i32 { let value: i64 = 2; return value as i32; }";
+        let (analysis, diagnostics) = analyze("test.co", text);
+        let analysis = analysis.unwrap();
+        let offset = text.rfind("value").unwrap();
+
+        assert!(diagnostics.is_empty());
+        assert_eq!(
+            analysis
+                .lookup("value", offset)
+                .unwrap()
+                .return_type
+                .to_string(),
+            "i64"
+        );
+        assert_eq!(
+            analysis.definition("value", offset).unwrap().start,
+            text.find("let").unwrap()
+        );
+    }
+}
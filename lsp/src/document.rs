@@ -0,0 +1,119 @@
+use chumsky::span::SimpleSpan;
+use comfy_types::Diagnostic;
+use serde_json::{json, Value};
+
+use crate::analysis::{analyze, Analysis};
+
+/// Path of a `file://` URI, imports are resolved relative to it
+fn uri_to_path(uri: &str) -> String {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    let raw = path.as_bytes();
+    let mut bytes = vec![];
+    let mut i = 0;
+
+    while i < raw.len() {
+        let escaped = match raw[i] {
+            b'%' => path
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+
+        match escaped {
+            Some(b) => {
+                bytes.push(b);
+                i += 3;
+            }
+            None => {
+                bytes.push(raw[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+pub struct Document {
+    pub uri: String,
+    pub text: String,
+    /// Byte offsets of the line starts
+    lines: Vec<usize>,
+    /// Analysis of the last version that parsed, kept while the text is being edited
+    pub analysis: Option<Analysis>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Document {
+    pub fn new(uri: String, text: String) -> Self {
+        let mut document = Self {
+            uri,
+            text: "".to_owned(),
+            lines: vec![0],
+            analysis: None,
+            diagnostics: vec![],
+        };
+
+        document.update(text);
+
+        document
+    }
+
+    pub fn update(&mut self, text: String) {
+        let (analysis, diagnostics) = analyze(&uri_to_path(&self.uri), &text);
+
+        if analysis.is_some() {
+            self.analysis = analysis;
+        }
+
+        self.diagnostics = diagnostics;
+        self.lines = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        self.text = text;
+    }
+
+    /// LSP position of a byte offset, characters are counted in UTF-16 code units
+    pub fn position(&self, offset: usize) -> Value {
+        let offset = offset.min(self.text.len());
+        let line = self.lines.partition_point(|start| *start <= offset) - 1;
+        let start = self.lines[line];
+
+        let character = self
+            .text
+            .get(start..offset)
+            .map(|s| s.chars().map(char::len_utf16).sum::<usize>())
+            .unwrap_or(0);
+
+        json!({ "line": line, "character": character })
+    }
+
+    /// Byte offset of an LSP position
+    pub fn offset(&self, position: &Value) -> usize {
+        let line = position["line"].as_u64().unwrap_or(0) as usize;
+        let character = position["character"].as_u64().unwrap_or(0) as usize;
+
+        let Some(start) = self.lines.get(line).copied() else {
+            return self.text.len();
+        };
+
+        let mut units = 0;
+
+        for (i, c) in self.text[start..].char_indices() {
+            if units >= character || c == '\n' {
+                return start + i;
+            }
+
+            units += c.len_utf16();
+        }
+
+        self.text.len()
+    }
+
+    pub fn range(&self, span: SimpleSpan) -> Value {
+        json!({
+            "start": self.position(span.start),
+            "end": self.position(span.end),
+        })
+    }
+}
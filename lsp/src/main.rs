@@ -0,0 +1,270 @@
+use std::{
+    collections::HashMap,
+    io::{self, Stdout},
+    process,
+};
+
+use analysis::describe;
+use chumsky::span::SimpleSpan;
+use comfy_compiler::compiler::{Ident, IdentValue};
+use comfy_types::Severity;
+use document::Document;
+use serde_json::{json, Value};
+
+mod analysis;
+mod document;
+mod rpc;
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+type RequestResult = Result<Value, (i64, String)>;
+
+struct Server {
+    output: Stdout,
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+}
+
+impl Server {
+    fn send(&mut self, message: Value) {
+        if let Err(e) = rpc::write_message(&mut self.output, &message) {
+            eprintln!("[comfy-lsp] Could not send message: {}", e);
+        }
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str) {
+        let diagnostics = match self.documents.get(uri) {
+            Some(document) => document
+                .diagnostics
+                .iter()
+                .map(|d| {
+                    // Errors of imported modules point into other files
+                    let (message, span) = match &d.file {
+                        Some(file) => (format!("{} (in {})", d.message, file), None),
+                        None => (d.message.clone(), d.span),
+                    };
+
+                    json!({
+                        "range": document.range(span.unwrap_or(SimpleSpan::new(0, 0))),
+                        "severity": match d.severity {
+                            Severity::Error => 1,
+                            Severity::Warning => 2,
+                        },
+                        "source": "comfy",
+                        "code": d.code,
+                        "message": message,
+                    })
+                })
+                .collect(),
+            None => vec![],
+        };
+
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        }));
+    }
+
+    /// Document and byte offset of `textDocument/*` position params
+    fn locate(&self, params: &Value) -> Result<(&Document, usize), (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+        let document = self
+            .documents
+            .get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("Unknown document: {}", uri)))?;
+
+        Ok((document, document.offset(&params["position"])))
+    }
+
+    fn hover(&self, params: &Value) -> RequestResult {
+        let (document, offset) = self.locate(params)?;
+
+        let hover = analysis_ident(document, offset).map(|(name, s, ident)| {
+            json!({
+                "contents": {
+                    "kind": "markdown",
+                    "value": format!("```comfy\n{}\n```", describe(&name, ident)),
+                },
+                "range": document.range(s),
+            })
+        });
+
+        Ok(hover.unwrap_or(Value::Null))
+    }
+
+    fn definition(&self, params: &Value) -> RequestResult {
+        let (document, offset) = self.locate(params)?;
+
+        let location = analysis::ident_at(&document.text, offset).and_then(|(name, _)| {
+            let span = document.analysis.as_ref()?.definition(&name, offset)?;
+
+            Some(json!({ "uri": document.uri, "range": document.range(span) }))
+        });
+
+        Ok(location.unwrap_or(Value::Null))
+    }
+
+    fn completion(&self, params: &Value) -> RequestResult {
+        let (document, offset) = self.locate(params)?;
+
+        let Some(scopes) = document
+            .analysis
+            .as_ref()
+            .and_then(|analysis| analysis.scopes_at(offset))
+        else {
+            return Ok(json!([]));
+        };
+
+        let mut items: Vec<Value> = vec![];
+        let mut seen = vec![];
+
+        // Inner scopes shadow outer ones
+        for scope in scopes.iter().rev() {
+            for (name, ident) in scope {
+                if seen.contains(&name) {
+                    continue;
+                }
+
+                seen.push(name);
+
+                let kind = match ident.value {
+                    IdentValue::Func(_, _) => 3,
+                    IdentValue::Struct(_, _) => 22,
+                    IdentValue::Enum(_) => 13,
+                    IdentValue::TypeParam => 25,
+                    IdentValue::Variable => 6,
+                };
+
+                items.push(json!({
+                    "label": name,
+                    "kind": kind,
+                    "detail": describe(name, ident),
+                }));
+            }
+        }
+
+        Ok(Value::Array(items))
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> RequestResult {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    // Full text on every change
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "completionProvider": {},
+                },
+                "serverInfo": { "name": "comfy-lsp", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => {
+                self.shutdown = true;
+
+                Ok(Value::Null)
+            }
+            "textDocument/hover" => self.hover(params),
+            "textDocument/definition" => self.definition(params),
+            "textDocument/completion" => self.completion(params),
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method: {}", method))),
+        }
+    }
+
+    /// Handles a notification, returns the exit code once the client asks to exit
+    fn notification(&mut self, method: &str, params: &Value) -> Option<i32> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_owned();
+
+        match method {
+            "exit" => return Some(if self.shutdown { 0 } else { 1 }),
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+
+                self.documents
+                    .insert(uri.clone(), Document::new(uri.clone(), text.to_owned()));
+                self.publish_diagnostics(&uri);
+            }
+            "textDocument/didChange" => {
+                let text = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+
+                if let (Some(document), Some(text)) = (self.documents.get_mut(&uri), text) {
+                    document.update(text.to_owned());
+                }
+
+                self.publish_diagnostics(&uri);
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                self.publish_diagnostics(&uri);
+            }
+            _ => {}
+        }
+
+        None
+    }
+
+    fn handle(&mut self, message: Value) -> Option<i32> {
+        // The server sends no requests, so there are no responses to handle
+        let method = message["method"].as_str()?;
+        let params = &message["params"];
+
+        match message.get("id") {
+            Some(id) => {
+                let response = match self.request(method, params) {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err((code, msg)) => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": code, "message": msg },
+                    }),
+                };
+
+                self.send(response);
+
+                None
+            }
+            None => self.notification(method, params),
+        }
+    }
+}
+
+/// Identifier at `offset` and what it resolves to in the last analysis
+fn analysis_ident(document: &Document, offset: usize) -> Option<(String, SimpleSpan, &Ident)> {
+    let (name, s) = analysis::ident_at(&document.text, offset)?;
+    let ident = document.analysis.as_ref()?.lookup(&name, offset)?;
+
+    Some((name, s, ident))
+}
+
+fn main() {
+    let mut input = io::stdin().lock();
+
+    let mut server = Server {
+        output: io::stdout(),
+        documents: HashMap::new(),
+        shutdown: false,
+    };
+
+    loop {
+        let message = match rpc::read_message(&mut input) {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("[comfy-lsp] Could not read message: {}", e);
+                break;
+            }
+        };
+
+        if let Some(code) = server.handle(message) {
+            process::exit(code);
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use std::io::{self, BufRead, Write};
+
+use serde_json::Value;
+
+/// Reads a message framed by its `Content-Length` header, `None` at the end of the input
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+
+    loop {
+        let mut line = String::new();
+
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "Missing Content-Length header")
+    })?;
+
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+pub fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
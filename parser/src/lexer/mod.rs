@@ -1,8 +1,20 @@
-use chumsky::prelude::*;
+use chumsky::{prelude::*, text::Char};
 use comfy_types::tokens::{Kind, Literal};
 
 use super::LexError;
 
+/// Digits in `radix` with `_` separators after the first one, the separators are removed
+fn digits<'a>(radix: u32) -> impl Parser<'a, &'a str, String, LexError<'a>> + Clone {
+    text::digits(radix)
+        .then(
+            any()
+                .filter(move |c: &char| *c == '_' || c.is_digit(radix))
+                .repeated(),
+        )
+        .to_slice()
+        .map(|s: &str| s.replace('_', ""))
+}
+
 pub fn literals<'a>() -> impl Parser<'a, &'a str, Literal, LexError<'a>> {
     let numeric = {
         let frac = just('.').labelled("fraction");
@@ -13,31 +25,59 @@ pub fn literals<'a>() -> impl Parser<'a, &'a str, Literal, LexError<'a>> {
             .then(pm.or_not())
             .labelled("exponent");
 
+        let suffix = choice((
+            just("i8"),
+            just("i16"),
+            just("i32"),
+            just("i64"),
+            just("u8"),
+            just("u16"),
+            just("u32"),
+            just("u64"),
+            just("f32"),
+            just("f64"),
+        ))
+        .map(ToString::to_string)
+        .labelled("type suffix");
+
         let decimal = pm
             .or_not()
             .then(text::int(10))
-            .then(frac.then(text::digits(10)).or_not())
-            .then(exp.then(text::digits(10)).or_not())
+            .then(
+                just('_')
+                    .repeated()
+                    .at_least(1)
+                    .then(text::digits(10))
+                    .repeated(),
+            )
+            .then(frac.then(digits(10)).or_not())
+            .then(exp.then(digits(10)).or_not())
+            // Separators before the suffix, like `1_u8`
+            .then(just('_').repeated())
             .to_slice()
-            .map(|s: &str| Literal::Decimal(s.to_owned()))
+            .then(suffix.or_not())
+            .map(|(s, suffix): (&str, _)| Literal::Decimal(s.replace('_', ""), suffix))
             .labelled("decimal literal");
 
         let binary = just("0b")
-            .or(just("0b"))
-            .ignore_then(text::digits(2).to_slice())
-            .map(|s: &str| Literal::Binary(s.to_owned()))
+            .or(just("0B"))
+            .ignore_then(digits(2))
+            .then(suffix.or_not())
+            .map(|(s, suffix)| Literal::Binary(s, suffix))
             .labelled("binary literal");
 
         let octal = just("0o")
             .or(just("0O"))
-            .ignore_then(text::digits(8).to_slice())
-            .map(|s: &str| Literal::Octal(s.to_owned()))
+            .ignore_then(digits(8))
+            .then(suffix.or_not())
+            .map(|(s, suffix)| Literal::Octal(s, suffix))
             .labelled("octal literal");
 
         let hex = just("0x")
             .or(just("0X"))
-            .ignore_then(text::digits(16).to_slice())
-            .map(|s: &str| Literal::Hex(s.to_owned()))
+            .ignore_then(digits(16))
+            .then(suffix.or_not())
+            .map(|(s, suffix)| Literal::Hex(s, suffix))
             .labelled("hex literal");
 
         choice((binary, octal, hex, decimal))
@@ -98,7 +138,12 @@ pub fn literals<'a>() -> impl Parser<'a, &'a str, Literal, LexError<'a>> {
 }
 
 pub fn ident<'a>() -> impl Parser<'a, &'a str, String, LexError<'a>> {
-    text::ident().map(ToString::to_string)
+    // `text::ident` follows the XID rules, which don't allow a leading `_`
+    any()
+        .filter(|c: &char| c.is_ident_start() || *c == '_')
+        .then(any().filter(|c: &char| c.is_ident_continue()).repeated())
+        .to_slice()
+        .map(ToString::to_string)
 }
 
 pub fn token<'a>() -> impl Parser<'a, &'a str, (Kind, SimpleSpan), LexError<'a>> {
@@ -129,8 +174,17 @@ pub fn token<'a>() -> impl Parser<'a, &'a str, (Kind, SimpleSpan), LexError<'a>>
         just("]").to(Kind::RSquare),
     ));
 
+    let op3 = choice((
+        just("..=").to(Kind::DoubleDotEqual),
+        just("<<=").to(Kind::LeftShiftAssign),
+        just(">>=").to(Kind::RightShiftAssign),
+    ));
+
     let op2 = choice((
+        just("..").to(Kind::DoubleDot),
         just("->").to(Kind::Arrow),
+        just("::").to(Kind::DoubleColon),
+        just("=>").to(Kind::FatArrow),
         just("<<").to(Kind::LeftShift),
         just(">>").to(Kind::RightShift),
         just("++").to(Kind::DoublePlus),
@@ -149,10 +203,6 @@ pub fn token<'a>() -> impl Parser<'a, &'a str, (Kind, SimpleSpan), LexError<'a>>
         just("!=").to(Kind::NotEqual),
         just("<=").to(Kind::LessEqual),
         just(">=").to(Kind::GreaterEqual),
-        just("as").to(Kind::As),
-        just("priv").to(Kind::Priv),
-        just("pub").to(Kind::Pub),
-        just("prot").to(Kind::Prot),
     ));
 
     let ident = ident().map(|s| match s.as_str() {
@@ -166,10 +216,22 @@ pub fn token<'a>() -> impl Parser<'a, &'a str, (Kind, SimpleSpan), LexError<'a>>
         "in" => Kind::In,
         "break" => Kind::Break,
         "continue" => Kind::Continue,
+        "struct" => Kind::Struct,
+        "impl" => Kind::Impl,
+        "enum" => Kind::Enum,
+        "match" => Kind::Match,
+        "use" => Kind::Use,
+        "extern" => Kind::Extern,
+        "as" => Kind::As,
+        "pub" => Kind::Pub,
+        "priv" => Kind::Priv,
+        "prot" => Kind::Prot,
+        "sizeof" => Kind::Sizeof,
+        "alignof" => Kind::Alignof,
         _ => Kind::Ident(s),
     });
 
-    let literal = literals().map(|l| Kind::Literal(l));
+    let literal = literals().map(Kind::Literal);
 
     let cpp_code = none_of("#")
         .repeated()
@@ -178,19 +240,84 @@ pub fn token<'a>() -> impl Parser<'a, &'a str, (Kind, SimpleSpan), LexError<'a>>
         .map(|s: &str| Kind::CppCode(s.to_owned()))
         .labelled("cpp code");
 
-    let token = cpp_code.or(op2).or(op1).or(literal).or(ident);
-
+    // `///` is a doc comment, `////` and longer are plain comments
     let comment = just("//")
-        .then(any().and_is(just('\n').not()).repeated())
-        .padded();
+        .ignore_then(any().and_is(just('\n').not()).repeated().to_slice())
+        .map(|s: &str| match s.strip_prefix('/') {
+            Some(doc) if !doc.starts_with('/') => Kind::DocComment(doc.trim_end().to_owned()),
+            _ => Kind::Comment(s.trim_end().to_owned()),
+        })
+        .labelled("comment");
+
+    let token = comment
+        .or(cpp_code)
+        .or(op3)
+        .or(op2)
+        .or(op1)
+        .or(literal)
+        .or(ident);
 
     token
         .map_with(|s, e| (s, e.span()))
-        .padded_by(comment.repeated())
         .padded()
         .recover_with(skip_then_retry_until(any().ignored(), end()))
 }
 
-pub fn tokens<'a>() -> impl Parser<'a, &'a str, Vec<(Kind, SimpleSpan)>, LexError<'a>> {
+/// Tokens with the comments kept as trivia, for tools that write the source back
+pub fn tokens_with_trivia<'a>() -> impl Parser<'a, &'a str, Vec<(Kind, SimpleSpan)>, LexError<'a>> {
     token().repeated().collect()
 }
+
+pub fn tokens<'a>() -> impl Parser<'a, &'a str, Vec<(Kind, SimpleSpan)>, LexError<'a>> {
+    tokens_with_trivia().map(|tokens| {
+        tokens
+            .into_iter()
+            .filter(|(kind, _)| !matches!(kind, Kind::Comment(_) | Kind::DocComment(_)))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chumsky::Parser;
+    use comfy_types::tokens::Literal;
+
+    use super::literals;
+
+    fn literal(source: &str) -> Literal {
+        literals().parse(source).into_result().unwrap()
+    }
+
+    fn suffixed(v: &str, suffix: &str) -> Option<(String, Option<String>)> {
+        Some((v.to_owned(), Some(suffix.to_owned())))
+    }
+
+    #[test]
+    fn separators_before_the_suffix() {
+        let decimal = |source| match literal(source) {
+            Literal::Decimal(v, suffix) => Some((v, suffix)),
+            _ => None,
+        };
+
+        assert_eq!(decimal("1_u8"), suffixed("1", "u8"));
+        assert_eq!(decimal("1_000_i64"), suffixed("1000", "i64"));
+        assert_eq!(decimal("2.5_f32"), suffixed("2.5", "f32"));
+        assert_eq!(decimal("1_000"), Some(("1000".to_owned(), None)));
+    }
+
+    #[test]
+    fn prefixed_literals() {
+        assert_eq!(
+            literal("0xFF_i32"),
+            Literal::Hex("FF".to_owned(), Some("i32".to_owned()))
+        );
+        assert_eq!(
+            literal("0o17u8"),
+            Literal::Octal("17".to_owned(), Some("u8".to_owned()))
+        );
+        assert_eq!(
+            literal("0b1010_u8"),
+            Literal::Binary("1010".to_owned(), Some("u8".to_owned()))
+        );
+    }
+}
@@ -1,44 +1,66 @@
 pub mod lexer;
 mod parser;
 
-use comfy_types::Ast;
+use std::fmt;
+
+use comfy_types::{Ast, Diagnostic, Severity};
 use lexer::tokens;
 pub use parser::*;
 
 use ariadne::{Color, Label, Report, ReportKind, Source};
 
+use chumsky::error::Rich;
 use chumsky::input::Input;
 use chumsky::Parser as ChumskyParser;
 
-pub fn parse(src_file: &str, src: String) -> Result<Ast, ()> {
-    match tokens().parse(&src).into_result() {
-        Ok(tokens) => {
-            let tokens = tokens.as_slice().spanned((0..src.len()).into());
-
-            match statements().parse(tokens).into_result() {
-                Ok(ast) => Ok(ast),
-                Err(parse_errs) => Err(print_err!(parse_errs, src_file, &src, "Parser")),
-            }
-        }
-        Err(parse_errs) => Err(print_err!(parse_errs, src_file, &src, "Lexer")),
-    }
+fn diagnostics<T: fmt::Display>(errs: Vec<Rich<'_, T>>, code: &str) -> Vec<Diagnostic> {
+    errs.into_iter()
+        .map(|e| Diagnostic {
+            label: e.reason().to_string(),
+            ..Diagnostic::error(code, e.to_string(), Some(*e.span()))
+        })
+        .collect()
 }
 
-#[macro_export]
-macro_rules! print_err {
-    ($errs:expr, $src_file:expr, $src:expr, $code: literal) => {
-        $errs.into_iter().for_each(|e| {
-            Report::build(ReportKind::Error, $src_file, e.span().start)
-                .with_code($code)
-                .with_message(e.to_string())
-                .with_label(
-                    Label::new(($src_file, e.span().into_range()))
-                        .with_message(e.reason().to_string())
-                        .with_color(Color::Red),
-                )
-                .finish()
-                .print(($src_file, Source::from($src)))
-                .unwrap()
-        })
+/// Lexes and parses `src`, returns the lexer or parser errors on failure
+pub fn parse(src: &str) -> Result<Ast, Vec<Diagnostic>> {
+    let tokens = tokens()
+        .parse(src)
+        .into_result()
+        .map_err(|errs| diagnostics(errs, "Lexer"))?;
+
+    // Bound so it is dropped before `tokens`, it borrows them
+    let parser = statements();
+
+    parser
+        .parse(tokens.as_slice().spanned((0..src.len()).into()))
+        .into_result()
+        .map_err(|errs| diagnostics(errs, "Parser"))
+}
+
+/// Prints a diagnostic of `src_file` as a report
+pub fn report(diagnostic: &Diagnostic, src_file: &str, src: &str) {
+    let (kind, color) = match diagnostic.severity {
+        Severity::Error => (ReportKind::Error, Color::Red),
+        Severity::Warning => (ReportKind::Warning, Color::Yellow),
     };
+
+    let start = diagnostic.span.map_or(0, |s| s.start);
+    let report = Report::build(kind, src_file, start)
+        .with_code(&diagnostic.code)
+        .with_message(&diagnostic.message);
+
+    let report = match diagnostic.span {
+        Some(span) => report.with_label(
+            Label::new((src_file, span.into_range()))
+                .with_message(&diagnostic.label)
+                .with_color(color),
+        ),
+        None => report,
+    };
+
+    report
+        .finish()
+        .print((src_file, Source::from(src)))
+        .unwrap()
 }
@@ -1,5 +1,5 @@
 use clap::Parser;
-use comfy_parser::parse;
+use comfy_parser::{parse, report};
 use std::fs;
 
 #[derive(Parser, Debug)]
@@ -15,12 +15,8 @@ fn main() {
     let src_file = &args.input_file;
     let src = fs::read_to_string(src_file).expect("Could not read file");
 
-    let res = parse(src_file, src);
-
-    match res {
-        Ok(ast) => {
-            println!("AST: {:#?}", ast);
-        }
-        Err(_) => {}
+    match parse(&src) {
+        Ok(ast) => println!("AST: {:#?}", ast),
+        Err(diagnostics) => diagnostics.iter().for_each(|d| report(d, src_file, &src)),
     }
 }
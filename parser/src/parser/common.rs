@@ -1,9 +1,11 @@
 use chumsky::prelude::*;
 use comfy_types::{
     tokens::{self, Kind, TokenInput},
-    AccessModifier, Argument, Expr, Type,
+    AccessModifier, Argument, Expr, Field, Type, Variant,
 };
 
+use crate::id;
+
 use super::{expression, types, ParseError};
 
 pub fn ident<'a>() -> impl Parser<'a, TokenInput<'a>, String, ParseError<'a>> {
@@ -18,6 +20,12 @@ pub fn lit<'a>() -> impl Parser<'a, TokenInput<'a>, tokens::Literal, ParseError<
     }
 }
 
+pub fn string<'a>() -> impl Parser<'a, TokenInput<'a>, String, ParseError<'a>> {
+    select! {
+        Kind::Literal(tokens::Literal::Str(s)) => s
+    }
+}
+
 pub fn cpp_code<'a>() -> impl Parser<'a, TokenInput<'a>, String, ParseError<'a>> {
     select! {
         Kind::CppCode(s) => s
@@ -60,6 +68,35 @@ pub fn assignment<'a>() -> impl Parser<'a, TokenInput<'a>, Expr, ParseError<'a>>
         .labelled("assignment")
 }
 
+pub fn decl_generics<'a>() -> impl Parser<'a, TokenInput<'a>, Vec<String>, ParseError<'a>> {
+    ident()
+        .separated_by(just(Kind::Comma))
+        .at_least(1)
+        .allow_trailing()
+        .collect()
+        .delimited_by(just(Kind::Less), just(Kind::Greater))
+        .or_not()
+        .map(Option::unwrap_or_default)
+        .labelled("type parameters")
+}
+
+pub fn receiver<'a>() -> impl Parser<'a, TokenInput<'a>, Argument, ParseError<'a>> {
+    let this = |e: SimpleSpan| Type::Custom("Self".to_owned(), e);
+
+    choice((
+        just(Kind::Ampersand)
+            .ignore_then(just(id!("mut")))
+            .ignore_then(just(id!("self")))
+            .map_with(move |_, e| Type::MutableRef(Box::new(this(e.span())), e.span())),
+        just(Kind::Ampersand)
+            .ignore_then(just(id!("self")))
+            .map_with(move |_, e| Type::Reference(Box::new(this(e.span())), e.span())),
+        just(id!("self")).map_with(move |_, e| this(e.span())),
+    ))
+    .map_with(|ty, e| Argument("self".to_owned(), ty, Expr::Unknown, e.span()))
+    .labelled("receiver")
+}
+
 pub fn decl_args<'a>() -> impl Parser<'a, TokenInput<'a>, Vec<Argument>, ParseError<'a>> {
     let arg = ident()
         .then(type_descriptor())
@@ -67,8 +104,58 @@ pub fn decl_args<'a>() -> impl Parser<'a, TokenInput<'a>, Vec<Argument>, ParseEr
         .map_with(|((name, ty), exp), e| Argument(name, ty, exp.unwrap_or(Expr::Unknown), e.span()))
         .labelled("argument");
 
-    arg.separated_by(just(Kind::Comma))
+    let args = arg
+        .separated_by(just(Kind::Comma))
         .allow_trailing()
-        .collect()
+        .collect::<Vec<_>>()
+        .boxed();
+
+    receiver()
+        .then(just(Kind::Comma).ignore_then(args.clone()).or_not())
+        .map(|(receiver, args)| [vec![receiver], args.unwrap_or_default()].concat())
+        .or(args)
         .labelled("arguments")
 }
+
+pub fn decl_fields<'a>() -> impl Parser<'a, TokenInput<'a>, Vec<Field>, ParseError<'a>> {
+    let field = access_modifier()
+        .or_not()
+        .then(ident())
+        .then_ignore(just(Kind::Colon))
+        .then(types())
+        .map_with(|((access_modifier, name), ty), e| {
+            Field(
+                access_modifier.unwrap_or(AccessModifier::Private(e.span())),
+                name,
+                ty,
+                e.span(),
+            )
+        })
+        .labelled("field");
+
+    field
+        .separated_by(just(Kind::Comma))
+        .allow_trailing()
+        .collect()
+        .labelled("fields")
+}
+
+pub fn decl_variants<'a>() -> impl Parser<'a, TokenInput<'a>, Vec<Variant>, ParseError<'a>> {
+    let variant = ident()
+        .then(
+            types()
+                .separated_by(just(Kind::Comma))
+                .allow_trailing()
+                .collect()
+                .delimited_by(just(Kind::LParen), just(Kind::RParen))
+                .or_not(),
+        )
+        .map_with(|(name, types), e| Variant(name, types.unwrap_or_default(), e.span()))
+        .labelled("variant");
+
+    variant
+        .separated_by(just(Kind::Comma))
+        .allow_trailing()
+        .collect()
+        .labelled("variants")
+}
@@ -2,14 +2,38 @@ use chumsky::pratt::*;
 use chumsky::prelude::*;
 use comfy_types::tokens::Kind;
 use comfy_types::tokens::TokenInput;
-use comfy_types::Expr;
+use comfy_types::{Expr, FieldInit, MatchExprArm, Type};
 use comfy_utils::b;
 
+use crate::id;
+
 use super::common::cpp_code;
 use super::ParseError;
 
-use super::types::types;
-use super::{common::ident, literals::literals};
+use super::types::{array_len, expr_types, types};
+use super::{common::ident, literals::literals, patterns::pattern};
+
+/// Sequences are parsed as values before types, `(i32, u8)`, `[u8; 4]` and
+/// `[u8]` are types when every element is a written type
+fn written_type(e: Expr) -> Expr {
+    let ty = |e: &Expr| match e {
+        Expr::Type(ty) => Some(ty.clone()),
+        _ => None,
+    };
+
+    let written = match &e {
+        Expr::Tuple(items, s) => items
+            .iter()
+            .map(ty)
+            .collect::<Option<_>>()
+            .map(|types| Type::Tuple(types, *s)),
+        Expr::Repeat(item, len, s) => ty(item).map(|t| Type::Array(b(t), *len, *s)),
+        Expr::Array(items, s) if items.len() == 1 => ty(&items[0]).map(|t| Type::Slice(b(t), *s)),
+        _ => None,
+    };
+
+    written.map_or(e, Expr::Type)
+}
 
 pub fn expression<'a>() -> impl Parser<'a, TokenInput<'a>, Expr, ParseError<'a>> {
     let cpp_code = cpp_code()
@@ -18,9 +42,9 @@ pub fn expression<'a>() -> impl Parser<'a, TokenInput<'a>, Expr, ParseError<'a>>
 
     let id = ident().map_with(|s, e| Expr::Ident(s, e.span())).boxed();
 
-    let lit = literals().map(|l| Expr::Literal(l)).boxed();
+    let lit = literals().map(Expr::Literal).boxed();
 
-    let ty = types().map(|t| Expr::Type(t)).boxed();
+    let ty = expr_types().map(Expr::Type).boxed();
 
     let op = |c: Kind| just(c).boxed();
 
@@ -31,26 +55,76 @@ pub fn expression<'a>() -> impl Parser<'a, TokenInput<'a>, Expr, ParseError<'a>>
             .allow_trailing()
             .collect()
             .delimited_by(just(Kind::LSquare), just(Kind::RSquare))
-            .map_with(|s, e| Expr::Array(s, e.span()))
+            .map_with(|s, e| written_type(Expr::Array(s, e.span())))
             .labelled("array expression");
 
+        let len = array_len(types());
+
+        let repeat_expr = expr
+            .clone()
+            .then_ignore(just(Kind::Semicolon))
+            .then(len)
+            .delimited_by(just(Kind::LSquare), just(Kind::RSquare))
+            .map_with(|(value, len), e| written_type(Expr::Repeat(b(value), len, e.span())))
+            .labelled("array repeat expression");
+
         let tuple_expr = expr
             .clone()
             .separated_by(just(Kind::Comma))
             .allow_trailing()
             .collect()
             .delimited_by(just(Kind::LParen), just(Kind::RParen))
-            .map_with(|s, e| Expr::Tuple(s, e.span()))
+            .map_with(|s, e| written_type(Expr::Tuple(s, e.span())))
             .labelled("tuple expression");
 
-        let atom = lit
+        let field_init = ident()
+            .then_ignore(just(Kind::Colon))
+            .then(expr.clone())
+            .map_with(|(name, value), e| FieldInit(name, value, e.span()))
+            .labelled("field initializer");
+
+        let struct_expr = ident()
+            .then(
+                field_init
+                    .separated_by(just(Kind::Comma))
+                    .at_least(1)
+                    .allow_trailing()
+                    .collect()
+                    .delimited_by(just(Kind::LAngle), just(Kind::RAngle)),
+            )
+            .map_with(|(name, fields), e| Expr::Struct(name, fields, e.span()))
+            .labelled("struct expression");
+
+        let match_arm = pattern()
+            .then_ignore(just(Kind::FatArrow))
+            .then(expr.clone())
+            .map_with(|(pattern, value), e| MatchExprArm(pattern, value, e.span()))
+            .labelled("match arm");
+
+        let match_expr = just(Kind::Match)
+            .ignore_then(expr.clone())
+            .then(
+                match_arm
+                    .separated_by(just(Kind::Comma))
+                    .at_least(1)
+                    .allow_trailing()
+                    .collect()
+                    .delimited_by(just(Kind::LAngle), just(Kind::RAngle)),
+            )
+            .map_with(|(value, arms), e| Expr::Match(b(value), arms, e.span()))
+            .labelled("match expression");
+
+        let atom = match_expr
+            .or(lit)
             .or(expr
                 .clone()
                 .delimited_by(just(Kind::LParen), just(Kind::RParen)))
-            .or(ty)
+            .or(repeat_expr)
             .or(arr_expr)
             .or(tuple_expr)
+            .or(ty)
             .or(cpp_code)
+            .or(struct_expr)
             .or(id)
             .boxed();
 
@@ -75,8 +149,13 @@ pub fn expression<'a>() -> impl Parser<'a, TokenInput<'a>, Expr, ParseError<'a>>
             )
             .labelled("call expression");
 
-        let prat = arr_member
+        arr_member
             .pratt((
+                //
+                //
+                infix(left(16), op(Kind::DoubleColon), |l, r| {
+                    Expr::Path(b(l), b(r))
+                }),
                 //
                 //
                 infix(left(15), op(Kind::Dot), |l, r| Expr::Member(b(l), b(r))),
@@ -93,6 +172,9 @@ pub fn expression<'a>() -> impl Parser<'a, TokenInput<'a>, Expr, ParseError<'a>>
                 prefix(14, op(Kind::ExclamationMark), |rhs| Expr::Not(b(rhs))),
                 prefix(14, op(Kind::Tilde), |rhs| Expr::BitNot(b(rhs))),
                 prefix(14, op(Kind::Star), |rhs| Expr::Deref(b(rhs))),
+                prefix(14, op(Kind::Ampersand).then(just(id!("mut"))), |rhs| {
+                    Expr::MutAddress(b(rhs))
+                }),
                 prefix(14, op(Kind::Ampersand), |rhs| Expr::Address(b(rhs))),
                 prefix(14, op(Kind::Sizeof), |rhs| Expr::Size(b(rhs))),
                 prefix(14, op(Kind::Alignof), |rhs| Expr::Align(b(rhs))),
@@ -109,16 +191,15 @@ pub fn expression<'a>() -> impl Parser<'a, TokenInput<'a>, Expr, ParseError<'a>>
                 //
                 infix(left(11), op(Kind::LeftShift), |l, r| Expr::Shl(b(l), b(r))),
                 infix(left(11), op(Kind::RightShift), |l, r| Expr::Shr(b(l), b(r))),
-                //
-                //
+            ))
+            .pratt((
                 infix(left(10), op(Kind::Less), |l, r| Expr::Lt(b(l), b(r))),
                 infix(left(10), op(Kind::LessEqual), |l, r| Expr::Le(b(l), b(r))),
                 infix(left(10), op(Kind::Greater), |l, r| Expr::Gt(b(l), b(r))),
                 infix(left(10), op(Kind::GreaterEqual), |l, r| {
                     Expr::Ge(b(l), b(r))
                 }),
-            ))
-            .pratt((
+                //
                 infix(left(9), op(Kind::DoubleEqual), |l, r| Expr::Eq(b(l), b(r))),
                 infix(left(9), op(Kind::NotEqual), |l, r| Expr::Ne(b(l), b(r))),
                 //
@@ -134,41 +215,122 @@ pub fn expression<'a>() -> impl Parser<'a, TokenInput<'a>, Expr, ParseError<'a>>
                 //
                 infix(left(4), op(Kind::DoublePipe), |l, r| Expr::Or(b(l), b(r))),
                 //
-                infix(right(3), op(Kind::Assign), |l, r| Expr::Assign(b(l), b(r))),
-                infix(right(3), op(Kind::PlusAssign), |l, r| {
+                infix(left(3), op(Kind::DoubleDot), |l, r| Expr::Range(b(l), b(r))),
+                infix(left(3), op(Kind::DoubleDotEqual), |l, r| {
+                    Expr::RangeInclusive(b(l), b(r))
+                }),
+                //
+                infix(right(2), op(Kind::Assign), |l, r| Expr::Assign(b(l), b(r))),
+                infix(right(2), op(Kind::PlusAssign), |l, r| {
                     Expr::AddAssign(b(l), b(r))
                 }),
-                infix(right(3), op(Kind::MinusAssign), |l, r| {
+                infix(right(2), op(Kind::MinusAssign), |l, r| {
                     Expr::SubAssign(b(l), b(r))
                 }),
-                infix(right(3), op(Kind::StarAssign), |l, r| {
+                infix(right(2), op(Kind::StarAssign), |l, r| {
                     Expr::MulAssign(b(l), b(r))
                 }),
-                infix(right(3), op(Kind::SlashAssign), |l, r| {
+                infix(right(2), op(Kind::SlashAssign), |l, r| {
                     Expr::DivAssign(b(l), b(r))
                 }),
-                infix(right(3), op(Kind::PercentAssign), |l, r| {
+                infix(right(2), op(Kind::PercentAssign), |l, r| {
                     Expr::ModAssign(b(l), b(r))
                 }),
-                infix(right(3), op(Kind::LeftShiftAssign), |l, r| {
+                infix(right(2), op(Kind::LeftShiftAssign), |l, r| {
                     Expr::ShlAssign(b(l), b(r))
                 }),
-                infix(right(3), op(Kind::RightShiftAssign), |l, r| {
+                infix(right(2), op(Kind::RightShiftAssign), |l, r| {
                     Expr::ShrAssign(b(l), b(r))
                 }),
-                infix(right(3), op(Kind::AmpersandAssign), |l, r| {
+                infix(right(2), op(Kind::AmpersandAssign), |l, r| {
                     Expr::BitAndAssign(b(l), b(r))
                 }),
-                infix(right(3), op(Kind::CaretAssign), |l, r| {
+                infix(right(2), op(Kind::CaretAssign), |l, r| {
                     Expr::BitXorAssign(b(l), b(r))
                 }),
-                infix(right(3), op(Kind::PipeAssign), |l, r| {
+                infix(right(2), op(Kind::PipeAssign), |l, r| {
                     Expr::BitOrAssign(b(l), b(r))
                 }),
-            ));
-
-        prat
+            ))
     });
 
     complex_expr.labelled("expression")
 }
+
+#[cfg(test)]
+mod tests {
+    use comfy_types::{Expr, MatchExprArm, Pattern, Statements, Type};
+
+    use crate::parse;
+
+    /// Value of `let x = <source>;`
+    fn value(source: &str) -> Expr {
+        let ast = parse(&format!("let x = {};", source)).unwrap();
+
+        match &ast[..] {
+            [Statements::LetStatement(_, _, _, value, _)] => value.clone(),
+            _ => panic!("expected a let statement, got {:?}", ast),
+        }
+    }
+
+    #[test]
+    fn tuple_of_variables() {
+        let Expr::Tuple(items, _) = value("(b, a)") else {
+            panic!("expected a tuple");
+        };
+
+        assert!(
+            matches!(&items[..], [Expr::Ident(b, _), Expr::Ident(a, _)] if b == "b" && a == "a")
+        );
+    }
+
+    #[test]
+    fn repeat_of_variable() {
+        let Expr::Repeat(item, 4, _) = value("[v; 4]") else {
+            panic!("expected a repeat literal");
+        };
+
+        assert!(matches!(item.as_ref(), Expr::Ident(v, _) if v == "v"));
+    }
+
+    #[test]
+    fn repeat_lengths() {
+        assert!(matches!(value("[0; 0x10]"), Expr::Repeat(_, 16, _)));
+        assert!(matches!(value("[0; 0b11]"), Expr::Repeat(_, 3, _)));
+        assert!(matches!(value("[0; sizeof u32]"), Expr::Repeat(_, 4, _)));
+
+        let errors = parse("let x = [0; n];").unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "Array length must be an integer literal or `sizeof T`, found `n`"
+        );
+    }
+
+    #[test]
+    fn written_types() {
+        assert!(
+            matches!(value("sizeof [u8; 4]"), Expr::Size(ty) if matches!(*ty, Expr::Type(Type::Array(_, 4, _))))
+        );
+        assert!(matches!(value("(i32, u8)"), Expr::Type(Type::Tuple(..))));
+        assert!(matches!(value("[u8]"), Expr::Type(Type::Slice(..))));
+    }
+
+    #[test]
+    fn match_expression() {
+        let Expr::Match(value, arms, _) =
+            value("match n { 1 => 2, Shape::Rect(w, _) => w, _ => 3, }")
+        else {
+            panic!("expected a match expression");
+        };
+
+        assert!(matches!(*value, Expr::Ident(n, _) if n == "n"));
+        assert!(matches!(
+            &arms[..],
+            [
+                MatchExprArm(Pattern::Literal(_), Expr::Literal(_), _),
+                MatchExprArm(Pattern::Variant(..), Expr::Ident(..), _),
+                MatchExprArm(Pattern::Wildcard(_), Expr::Literal(_), _),
+            ]
+        ));
+    }
+}
@@ -1,22 +1,49 @@
-use chumsky::prelude::*;
+use chumsky::{prelude::*, span::SimpleSpan};
 use comfy_types::{
     tokens::{self, TokenInput},
-    Literal,
+    Literal, Type,
 };
 
 use super::ParseError;
 
 use super::common::lit;
 
+/// Type of a numeric suffix, which ends the literal at `span`
+fn suffix_type(suffix: Option<String>, span: SimpleSpan) -> Option<Type> {
+    let suffix = suffix?;
+    let s = SimpleSpan::new(span.end - suffix.len(), span.end);
+
+    Some(match suffix.as_str() {
+        "i8" => Type::I8(s),
+        "i16" => Type::I16(s),
+        "i32" => Type::I32(s),
+        "i64" => Type::I64(s),
+        "u8" => Type::U8(s),
+        "u16" => Type::U16(s),
+        "u32" => Type::U32(s),
+        "u64" => Type::U64(s),
+        "f32" => Type::F32(s),
+        _ => Type::F64(s),
+    })
+}
+
 pub fn literals<'a>() -> impl Parser<'a, TokenInput<'a>, Literal, ParseError<'a>> {
     lit()
         .map_with(|s: tokens::Literal, e| match s {
             tokens::Literal::True => Literal::True(e.span()),
             tokens::Literal::False => Literal::False(e.span()),
-            tokens::Literal::Decimal(s) => Literal::Decimal(s, e.span()),
-            tokens::Literal::Hex(s) => Literal::Hex(s, e.span()),
-            tokens::Literal::Octal(s) => Literal::Octal(s, e.span()),
-            tokens::Literal::Binary(s) => Literal::Binary(s, e.span()),
+            tokens::Literal::Decimal(s, suffix) => {
+                Literal::Decimal(s, suffix_type(suffix, e.span()), e.span())
+            }
+            tokens::Literal::Hex(s, suffix) => {
+                Literal::Hex(s, suffix_type(suffix, e.span()), e.span())
+            }
+            tokens::Literal::Octal(s, suffix) => {
+                Literal::Octal(s, suffix_type(suffix, e.span()), e.span())
+            }
+            tokens::Literal::Binary(s, suffix) => {
+                Literal::Binary(s, suffix_type(suffix, e.span()), e.span())
+            }
             tokens::Literal::Char(c) => Literal::Char(c, e.span()),
             tokens::Literal::Str(s) => Literal::Str(s, e.span()),
         })
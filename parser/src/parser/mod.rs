@@ -1,12 +1,14 @@
 mod common;
 mod expressions;
 mod literals;
+mod patterns;
 mod statements;
 mod types;
 
 use comfy_types::tokens::Kind;
 pub use common::ident;
 pub use expressions::expression;
+pub use patterns::pattern;
 
 pub use statements::statements;
 pub use types::types;
@@ -0,0 +1,50 @@
+use chumsky::prelude::*;
+use comfy_types::{
+    tokens::{Kind, TokenInput},
+    Pattern,
+};
+
+use crate::id;
+
+use super::ParseError;
+
+use super::{common::ident, literals::literals};
+
+pub fn pattern<'a>() -> impl Parser<'a, TokenInput<'a>, Pattern, ParseError<'a>> {
+    recursive(|pattern| {
+        let wildcard = just(id!("_"))
+            .map_with(|_, e| Pattern::Wildcard(e.span()))
+            .labelled("wildcard pattern")
+            .boxed();
+
+        let literal = literals()
+            .map(Pattern::Literal)
+            .labelled("literal pattern")
+            .boxed();
+
+        let variant = ident()
+            .then_ignore(just(Kind::DoubleColon))
+            .then(ident())
+            .then(
+                pattern
+                    .separated_by(just(Kind::Comma))
+                    .allow_trailing()
+                    .collect()
+                    .delimited_by(just(Kind::LParen), just(Kind::RParen))
+                    .or_not(),
+            )
+            .map_with(|((name, variant), patterns), e| {
+                Pattern::Variant(name, variant, patterns.unwrap_or_default(), e.span())
+            })
+            .labelled("variant pattern")
+            .boxed();
+
+        let binding = ident()
+            .map_with(|name, e| Pattern::Binding(name, e.span()))
+            .labelled("binding pattern")
+            .boxed();
+
+        choice((wildcard, literal, variant, binding))
+    })
+    .labelled("pattern")
+}
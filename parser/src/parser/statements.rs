@@ -2,6 +2,8 @@ use chumsky::prelude::*;
 use comfy_types::tokens::Kind;
 use comfy_types::tokens::TokenInput;
 use comfy_types::AccessModifier;
+use comfy_types::ExternFunction;
+use comfy_types::MatchArm;
 use comfy_types::Statements;
 
 use super::ParseError;
@@ -9,10 +11,15 @@ use super::ParseError;
 use super::common::access_modifier;
 use super::common::assignment;
 use super::common::decl_args;
+use super::common::decl_fields;
+use super::common::decl_generics;
+use super::common::decl_variants;
 use super::common::fn_type_descriptor;
+use super::common::string;
 
 use super::common::type_descriptor;
-use super::{expression, ident};
+use super::{expression, ident, pattern};
+use crate::id;
 
 pub fn statements<'a>() -> impl Parser<'a, TokenInput<'a>, Vec<Statements>, ParseError<'a>> {
     recursive(|stmt| {
@@ -36,38 +43,155 @@ pub fn statements<'a>() -> impl Parser<'a, TokenInput<'a>, Vec<Statements>, Pars
             .labelled("expression statement");
 
         let let_statement = just(Kind::Let)
-            .ignore_then(ident())
+            .ignore_then(just(id!("mut")).or_not().map(|m| m.is_some()))
+            .then(ident())
+            .then(type_descriptor())
+            .then(assignment())
+            .then_ignore(just(Kind::Semicolon))
+            .map_with(|(((mutable, name), ty), expr), e| {
+                Statements::LetStatement(name, mutable, ty, expr, e.span())
+            })
+            .labelled("let statement");
+
+        let let_tuple_statement = just(Kind::Let)
+            .ignore_then(
+                ident()
+                    .separated_by(just(Kind::Comma))
+                    .at_least(1)
+                    .allow_trailing()
+                    .collect()
+                    .delimited_by(just(Kind::LParen), just(Kind::RParen)),
+            )
             .then(type_descriptor())
             .then(assignment())
             .then_ignore(just(Kind::Semicolon))
-            .map_with(|((name, ty), expr), e| Statements::LetStatement(name, ty, expr, e.span()))
+            .map_with(|((names, ty), expr), e| {
+                Statements::LetTupleStatement(names, ty, expr, e.span())
+            })
             .labelled("let statement");
 
         let function_declaration = access_modifier()
             .or_not()
             .then_ignore(just(Kind::Fn))
             .then(ident())
+            .then(decl_generics())
             .then(decl_args().delimited_by(just(Kind::LParen), just(Kind::RParen)))
             .then(fn_type_descriptor())
             .then(code_block.clone())
-            .map_with(|((((access_modifier, name), args), ty), body), e| {
-                Statements::FunctionDeclaration(
+            .map_with(
+                |(((((access_modifier, name), generics), args), ty), body), e| {
+                    Statements::FunctionDeclaration(
+                        access_modifier.unwrap_or(AccessModifier::Private(e.span())),
+                        name,
+                        generics,
+                        args,
+                        ty,
+                        body,
+                        e.span(),
+                    )
+                },
+            )
+            .labelled("function declaration")
+            .boxed();
+
+        let struct_declaration = access_modifier()
+            .or_not()
+            .then_ignore(just(Kind::Struct))
+            .then(ident())
+            .then(decl_generics())
+            .then(decl_fields().delimited_by(just(Kind::LAngle), just(Kind::RAngle)))
+            .map_with(|(((access_modifier, name), generics), fields), e| {
+                Statements::StructDeclaration(
                     access_modifier.unwrap_or(AccessModifier::Private(e.span())),
                     name,
-                    args,
-                    ty,
-                    body,
+                    generics,
+                    fields,
                     e.span(),
                 )
             })
-            .labelled("function declaration");
+            .labelled("struct declaration");
+
+        let enum_declaration = access_modifier()
+            .or_not()
+            .then_ignore(just(Kind::Enum))
+            .then(ident())
+            .then(decl_variants().delimited_by(just(Kind::LAngle), just(Kind::RAngle)))
+            .map_with(|((access_modifier, name), variants), e| {
+                Statements::EnumDeclaration(
+                    access_modifier.unwrap_or(AccessModifier::Private(e.span())),
+                    name,
+                    variants,
+                    e.span(),
+                )
+            })
+            .labelled("enum declaration");
+
+        let impl_declaration = just(Kind::Impl)
+            .ignore_then(ident())
+            .then(
+                function_declaration
+                    .clone()
+                    .repeated()
+                    .collect()
+                    .delimited_by(just(Kind::LAngle), just(Kind::RAngle)),
+            )
+            .map_with(|(name, methods), e| Statements::ImplDeclaration(name, methods, e.span()))
+            .labelled("impl declaration");
+
+        let use_statement = just(Kind::Use)
+            .ignore_then(
+                ident()
+                    .then_ignore(just(Kind::DoubleColon))
+                    .repeated()
+                    .at_least(1)
+                    .collect(),
+            )
+            .then(choice((
+                ident().map(|item| vec![item]),
+                ident()
+                    .separated_by(just(Kind::Comma))
+                    .at_least(1)
+                    .allow_trailing()
+                    .collect()
+                    .delimited_by(just(Kind::LAngle), just(Kind::RAngle)),
+            )))
+            .then_ignore(just(Kind::Semicolon))
+            .map_with(|(path, items), e| Statements::UseStatement(path, items, e.span()))
+            .labelled("use statement");
+
+        let extern_function = just(Kind::Fn)
+            .ignore_then(ident())
+            .then(decl_args().delimited_by(just(Kind::LParen), just(Kind::RParen)))
+            .then(fn_type_descriptor())
+            .then_ignore(just(Kind::Semicolon))
+            .map_with(|((name, args), ty), e| ExternFunction(name, args, ty, e.span()))
+            .labelled("extern function");
+
+        let extern_block = just(Kind::Extern)
+            .ignore_then(string())
+            .then(
+                just(id!("include"))
+                    .ignore_then(string())
+                    .repeated()
+                    .collect(),
+            )
+            .then(
+                extern_function
+                    .repeated()
+                    .collect()
+                    .delimited_by(just(Kind::LAngle), just(Kind::RAngle)),
+            )
+            .map_with(|((abi, includes), functions), e| {
+                Statements::ExternBlock(abi, includes, functions, e.span())
+            })
+            .labelled("extern block");
 
         let if_statement = just(Kind::If)
             .ignore_then(expression())
             .then(code_block.clone())
             .then(
                 just(Kind::Else)
-                    .ignore_then(code_block)
+                    .ignore_then(code_block.clone())
                     .or_not()
                     .map(|b| b.unwrap_or(vec![])),
             )
@@ -76,6 +200,51 @@ pub fn statements<'a>() -> impl Parser<'a, TokenInput<'a>, Vec<Statements>, Pars
             })
             .labelled("if statement");
 
+        let match_arm = pattern()
+            .then_ignore(just(Kind::FatArrow))
+            .then(code_block.clone())
+            .then_ignore(just(Kind::Comma).or_not())
+            .map_with(|(pattern, body), e| MatchArm(pattern, body, e.span()))
+            .labelled("match arm");
+
+        let match_statement = just(Kind::Match)
+            .ignore_then(expression())
+            .then(
+                match_arm
+                    .repeated()
+                    .at_least(1)
+                    .collect()
+                    .delimited_by(just(Kind::LAngle), just(Kind::RAngle)),
+            )
+            .map_with(|(value, arms), e| Statements::MatchStatement(value, arms, e.span()))
+            .labelled("match statement");
+
+        let while_statement = just(Kind::While)
+            .ignore_then(expression())
+            .then(code_block.clone())
+            .map_with(|(condition, body), e| Statements::WhileStatement(condition, body, e.span()))
+            .labelled("while statement");
+
+        let for_statement = just(Kind::For)
+            .ignore_then(ident())
+            .then_ignore(just(Kind::In))
+            .then(expression())
+            .then(code_block)
+            .map_with(|((name, iterable), body), e| {
+                Statements::ForStatement(name, iterable, body, e.span())
+            })
+            .labelled("for statement");
+
+        let break_statement = just(Kind::Break)
+            .then_ignore(just(Kind::Semicolon))
+            .map_with(|_, e| Statements::BreakStatement(e.span()))
+            .labelled("break statement");
+
+        let continue_statement = just(Kind::Continue)
+            .then_ignore(just(Kind::Semicolon))
+            .map_with(|_, e| Statements::ContinueStatement(e.span()))
+            .labelled("continue statement");
+
         let return_statement = choice((
             just(Kind::Return)
                 .ignore_then(expression())
@@ -87,9 +256,20 @@ pub fn statements<'a>() -> impl Parser<'a, TokenInput<'a>, Vec<Statements>, Pars
 
         choice((
             function_declaration,
+            struct_declaration,
+            enum_declaration,
+            impl_declaration,
+            use_statement,
+            extern_block,
             expr_statement,
             let_statement,
+            let_tuple_statement,
             if_statement,
+            match_statement,
+            while_statement,
+            for_statement,
+            break_statement,
+            continue_statement,
             return_statement,
         ))
         .boxed()
@@ -23,6 +23,19 @@ macro_rules! id {
 }
 
 pub fn types<'a>() -> impl Parser<'a, TokenInput<'a>, Type, ParseError<'a>> {
+    choice((expr_types(), custom())).labelled("type")
+}
+
+fn custom<'a>() -> impl Parser<'a, TokenInput<'a>, Type, ParseError<'a>> + Clone {
+    ident()
+        .map_with(|s, e| Type::Custom(s, e.span()))
+        .labelled("user-defined type")
+        .boxed()
+}
+
+/// Types that may appear inside expressions, a bare user-defined
+/// name is parsed as an identifier there
+pub fn expr_types<'a>() -> impl Parser<'a, TokenInput<'a>, Type, ParseError<'a>> {
     let bool = just(id!("bool")).map_with(to!(Type::Bool));
 
     let numeric = choice((
@@ -57,17 +70,8 @@ pub fn types<'a>() -> impl Parser<'a, TokenInput<'a>, Type, ParseError<'a>> {
         .labelled("simple type")
         .boxed();
 
-    let literal = select! {
-        Kind::Literal(l) => l
-    };
-
-    let custom = ident()
-        .map_with(|s, e| Type::Custom(s, e.span()))
-        .labelled("user-defined type")
-        .boxed();
-
     let complex_types = recursive(|complex| {
-        let t = simple_types.clone().or(complex).or(custom);
+        let t = simple_types.clone().or(complex).or(custom());
 
         let tuple = t
             .clone()
@@ -78,24 +82,14 @@ pub fn types<'a>() -> impl Parser<'a, TokenInput<'a>, Type, ParseError<'a>> {
             .map_with(|s, e| Type::Tuple(s, e.span()))
             .labelled("tuple type");
 
+        let size = array_len(t.clone());
+
         let array = t
             .clone()
             .then_ignore(just(Kind::Semicolon))
-            .then(literal)
+            .then(size)
             .delimited_by(just(Kind::LSquare), just(Kind::RSquare))
-            .try_map_with(|(ty, size), e| match size {
-                tokens::Literal::Decimal(s) => match s.parse::<u64>() {
-                    Ok(s) => Ok(Type::Array(Box::new(ty), s, e.span())),
-                    Err(ee) => Err(Rich::custom(
-                        e.span(),
-                        format!("Array size must be a decimal integer literal: {}", ee),
-                    )),
-                },
-                _ => Err(Rich::custom(
-                    e.span(),
-                    "Array size must be a decimal integer literal",
-                )),
-            })
+            .map_with(|(ty, size), e| Type::Array(Box::new(ty), size, e.span()))
             .labelled("array type");
 
         let slice = t
@@ -137,3 +131,55 @@ pub fn types<'a>() -> impl Parser<'a, TokenInput<'a>, Type, ParseError<'a>> {
 
     choice((complex_types, simple_types)).labelled("type")
 }
+
+/// Length of an array type or a repeat literal, an integer literal or
+/// `sizeof T` and `alignof T` of a type with a known layout
+pub fn array_len<'a>(
+    ty: impl Parser<'a, TokenInput<'a>, Type, ParseError<'a>> + 'a,
+) -> impl Parser<'a, TokenInput<'a>, u64, ParseError<'a>> + Clone {
+    let literal = select! {
+        Kind::Literal(tokens::Literal::Decimal(v, None)) => (v, 10),
+        Kind::Literal(tokens::Literal::Hex(v, None)) => (v, 16),
+        Kind::Literal(tokens::Literal::Octal(v, None)) => (v, 8),
+        Kind::Literal(tokens::Literal::Binary(v, None)) => (v, 2),
+    }
+    .try_map(|(v, radix), s| {
+        u64::from_str_radix(&v, radix)
+            .map_err(|e| Rich::custom(s, format!("Invalid array length: {}", e)))
+    });
+
+    let layout = choice((just(Kind::Sizeof).to(true), just(Kind::Alignof).to(false)))
+        .then(ty)
+        .validate(|(size, ty), e, emitter| match ty.layout() {
+            Some(layout) => {
+                if size {
+                    layout.0
+                } else {
+                    layout.1
+                }
+            }
+            None => {
+                emitter.emit(Rich::custom(
+                    e.span(),
+                    format!("Layout of {} is not known at compile time", ty),
+                ));
+
+                0
+            }
+        });
+
+    // There are no constants, a name is reported instead of a generic parse error
+    let name = select! { Kind::Ident(name) => name }.validate(|name, e, emitter| {
+        emitter.emit(Rich::custom(
+            e.span(),
+            format!(
+                "Array length must be an integer literal or `sizeof T`, found `{}`",
+                name
+            ),
+        ));
+
+        0
+    });
+
+    literal.or(layout).or(name).boxed()
+}
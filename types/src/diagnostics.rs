@@ -0,0 +1,46 @@
+use std::fmt;
+
+use chumsky::span::SimpleSpan;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// Error or warning of any phase, rendered by the CLIs and editor tooling
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Phase that reported it, like `Parser` or `Compiler`, or the name of the lint
+    pub code: String,
+    pub message: String,
+    /// Message at the span
+    pub label: String,
+    /// `None` for errors that are not in the source, like clang++ failing
+    pub span: Option<SimpleSpan>,
+    /// File the span points into, `None` for the file being compiled
+    pub file: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &str, message: String, span: Option<SimpleSpan>) -> Self {
+        Self {
+            severity: Severity::Error,
+            code: code.to_owned(),
+            label: message.clone(),
+            message,
+            span,
+            file: None,
+        }
+    }
+}
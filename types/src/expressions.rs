@@ -1,6 +1,6 @@
 use chumsky::span::SimpleSpan;
 
-use crate::{Literal, Type};
+use crate::{Literal, Pattern, Type};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
@@ -32,6 +32,7 @@ pub enum Expr {
 
     Deref(Box<Self>),
     Address(Box<Self>),
+    MutAddress(Box<Self>),
 
     // Comparison
     Eq(Box<Self>, Box<Self>),
@@ -56,6 +57,7 @@ pub enum Expr {
 
     // Member
     Member(Box<Self>, Box<Self>),
+    Path(Box<Self>, Box<Self>),
 
     // Cast
     Cast(Box<Self>, Box<Self>),
@@ -80,9 +82,27 @@ pub enum Expr {
 
     ArrMember(Box<Self>, Box<Self>),
 
+    // Range
+    Range(Box<Self>, Box<Self>),
+    RangeInclusive(Box<Self>, Box<Self>),
+
     // Sequence
     Tuple(Vec<Self>, SimpleSpan),
     Array(Vec<Self>, SimpleSpan),
+    /// `[value; len]`
+    Repeat(Box<Self>, u64, SimpleSpan),
+
+    // User-defined
+    Struct(String, Vec<FieldInit>, SimpleSpan),
+
+    /// `match value { pattern => value, ... }`, the value of the arm that matches
+    Match(Box<Self>, Vec<MatchExprArm>, SimpleSpan),
 
     Unknown, // For variable initialization
 }
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldInit(pub String, pub Expr, pub SimpleSpan);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchExprArm(pub Pattern, pub Expr, pub SimpleSpan);
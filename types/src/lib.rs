@@ -1,11 +1,15 @@
 mod access_modifier;
+mod diagnostics;
 mod expressions;
+mod patterns;
 mod statements;
 pub mod tokens;
 mod values;
 
 pub use access_modifier::*;
+pub use diagnostics::*;
 pub use expressions::*;
+pub use patterns::*;
 pub use statements::*;
 pub use values::*;
 
@@ -0,0 +1,11 @@
+use chumsky::span::SimpleSpan;
+
+use crate::Literal;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Wildcard(SimpleSpan),
+    Binding(String, SimpleSpan),
+    Literal(Literal),
+    Variant(String, String, Vec<Pattern>, SimpleSpan),
+}
@@ -1,22 +1,47 @@
 use chumsky::span::SimpleSpan;
 
-use crate::{AccessModifier, Expr, Type};
+use crate::{AccessModifier, Expr, Pattern, Type};
 
 #[derive(Debug, Clone)]
 pub enum Statements {
     ExpressionStatement(Expr, SimpleSpan),
-    LetStatement(String, Type, Expr, SimpleSpan),
+    /// `let name` or `let mut name` when the flag is set
+    LetStatement(String, bool, Type, Expr, SimpleSpan),
+    LetTupleStatement(Vec<String>, Type, Expr, SimpleSpan),
     FunctionDeclaration(
         AccessModifier,
         String,
+        Vec<String>,
         Vec<Argument>,
         Type,
         Vec<Statements>,
         SimpleSpan,
     ),
+    StructDeclaration(AccessModifier, String, Vec<String>, Vec<Field>, SimpleSpan),
+    EnumDeclaration(AccessModifier, String, Vec<Variant>, SimpleSpan),
+    ImplDeclaration(String, Vec<Statements>, SimpleSpan),
+    UseStatement(Vec<String>, Vec<String>, SimpleSpan),
+    ExternBlock(String, Vec<String>, Vec<ExternFunction>, SimpleSpan),
     IfStatement(Expr, Vec<Statements>, Vec<Statements>, SimpleSpan),
+    MatchStatement(Expr, Vec<MatchArm>, SimpleSpan),
+    WhileStatement(Expr, Vec<Statements>, SimpleSpan),
+    ForStatement(String, Expr, Vec<Statements>, SimpleSpan),
+    BreakStatement(SimpleSpan),
+    ContinueStatement(SimpleSpan),
     ReturnStatement(Expr, SimpleSpan),
 }
 
 #[derive(Debug, Clone)]
 pub struct Argument(pub String, pub Type, pub Expr, pub SimpleSpan);
+
+#[derive(Debug, Clone)]
+pub struct ExternFunction(pub String, pub Vec<Argument>, pub Type, pub SimpleSpan);
+
+#[derive(Debug, Clone)]
+pub struct Field(pub AccessModifier, pub String, pub Type, pub SimpleSpan);
+
+#[derive(Debug, Clone)]
+pub struct Variant(pub String, pub Vec<Type>, pub SimpleSpan);
+
+#[derive(Debug, Clone)]
+pub struct MatchArm(pub Pattern, pub Vec<Statements>, pub SimpleSpan);
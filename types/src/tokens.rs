@@ -10,10 +10,18 @@ pub enum Kind {
     Comma,
     /// :
     Colon,
+    /// ::
+    DoubleColon,
     /// ->
     Arrow,
+    /// =>
+    FatArrow,
     /// .
     Dot,
+    /// ..
+    DoubleDot,
+    /// ..=
+    DoubleDotEqual,
 
     // Operators
     /// +
@@ -120,6 +128,12 @@ pub enum Kind {
     Pub,
     Priv,
     Prot,
+    Struct,
+    Impl,
+    Enum,
+    Match,
+    Use,
+    Extern,
 
     // Identifiers
     Ident(String),
@@ -129,6 +143,12 @@ pub enum Kind {
 
     // Literals
     Literal(Literal),
+
+    // Trivia
+    /// // comment
+    Comment(String),
+    /// /// doc comment
+    DocComment(String),
 }
 
 impl fmt::Display for Kind {
@@ -139,8 +159,12 @@ impl fmt::Display for Kind {
             Kind::Semicolon => write!(f, ";"),
             Kind::Comma => write!(f, ","),
             Kind::Colon => write!(f, ":"),
+            Kind::DoubleColon => write!(f, "::"),
             Kind::Arrow => write!(f, "->"),
+            Kind::FatArrow => write!(f, "=>"),
             Kind::Dot => write!(f, "."),
+            Kind::DoubleDot => write!(f, ".."),
+            Kind::DoubleDotEqual => write!(f, "..="),
             Kind::Plus => write!(f, "+"),
             Kind::Minus => write!(f, "-"),
             Kind::Star => write!(f, "*"),
@@ -197,7 +221,15 @@ impl fmt::Display for Kind {
             Kind::Pub => write!(f, "pub"),
             Kind::Priv => write!(f, "priv"),
             Kind::Prot => write!(f, "prot"),
+            Kind::Struct => write!(f, "struct"),
+            Kind::Impl => write!(f, "impl"),
+            Kind::Enum => write!(f, "enum"),
+            Kind::Match => write!(f, "match"),
+            Kind::Use => write!(f, "use"),
+            Kind::Extern => write!(f, "extern"),
             Kind::CppCode(v) => write!(f, "raw_cpp# {} #", v),
+            Kind::Comment(c) => write!(f, "//{}", c),
+            Kind::DocComment(c) => write!(f, "///{}", c),
         }
     }
 }
@@ -208,11 +240,11 @@ pub enum Literal {
     True,
     False,
 
-    // Numeric
-    Decimal(String),
-    Hex(String),
-    Octal(String),
-    Binary(String),
+    // Numeric, with the type suffix like `u8` in `255u8`
+    Decimal(String, Option<String>),
+    Hex(String, Option<String>),
+    Octal(String, Option<String>),
+    Binary(String, Option<String>),
 
     // Textual
     Char(String),
@@ -224,10 +256,10 @@ impl fmt::Display for Literal {
         match self {
             Literal::Char(s) => write!(f, "character '{}'", s),
             Literal::Str(s) => write!(f, "string '{}'", s),
-            Literal::Decimal(s) => write!(f, "{}", s),
-            Literal::Hex(s) => write!(f, "0x{}", s),
-            Literal::Octal(s) => write!(f, "0o{}", s),
-            Literal::Binary(s) => write!(f, "0b{}", s),
+            Literal::Decimal(s, suffix) => write!(f, "{}{}", s, suffix.as_deref().unwrap_or("")),
+            Literal::Hex(s, suffix) => write!(f, "0x{}{}", s, suffix.as_deref().unwrap_or("")),
+            Literal::Octal(s, suffix) => write!(f, "0o{}{}", s, suffix.as_deref().unwrap_or("")),
+            Literal::Binary(s, suffix) => write!(f, "0b{}{}", s, suffix.as_deref().unwrap_or("")),
             Literal::True => write!(f, "true"),
             Literal::False => write!(f, "false"),
         }
@@ -102,19 +102,138 @@ impl std::fmt::Display for Type {
     }
 }
 
+impl Type {
+    /// Size and alignment in bytes of the types whose layout does not depend
+    /// on the C++ compiler, used where a constant is needed like array sizes
+    pub fn layout(&self) -> Option<(u64, u64)> {
+        self.layout_with(&|_| None)
+    }
+
+    /// Layout of the type where `fields` gives the field types of the structs
+    /// that are known. Tuples and structs are laid out like a C++ struct, every
+    /// member is padded to its alignment and the alignment is the largest one
+    pub fn layout_with(&self, fields: &dyn Fn(&str) -> Option<Vec<Type>>) -> Option<(u64, u64)> {
+        let size = match self {
+            Type::Bool(_) | Type::Char(_) | Type::I8(_) | Type::U8(_) => 1,
+            Type::I16(_) | Type::U16(_) => 2,
+            Type::I32(_) | Type::U32(_) | Type::Int(_) | Type::Uint(_) | Type::F32(_) => 4,
+            Type::I64(_) | Type::U64(_) | Type::F64(_) => 8,
+            Type::Array(ty, len, _) => {
+                let (size, align) = ty.layout_with(fields)?;
+
+                return Some((size * len, align));
+            }
+            Type::Tuple(types, _) => return members_layout(types, fields),
+            Type::Custom(name, _) => return members_layout(&fields(name)?, fields),
+            _ => return None,
+        };
+
+        Some((size, size))
+    }
+}
+
+/// Layout of a struct with the members `types`, an empty one still takes a byte
+fn members_layout(
+    types: &[Type],
+    fields: &dyn Fn(&str) -> Option<Vec<Type>>,
+) -> Option<(u64, u64)> {
+    let mut size: u64 = 0;
+    let mut align: u64 = 1;
+
+    for ty in types {
+        let (member_size, member_align) = ty.layout_with(fields)?;
+
+        size = size.next_multiple_of(member_align) + member_size;
+        align = align.max(member_align);
+    }
+
+    Some((size.max(1).next_multiple_of(align), align))
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
     // Boolean
     True(SimpleSpan),
     False(SimpleSpan),
 
-    // Numeric
-    Decimal(String, SimpleSpan),
-    Hex(String, SimpleSpan),
-    Octal(String, SimpleSpan),
-    Binary(String, SimpleSpan),
+    // Numeric, with the type of the suffix like `u8` in `255u8`
+    Decimal(String, Option<Type>, SimpleSpan),
+    Hex(String, Option<Type>, SimpleSpan),
+    Octal(String, Option<Type>, SimpleSpan),
+    Binary(String, Option<Type>, SimpleSpan),
 
     // Textual
     Char(String, SimpleSpan),
     Str(String, SimpleSpan),
 }
+
+#[cfg(test)]
+mod tests {
+    use chumsky::span::SimpleSpan;
+
+    use super::Type;
+
+    fn s() -> SimpleSpan {
+        SimpleSpan::new(0, 0)
+    }
+
+    #[test]
+    fn scalar_layouts() {
+        assert_eq!(Type::U8(s()).layout(), Some((1, 1)));
+        assert_eq!(Type::I32(s()).layout(), Some((4, 4)));
+        assert_eq!(Type::F64(s()).layout(), Some((8, 8)));
+    }
+
+    #[test]
+    fn array_layouts() {
+        assert_eq!(
+            Type::Array(Box::new(Type::U8(s())), 3, s()).layout(),
+            Some((3, 1))
+        );
+        assert_eq!(
+            Type::Array(Box::new(Type::U16(s())), 3, s()).layout(),
+            Some((6, 2))
+        );
+    }
+
+    #[test]
+    fn tuple_layouts() {
+        let tuple = |types: Vec<Type>| Type::Tuple(types, s()).layout();
+
+        assert_eq!(
+            tuple(vec![Type::U8(s()), Type::U8(s()), Type::U8(s())]),
+            Some((3, 1))
+        );
+        assert_eq!(tuple(vec![Type::U8(s()), Type::I32(s())]), Some((8, 4)));
+        assert_eq!(tuple(vec![Type::I64(s()), Type::U8(s())]), Some((16, 8)));
+        assert_eq!(
+            tuple(vec![
+                Type::U16(s()),
+                Type::Array(Box::new(Type::U8(s())), 3, s())
+            ]),
+            Some((6, 2))
+        );
+        assert_eq!(tuple(vec![]), Some((1, 1)));
+    }
+
+    #[test]
+    fn struct_layouts() {
+        let fields = |name: &str| match name {
+            "P" => Some(vec![Type::U8(s()), Type::U16(s()), Type::U8(s())]),
+            _ => None,
+        };
+        let layout = |ty: Type| ty.layout_with(&fields);
+
+        assert_eq!(layout(Type::Custom("P".to_owned(), s())), Some((6, 2)));
+        assert_eq!(
+            layout(Type::Array(
+                Box::new(Type::Custom("P".to_owned(), s())),
+                2,
+                s()
+            )),
+            Some((12, 2))
+        );
+        assert_eq!(layout(Type::Custom("Q".to_owned(), s())), None);
+        assert_eq!(Type::Custom("P".to_owned(), s()).layout(), None);
+    }
+}